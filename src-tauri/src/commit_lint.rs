@@ -0,0 +1,128 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+const SUBJECT_SOFT_LIMIT: usize = 50;
+const SUBJECT_HARD_LIMIT: usize = 72;
+const BODY_LINE_LIMIT: usize = 72;
+
+pub const CONVENTIONAL_TYPES: &[&str] = &[
+    "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintIssue {
+    pub rule: String,
+    /// "error" or "warning"
+    pub severity: String,
+    pub message: String,
+    /// 1-based line in the commit message
+    pub line: usize,
+}
+
+impl LintIssue {
+    fn new(rule: &str, severity: &str, line: usize, message: String) -> Self {
+        Self {
+            rule: rule.to_string(),
+            severity: severity.to_string(),
+            message,
+            line,
+        }
+    }
+}
+
+/// `type(scope)!: subject` with any word as the type; the type list is checked separately
+/// so we can tell "unknown type" apart from "no type at all"
+fn header_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^([a-zA-Z]+)(\([^()\s][^()]*\))?(!)?: (\S.*)$").unwrap())
+}
+
+/// Whether `subject` follows Conventional Commits with one of the standard types
+pub fn is_conventional_subject(subject: &str) -> bool {
+    header_regex()
+        .captures(subject.trim())
+        .map(|c| CONVENTIONAL_TYPES.contains(&&c[1]))
+        .unwrap_or(false)
+}
+
+/// Lint a full commit message (subject, blank line, body) in the style of commitlint
+pub fn lint(message: &str, conventional: bool) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let lines: Vec<&str> = message.trim_end().lines().collect();
+    let subject = lines.first().map(|s| s.trim_end()).unwrap_or("");
+
+    if subject.trim().is_empty() {
+        issues.push(LintIssue::new("subject-empty", "error", 1, "Subject line is empty".to_string()));
+        return issues;
+    }
+
+    let subject_len = subject.chars().count();
+    if subject_len > SUBJECT_HARD_LIMIT {
+        issues.push(LintIssue::new(
+            "subject-max-length",
+            "error",
+            1,
+            format!("Subject is {} characters; keep it under {}", subject_len, SUBJECT_HARD_LIMIT),
+        ));
+    } else if subject_len > SUBJECT_SOFT_LIMIT {
+        issues.push(LintIssue::new(
+            "subject-max-length",
+            "warning",
+            1,
+            format!("Subject is {} characters; {} or fewer reads best", subject_len, SUBJECT_SOFT_LIMIT),
+        ));
+    }
+    if subject.ends_with('.') {
+        issues.push(LintIssue::new(
+            "subject-full-stop",
+            "warning",
+            1,
+            "Subject should not end with a period".to_string(),
+        ));
+    }
+
+    if conventional {
+        match header_regex().captures(subject) {
+            None => issues.push(LintIssue::new(
+                "type-empty",
+                "error",
+                1,
+                "Subject should start with a type, e.g. \"feat: add login\" or \"fix(parser): handle nulls\"".to_string(),
+            )),
+            Some(caps) if !CONVENTIONAL_TYPES.contains(&&caps[1]) => issues.push(LintIssue::new(
+                "type-enum",
+                "error",
+                1,
+                format!("Unknown type \"{}\"; use one of {}", &caps[1], CONVENTIONAL_TYPES.join(", ")),
+            )),
+            Some(_) => {}
+        }
+    }
+
+    if let Some(second) = lines.get(1) {
+        if !second.trim().is_empty() {
+            issues.push(LintIssue::new(
+                "body-leading-blank",
+                "error",
+                2,
+                "Leave a blank line between the subject and the body".to_string(),
+            ));
+        }
+    }
+
+    for (i, line) in lines.iter().enumerate().skip(1) {
+        let len = line.chars().count();
+        // Long URLs and similar unbreakable tokens are fine
+        if len > BODY_LINE_LIMIT && line.trim().contains(' ') {
+            issues.push(LintIssue::new(
+                "body-max-line-length",
+                "warning",
+                i + 1,
+                format!("Line is {} characters; wrap body text at {}", len, BODY_LINE_LIMIT),
+            ));
+        }
+    }
+
+    issues
+}
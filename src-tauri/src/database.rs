@@ -1,7 +1,11 @@
 use crate::Project;
-use rusqlite::{Connection, params};
+use parking_lot::Mutex;
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{Connection, OpenFlags, TransactionBehavior, params};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LinkedDevice {
@@ -69,109 +73,681 @@ fn generate_passphrase() -> String {
         .join("-")
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub text: String,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalProfile {
+    pub name: String,
+    /// Empty means the user's default shell
+    #[serde(default)]
+    pub shell: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(rename = "isAssistant", default)]
+    pub is_assistant: bool,
+}
+
+/// An editor `open_file_in_editor` can launch. `{path}`, `{line}` and `{col}` in `args`
+/// are replaced when opening a file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditorConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Runs inside a terminal window (nvim, hx) rather than opening its own
+    #[serde(default)]
+    pub terminal: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalShortcut {
+    pub accelerator: String,
+    pub action: String,
+}
+
+/// Token totals for one provider in one calendar month
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiUsageTotal {
+    pub provider: String,
+    /// "YYYY-MM" in local time
+    pub month: String,
+    #[serde(rename = "promptTokens")]
+    pub prompt_tokens: u64,
+    #[serde(rename = "completionTokens")]
+    pub completion_tokens: u64,
+    pub requests: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseStats {
+    pub path: String,
+    #[serde(rename = "fileSize")]
+    pub file_size: u64,
+    #[serde(rename = "walSize")]
+    pub wal_size: u64,
+    #[serde(rename = "freePages")]
+    pub free_pages: i64,
+    #[serde(rename = "pageSize")]
+    pub page_size: i64,
+    #[serde(rename = "tableRows")]
+    pub table_rows: Vec<(String, i64)>,
+    #[serde(rename = "integrityOk")]
+    pub integrity_ok: bool,
+}
+
+/// Schema migrations in order; the database's `user_version` pragma is how many have run.
+/// Only ever append: shipped entries must not be edited or reordered. The early ones are
+/// idempotent because databases from before versioning already have some of their changes.
+const MIGRATIONS: &[fn(&Connection) -> rusqlite::Result<()>] = &[
+    migrate_projects,
+    migrate_project_shell_settings,
+    migrate_portal_and_templates,
+    migrate_terminal_profiles_and_shortcuts,
+    migrate_ai_usage,
+    migrate_editors,
+    migrate_settings,
+    migrate_project_tags,
+    migrate_projects_revision,
+];
+
+/// Latest schema version this build knows how to use
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+fn schema_version(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Bring the schema up to `SCHEMA_VERSION`, one transaction per migration. Refuses to touch
+/// a database written by a newer Orca rather than risk corrupting it.
+fn run_migrations(conn: &mut Connection) -> Result<(), String> {
+    let current = schema_version(conn).map_err(|e| e.to_string())?;
+    if current > SCHEMA_VERSION {
+        return Err(format!(
+            "Database schema version {} is newer than this version of Orca supports ({}). Please update Orca.",
+            current, SCHEMA_VERSION
+        ));
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        let version = index as i64 + 1;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        migration(&tx).map_err(|e| format!("Migration to schema version {} failed: {}", version, e))?;
+        tx.pragma_update(None, "user_version", version).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        log::info!("[Database] Migrated schema to version {}", version);
+    }
+    Ok(())
+}
+
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> rusqlite::Result<()> {
+    let exists: bool = conn.query_row(
+        &format!("SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?1", table),
+        params![column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
+
+fn migrate_projects(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS projects (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            path TEXT NOT NULL,
+            last_opened TEXT NOT NULL,
+            folders TEXT
+        )",
+        [],
+    )?;
+    add_column_if_missing(conn, "projects", "folders", "TEXT")?;
+
+    // Clean up duplicate projects (keep the most recently opened one for each path)
+    conn.execute(
+        "DELETE FROM projects WHERE id NOT IN (
+            SELECT id FROM (
+                SELECT id, ROW_NUMBER() OVER (PARTITION BY path ORDER BY last_opened DESC) as rn
+                FROM projects
+            ) WHERE rn = 1
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_projects_path ON projects(path)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn migrate_project_shell_settings(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "projects", "default_shell", "TEXT")?;
+    add_column_if_missing(conn, "projects", "startup_commands", "TEXT")?;
+    add_column_if_missing(conn, "projects", "env", "TEXT")
+}
+
+fn migrate_portal_and_templates(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS portal_config (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS prompt_templates (
+            name TEXT PRIMARY KEY,
+            text TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn migrate_terminal_profiles_and_shortcuts(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS terminal_profiles (
+            name TEXT PRIMARY KEY,
+            shell TEXT NOT NULL,
+            args TEXT NOT NULL,
+            cwd TEXT,
+            env TEXT NOT NULL,
+            is_assistant INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS global_shortcuts (
+            accelerator TEXT PRIMARY KEY,
+            action TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn migrate_ai_usage(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ai_usage (
+            provider TEXT NOT NULL,
+            month TEXT NOT NULL,
+            prompt_tokens INTEGER NOT NULL DEFAULT 0,
+            completion_tokens INTEGER NOT NULL DEFAULT 0,
+            requests INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (provider, month)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn migrate_editors(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS editors (
+            name TEXT PRIMARY KEY,
+            command TEXT NOT NULL,
+            args TEXT NOT NULL,
+            terminal INTEGER NOT NULL DEFAULT 0,
+            position INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn migrate_settings(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn migrate_project_tags(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "projects", "tags", "TEXT")?;
+    add_column_if_missing(conn, "projects", "pinned", "INTEGER NOT NULL DEFAULT 0")
+}
+
+/// A counter bumped by triggers on every change to `projects`, from any connection or
+/// process, so the database watcher can ignore writes to other tables
+fn migrate_projects_revision(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS projects_revision (
+            id INTEGER PRIMARY KEY CHECK (id = 0),
+            revision INTEGER NOT NULL
+         );
+         INSERT OR IGNORE INTO projects_revision (id, revision) VALUES (0, 0);
+         CREATE TRIGGER IF NOT EXISTS projects_revision_insert AFTER INSERT ON projects
+         BEGIN UPDATE projects_revision SET revision = revision + 1; END;
+         CREATE TRIGGER IF NOT EXISTS projects_revision_update AFTER UPDATE ON projects
+         BEGIN UPDATE projects_revision SET revision = revision + 1; END;
+         CREATE TRIGGER IF NOT EXISTS projects_revision_delete AFTER DELETE ON projects
+         BEGIN UPDATE projects_revision SET revision = revision + 1; END;",
+    )
+}
+
+fn projects_revision(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row("SELECT revision FROM projects_revision WHERE id = 0", [], |row| row.get(0))
+}
+
+const POOL_SIZE: u32 = 4;
+/// Own revision steps kept for the watcher. It polls every second, so it's never far behind;
+/// this only bounds the map when nothing is watching.
+const OWN_PROJECT_CHANGES_KEPT: i64 = 1000;
+
 pub struct Database {
-    conn: Connection,
+    path: PathBuf,
+    pool: Pool<SqliteConnectionManager>,
+    // Dedicated connection for change detection, so polling never waits on the pool
+    watch_conn: Mutex<Connection>,
+    /// `projects_revision` steps made by this process's own writes, old revision to new
+    own_project_changes: Mutex<HashMap<i64, i64>>,
 }
 
 impl Database {
     pub fn new(path: PathBuf) -> Result<Self, String> {
-        let conn = Connection::open(&path).map_err(|e| e.to_string())?;
+        // WAL lets readers proceed while a write is in progress; busy_timeout makes
+        // concurrent writers wait for the lock instead of failing immediately
+        let manager = SqliteConnectionManager::file(&path).with_init(|c| {
+            c.execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 PRAGMA synchronous = NORMAL;
+                 PRAGMA busy_timeout = 5000;",
+            )
+        });
+        let pool = Pool::builder()
+            .max_size(POOL_SIZE)
+            .build(manager)
+            .map_err(|e| e.to_string())?;
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        run_migrations(&mut conn)?;
+        drop(conn);
+        let watch_conn = Connection::open(&path).map_err(|e| e.to_string())?;
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS projects (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                path TEXT NOT NULL,
-                last_opened TEXT NOT NULL,
-                folders TEXT
-            )",
-            [],
-        )
-        .map_err(|e| e.to_string())?;
+        Ok(Self {
+            path,
+            pool,
+            watch_conn: Mutex::new(watch_conn),
+            own_project_changes: Mutex::new(HashMap::new()),
+        })
+    }
 
-        // Migration: Add folders column if it doesn't exist
-        conn.execute(
-            "ALTER TABLE projects ADD COLUMN folders TEXT",
-            [],
-        )
-        .ok(); // Ignore if column already exists
+    fn conn(&self) -> Result<PooledConnection<SqliteConnectionManager>, String> {
+        self.pool.get().map_err(|e| e.to_string())
+    }
+
+    /// Run a write to `projects` in one immediate transaction, recording the revision step
+    /// it makes so the watcher can tell it apart from another instance's changes
+    fn write_projects<T>(&self, write: impl FnOnce(&Connection) -> Result<T, String>) -> Result<T, String> {
+        let mut conn = self.conn()?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|e| e.to_string())?;
+        let before = projects_revision(&tx).map_err(|e| e.to_string())?;
+        let result = write(&tx)?;
+        let after = projects_revision(&tx).map_err(|e| e.to_string())?;
+        // Recorded before the commit so the watcher can never see the new revision first
+        if after != before {
+            let mut own = self.own_project_changes.lock();
+            own.retain(|&step, _| before - step < OWN_PROJECT_CHANGES_KEPT);
+            own.insert(before, after);
+        }
+        if let Err(e) = tx.commit() {
+            self.own_project_changes.lock().remove(&before);
+            return Err(e.to_string());
+        }
+        Ok(result)
+    }
 
+    /// Path of a restore staged by `stage_restore`, applied on next launch
+    fn pending_restore_path(path: &Path) -> PathBuf {
+        path.with_extension("db.restore")
+    }
+
+    /// Swap in a database staged by `stage_restore`. Must run before `Database::new`
+    /// opens the file.
+    pub fn apply_pending_restore(path: &Path) -> Result<bool, String> {
+        let pending = Self::pending_restore_path(path);
+        if !pending.exists() {
+            return Ok(false);
+        }
+        // Stale WAL/SHM files belong to the old database and would corrupt the new one
+        for ext in ["db-wal", "db-shm"] {
+            let _ = std::fs::remove_file(path.with_extension(ext));
+        }
+        std::fs::rename(&pending, path).map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    /// Check that a file is an intact SQLite database with Orca's schema
+    pub fn validate_file(path: &Path) -> Result<(), String> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| format!("Not a valid database: {}", e))?;
+
+        let integrity: String = conn
+            .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+            .map_err(|e| format!("Not a valid database: {}", e))?;
+        if integrity != "ok" {
+            return Err(format!("Database integrity check failed: {}", integrity));
+        }
+
+        let version = schema_version(&conn).map_err(|e| e.to_string())?;
+        if version > SCHEMA_VERSION {
+            return Err(format!(
+                "Database was written by a newer version of Orca (schema {}, this build supports {})",
+                version, SCHEMA_VERSION
+            ));
+        }
+
+        for table in ["projects", "portal_config"] {
+            let exists: bool = conn
+                .query_row(
+                    "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                    params![table],
+                    |row| row.get(0),
+                )
+                .map_err(|e| e.to_string())?;
+            if !exists {
+                return Err(format!("Not an Orca database: missing '{}' table", table));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Result<DatabaseStats, String> {
+        let conn = self.conn()?;
+
+        let tables: Vec<String> = {
+            let mut stmt = conn
+                .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+        };
+
+        let mut table_rows = Vec::new();
+        for table in tables {
+            // Table names come from sqlite_master, quote them for identifiers with odd characters
+            let count: i64 = conn
+                .query_row(&format!("SELECT COUNT(*) FROM \"{}\"", table.replace('"', "\"\"")), [], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            table_rows.push((table, count));
+        }
+
+        let page_size: i64 = conn
+            .query_row("PRAGMA page_size", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        let free_pages: i64 = conn
+            .query_row("PRAGMA freelist_count", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        let integrity: String = conn
+            .query_row("PRAGMA quick_check", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+
+        let file_size = std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        let wal_size = std::fs::metadata(self.path.with_extension("db-wal")).map(|m| m.len()).unwrap_or(0);
+
+        Ok(DatabaseStats {
+            path: self.path.to_string_lossy().to_string(),
+            file_size,
+            wal_size,
+            free_pages,
+            page_size,
+            table_rows,
+            integrity_ok: integrity == "ok",
+        })
+    }
+
+    /// Rebuild the database file to reclaim free pages. Blocks other writers while it runs.
+    pub fn vacuum(&self) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+            .map_err(|e| format!("Vacuum failed: {}", e))
+    }
+
+    /// Online backup to `dest`, safe while the app is using the database
+    pub fn backup_to(&self, dest: &Path) -> Result<(), String> {
+        self.conn()?
+            .backup(rusqlite::MAIN_DB, dest, None)
+            .map_err(|e| format!("Backup failed: {}", e))
+    }
+
+    /// Backup for moving to another machine. Portal pairing (device id, pairing code and
+    /// passphrase, linked devices and their session tokens) is left out of the copy, and so
+    /// are project and terminal profile environment variables, which often hold API keys,
+    /// unless `include_env` is set.
+    pub fn export_to(&self, dest: &Path, include_env: bool) -> Result<(), String> {
+        self.backup_to(dest)?;
+        let exported = Connection::open(dest).map_err(|e| e.to_string())?;
+        if !include_env {
+            exported
+                .execute_batch(
+                    "UPDATE projects SET env = NULL;
+                     UPDATE terminal_profiles SET env = '{}';",
+                )
+                .map_err(|e| e.to_string())?;
+        }
+        let portal: Option<String> = exported
+            .query_row("SELECT value FROM portal_config WHERE key = 'config'", [], |row| row.get(0))
+            .ok();
+        if let Some(config) = portal.and_then(|v| serde_json::from_str::<PortalConfig>(&v).ok()) {
+            let scrubbed = PortalConfig {
+                is_enabled: config.is_enabled,
+                relay_url: config.relay_url,
+                ..PortalConfig::default()
+            };
+            let value = serde_json::to_string(&scrubbed).map_err(|e| e.to_string())?;
+            exported
+                .execute("UPDATE portal_config SET value = ?1 WHERE key = 'config'", params![value])
+                .map_err(|e| e.to_string())?;
+        }
+        // The scrubbed values would otherwise still be readable in the file's free pages
+        exported.execute_batch("VACUUM;").map_err(|e| e.to_string())
+    }
+
+    /// Replace the live database's contents with `src` in place, without a restart. The
+    /// import is migrated to the current schema, and this machine's Portal pairing is kept.
+    pub fn import_from(&self, src: &Path) -> Result<(), String> {
+        Self::validate_file(src)?;
+        let portal = self.get_portal_config()?;
+        let mut conn = self.conn()?;
+        // Check out the rest of the pool too, so no other command reads or writes mid-restore,
+        // and hold off the watcher until the revision below is in place
+        let drained = (1..POOL_SIZE).map(|_| self.conn()).collect::<Result<Vec<_>, _>>()?;
+        let watch_conn = self.watch_conn.lock();
+        let before = projects_revision(&conn).map_err(|e| e.to_string())?;
+        conn.restore(rusqlite::MAIN_DB, src, None::<fn(rusqlite::backup::Progress)>)
+            .map_err(|e| format!("Import failed: {}", e))?;
+        conn.execute_batch("PRAGMA journal_mode = WAL;").map_err(|e| e.to_string())?;
+        run_migrations(&mut conn)?;
+        // The import brings its own revision; carry on from ours so the watcher sees one step
         conn.execute(
-            "CREATE TABLE IF NOT EXISTS portal_config (
-                key TEXT PRIMARY KEY,
-                value TEXT NOT NULL
-            )",
-            [],
+            "UPDATE projects_revision SET revision = ?1 WHERE id = 0",
+            params![before + 1],
         )
         .map_err(|e| e.to_string())?;
+        self.own_project_changes.lock().insert(before, before + 1);
+        drop(watch_conn);
+        drop(drained);
+        drop(conn);
+        self.set_portal_config(&portal)
+    }
 
-        // Clean up duplicate projects (keep the most recently opened one for each path)
-        conn.execute(
-            "DELETE FROM projects WHERE id NOT IN (
-                SELECT id FROM (
-                    SELECT id, ROW_NUMBER() OVER (PARTITION BY path ORDER BY last_opened DESC) as rn
-                    FROM projects
-                ) WHERE rn = 1
-            )",
-            [],
-        )
-        .ok(); // Ignore errors if table is empty or query fails
+    /// Validate `src` and stage it to replace the live database on next launch
+    pub fn stage_restore(&self, src: &Path) -> Result<(), String> {
+        Self::validate_file(src)?;
+        std::fs::copy(src, Self::pending_restore_path(&self.path))
+            .map_err(|e| format!("Failed to stage restore: {}", e))?;
+        Ok(())
+    }
 
-        // Create unique index on path if it doesn't exist
-        conn.execute(
-            "CREATE UNIQUE INDEX IF NOT EXISTS idx_projects_path ON projects(path)",
-            [],
-        )
-        .ok(); // Ignore if already exists
+    /// Build a `Project` from a row selected as
+    /// `id, name, path, last_opened, folders, default_shell, startup_commands, env, tags, pinned`
+    fn project_from_row(row: &rusqlite::Row) -> rusqlite::Result<Project> {
+        let folders_json: Option<String> = row.get(4).ok();
+        let folders = folders_json.and_then(|json| serde_json::from_str(&json).ok());
+        let startup_json: Option<String> = row.get(6).ok().flatten();
+        let env_json: Option<String> = row.get(7).ok().flatten();
+        let tags_json: Option<String> = row.get(8).ok().flatten();
 
-        Ok(Self { conn })
+        Ok(Project {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            path: row.get(2)?,
+            last_opened: row.get(3)?,
+            folders,
+            default_shell: row.get(5).ok().flatten(),
+            startup_commands: startup_json
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            env: env_json
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            tags: tags_json
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            pinned: row.get(9).unwrap_or(false),
+        })
     }
 
+    /// Insert or update a project. Tags and pinning on an existing project are left alone;
+    /// they change only through `set_project_tags` and `set_project_pinned`.
     pub fn add_project(&self, project: &Project) -> Result<(), String> {
         // Serialize folders to JSON
         let folders_json = project.folders.as_ref()
             .map(|f| serde_json::to_string(f).unwrap_or_default());
+        let startup_json = serde_json::to_string(&project.startup_commands).unwrap_or_default();
+        let env_json = serde_json::to_string(&project.env).unwrap_or_default();
+        let tags_json = serde_json::to_string(&project.tags).unwrap_or_default();
+
+        self.write_projects(|conn| {
+            // Check if project with same path already exists
+            let existing_id: Option<String> = conn
+                .query_row(
+                    "SELECT id FROM projects WHERE path = ?1",
+                    params![project.path],
+                    |row| row.get(0),
+                )
+                .ok();
+
+            if let Some(existing) = existing_id {
+                // Update existing project by path. Shell settings, tags and pinned have their own
+                // setters; the frontend's Project doesn't carry them and would wipe them here.
+                conn
+                    .execute(
+                        "UPDATE projects SET name = ?1, last_opened = ?2, folders = ?3 WHERE id = ?4",
+                        params![project.name, project.last_opened, folders_json, existing],
+                    )
+                    .map_err(|e| e.to_string())?;
+            } else {
+                // Insert new project
+                conn
+                    .execute(
+                        "INSERT INTO projects (id, name, path, last_opened, folders, default_shell, startup_commands, env, tags, pinned) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                        params![project.id, project.name, project.path, project.last_opened, folders_json, project.default_shell, startup_json, env_json, tags_json, project.pinned],
+                    )
+                    .map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        })
+    }
 
-        // Check if project with same path already exists
-        let existing_id: Option<String> = self.conn
-            .query_row(
-                "SELECT id FROM projects WHERE path = ?1",
-                params![project.path],
-                |row| row.get(0),
+    pub fn set_project_shell_settings(
+        &self,
+        id: &str,
+        default_shell: Option<&str>,
+        startup_commands: &[String],
+        env: &HashMap<String, String>,
+    ) -> Result<(), String> {
+        let startup_json = serde_json::to_string(startup_commands).unwrap_or_default();
+        let env_json = serde_json::to_string(env).unwrap_or_default();
+        let updated = self.write_projects(|conn| {
+            conn.execute(
+                "UPDATE projects SET default_shell = ?1, startup_commands = ?2, env = ?3 WHERE id = ?4",
+                params![default_shell, startup_json, env_json, id],
             )
-            .ok();
+            .map_err(|e| e.to_string())
+        })?;
+        if updated == 0 {
+            return Err(format!("Project not found: {}", id));
+        }
+        Ok(())
+    }
 
-        if let Some(existing) = existing_id {
-            // Update existing project by path
-            self.conn
-                .execute(
-                    "UPDATE projects SET name = ?1, last_opened = ?2, folders = ?3 WHERE id = ?4",
-                    params![project.name, project.last_opened, folders_json, existing],
-                )
-                .map_err(|e| e.to_string())?;
-        } else {
-            // Insert new project
-            self.conn
-                .execute(
-                    "INSERT INTO projects (id, name, path, last_opened, folders) VALUES (?1, ?2, ?3, ?4, ?5)",
-                    params![project.id, project.name, project.path, project.last_opened, folders_json],
-                )
-                .map_err(|e| e.to_string())?;
+    pub fn set_project_tags(&self, id: &str, tags: &[String]) -> Result<(), String> {
+        let tags_json = serde_json::to_string(tags).unwrap_or_default();
+        let updated = self.write_projects(|conn| {
+            conn.execute("UPDATE projects SET tags = ?1 WHERE id = ?2", params![tags_json, id])
+                .map_err(|e| e.to_string())
+        })?;
+        if updated == 0 {
+            return Err(format!("Project not found: {}", id));
         }
         Ok(())
     }
 
-    pub fn remove_project(&self, id: &str) -> Result<(), String> {
-        self.conn
-            .execute("DELETE FROM projects WHERE id = ?1", params![id])
+    pub fn set_project_pinned(&self, id: &str, pinned: bool) -> Result<(), String> {
+        let updated = self.write_projects(|conn| {
+            conn.execute("UPDATE projects SET pinned = ?1 WHERE id = ?2", params![pinned, id])
+                .map_err(|e| e.to_string())
+        })?;
+        if updated == 0 {
+            return Err(format!("Project not found: {}", id));
+        }
+        Ok(())
+    }
+
+    /// Projects carrying `tag`, pinned first then most recently opened
+    pub fn get_projects_by_tag(&self, tag: &str) -> Result<Vec<Project>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, name, path, last_opened, folders, default_shell, startup_commands, env, tags, pinned FROM projects
+                 WHERE EXISTS (SELECT 1 FROM json_each(projects.tags) WHERE json_each.value = ?1)
+                 ORDER BY pinned DESC, last_opened DESC",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map(params![tag], Self::project_from_row)
             .map_err(|e| e.to_string())?;
+
+        let mut projects = Vec::new();
+        for row in rows {
+            projects.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok(projects)
+    }
+
+    pub fn remove_project(&self, id: &str) -> Result<(), String> {
+        self.write_projects(|conn| {
+            conn.execute("DELETE FROM projects WHERE id = ?1", params![id])
+                .map_err(|e| e.to_string())
+        })?;
         Ok(())
     }
 
     pub fn get_project(&self, id: &str) -> Result<Option<Project>, String> {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT id, name, path, last_opened, folders FROM projects WHERE id = ?1")
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT id, name, path, last_opened, folders, default_shell, startup_commands, env, tags, pinned FROM projects WHERE id = ?1")
             .map_err(|e| e.to_string())?;
 
         let mut rows = stmt
@@ -179,40 +755,20 @@ impl Database {
             .map_err(|e| e.to_string())?;
 
         if let Some(row) = rows.next().map_err(|e| e.to_string())? {
-            let folders_json: Option<String> = row.get(4).ok();
-            let folders = folders_json.and_then(|json| serde_json::from_str(&json).ok());
-
-            Ok(Some(Project {
-                id: row.get(0).map_err(|e| e.to_string())?,
-                name: row.get(1).map_err(|e| e.to_string())?,
-                path: row.get(2).map_err(|e| e.to_string())?,
-                last_opened: row.get(3).map_err(|e| e.to_string())?,
-                folders,
-            }))
+            Ok(Some(Self::project_from_row(row).map_err(|e| e.to_string())?))
         } else {
             Ok(None)
         }
     }
 
     pub fn get_all_projects(&self) -> Result<Vec<Project>, String> {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT id, name, path, last_opened, folders FROM projects ORDER BY last_opened DESC")
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT id, name, path, last_opened, folders, default_shell, startup_commands, env, tags, pinned FROM projects ORDER BY pinned DESC, last_opened DESC")
             .map_err(|e| e.to_string())?;
 
         let rows = stmt
-            .query_map([], |row| {
-                let folders_json: Option<String> = row.get(4).ok();
-                let folders = folders_json.and_then(|json| serde_json::from_str(&json).ok());
-
-                Ok(Project {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    path: row.get(2)?,
-                    last_opened: row.get(3)?,
-                    folders,
-                })
-            })
+            .query_map([], Self::project_from_row)
             .map_err(|e| e.to_string())?;
 
         let mut projects = Vec::new();
@@ -223,9 +779,311 @@ impl Database {
         Ok(projects)
     }
 
+    pub fn save_prompt_template(&self, name: &str, text: &str) -> Result<(), String> {
+        self.conn()?
+            .execute(
+                "INSERT OR REPLACE INTO prompt_templates (name, text, updated_at) VALUES (?1, ?2, ?3)",
+                params![name, text, chrono::Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn get_prompt_templates(&self) -> Result<Vec<PromptTemplate>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT name, text, updated_at FROM prompt_templates ORDER BY name")
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(PromptTemplate {
+                    name: row.get(0)?,
+                    text: row.get(1)?,
+                    updated_at: row.get(2)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut templates = Vec::new();
+        for row in rows {
+            templates.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok(templates)
+    }
+
+    pub fn get_prompt_template(&self, name: &str) -> Result<Option<String>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT text FROM prompt_templates WHERE name = ?1")
+            .map_err(|e| e.to_string())?;
+        let mut rows = stmt.query(params![name]).map_err(|e| e.to_string())?;
+        match rows.next().map_err(|e| e.to_string())? {
+            Some(row) => Ok(Some(row.get(0).map_err(|e| e.to_string())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn delete_prompt_template(&self, name: &str) -> Result<(), String> {
+        self.conn()?
+            .execute("DELETE FROM prompt_templates WHERE name = ?1", params![name])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn terminal_profile_from_row(row: &rusqlite::Row) -> rusqlite::Result<TerminalProfile> {
+        let args: String = row.get(2)?;
+        let env: String = row.get(4)?;
+        Ok(TerminalProfile {
+            name: row.get(0)?,
+            shell: row.get(1)?,
+            args: serde_json::from_str(&args).unwrap_or_default(),
+            cwd: row.get(3)?,
+            env: serde_json::from_str(&env).unwrap_or_default(),
+            is_assistant: row.get(5)?,
+        })
+    }
+
+    pub fn save_terminal_profile(&self, profile: &TerminalProfile) -> Result<(), String> {
+        let args_json = serde_json::to_string(&profile.args).unwrap_or_default();
+        let env_json = serde_json::to_string(&profile.env).unwrap_or_default();
+        self.conn()?
+            .execute(
+                "INSERT OR REPLACE INTO terminal_profiles (name, shell, args, cwd, env, is_assistant) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![profile.name, profile.shell, args_json, profile.cwd, env_json, profile.is_assistant],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn get_terminal_profiles(&self) -> Result<Vec<TerminalProfile>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT name, shell, args, cwd, env, is_assistant FROM terminal_profiles ORDER BY name")
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map([], Self::terminal_profile_from_row)
+            .map_err(|e| e.to_string())?;
+
+        let mut profiles = Vec::new();
+        for row in rows {
+            profiles.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok(profiles)
+    }
+
+    pub fn get_terminal_profile(&self, name: &str) -> Result<Option<TerminalProfile>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT name, shell, args, cwd, env, is_assistant FROM terminal_profiles WHERE name = ?1")
+            .map_err(|e| e.to_string())?;
+        let mut rows = stmt.query(params![name]).map_err(|e| e.to_string())?;
+        match rows.next().map_err(|e| e.to_string())? {
+            Some(row) => Ok(Some(Self::terminal_profile_from_row(row).map_err(|e| e.to_string())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn delete_terminal_profile(&self, name: &str) -> Result<(), String> {
+        self.conn()?
+            .execute("DELETE FROM terminal_profiles WHERE name = ?1", params![name])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Editors in the order `open_file_in_editor` tries them. Empty until the user saves a list.
+    pub fn get_editors(&self) -> Result<Vec<EditorConfig>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT name, command, args, terminal FROM editors ORDER BY position")
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                let args: String = row.get(2)?;
+                Ok(EditorConfig {
+                    name: row.get(0)?,
+                    command: row.get(1)?,
+                    args: serde_json::from_str(&args).unwrap_or_default(),
+                    terminal: row.get(3)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut editors = Vec::new();
+        for row in rows {
+            editors.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok(editors)
+    }
+
+    /// Replace the editor list; order is preserved
+    pub fn set_editors(&self, editors: &[EditorConfig]) -> Result<(), String> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM editors", []).map_err(|e| e.to_string())?;
+        for (position, editor) in editors.iter().enumerate() {
+            let args_json = serde_json::to_string(&editor.args).unwrap_or_default();
+            tx.execute(
+                "INSERT OR REPLACE INTO editors (name, command, args, terminal, position) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![editor.name, editor.command, args_json, editor.terminal, position as i64],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    pub fn get_global_shortcuts(&self) -> Result<Vec<GlobalShortcut>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT accelerator, action FROM global_shortcuts ORDER BY accelerator")
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(GlobalShortcut {
+                    accelerator: row.get(0)?,
+                    action: row.get(1)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut shortcuts = Vec::new();
+        for row in rows {
+            shortcuts.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok(shortcuts)
+    }
+
+    pub fn save_global_shortcut(&self, accelerator: &str, action: &str) -> Result<(), String> {
+        self.conn()?
+            .execute(
+                "INSERT OR REPLACE INTO global_shortcuts (accelerator, action) VALUES (?1, ?2)",
+                params![accelerator, action],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn delete_global_shortcut(&self, accelerator: &str) -> Result<(), String> {
+        self.conn()?
+            .execute("DELETE FROM global_shortcuts WHERE accelerator = ?1", params![accelerator])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Add one request's tokens to this month's running total for `provider`
+    pub fn record_ai_usage(&self, provider: &str, prompt_tokens: u64, completion_tokens: u64) -> Result<(), String> {
+        let month = chrono::Local::now().format("%Y-%m").to_string();
+        self.conn()?
+            .execute(
+                "INSERT INTO ai_usage (provider, month, prompt_tokens, completion_tokens, requests)
+                 VALUES (?1, ?2, ?3, ?4, 1)
+                 ON CONFLICT(provider, month) DO UPDATE SET
+                    prompt_tokens = prompt_tokens + excluded.prompt_tokens,
+                    completion_tokens = completion_tokens + excluded.completion_tokens,
+                    requests = requests + 1",
+                params![provider, month, prompt_tokens as i64, completion_tokens as i64],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Usage totals, newest month first. `month` ("YYYY-MM") limits it to one month.
+    pub fn get_ai_usage(&self, month: Option<&str>) -> Result<Vec<AiUsageTotal>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT provider, month, prompt_tokens, completion_tokens, requests FROM ai_usage
+                 WHERE ?1 IS NULL OR month = ?1
+                 ORDER BY month DESC, provider",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map(params![month], |row| {
+                Ok(AiUsageTotal {
+                    provider: row.get(0)?,
+                    month: row.get(1)?,
+                    prompt_tokens: row.get::<_, i64>(2)? as u64,
+                    completion_tokens: row.get::<_, i64>(3)? as u64,
+                    requests: row.get::<_, i64>(4)? as u64,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut totals = Vec::new();
+        for row in rows {
+            totals.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok(totals)
+    }
+
+    /// Current `projects_revision`. It moves on every change to the projects table, whether
+    /// from this process or a second Orca instance.
+    pub fn projects_revision(&self) -> Result<i64, String> {
+        projects_revision(&self.watch_conn.lock()).map_err(|e| e.to_string())
+    }
+
+    /// Whether every step from revision `from` to `to` was made by this process's own writes.
+    /// Steps at or before `to` are forgotten either way.
+    pub fn is_own_projects_change(&self, from: i64, to: i64) -> bool {
+        let mut own = self.own_project_changes.lock();
+        let mut at = from;
+        while at != to {
+            match own.get(&at) {
+                Some(&next) => at = next,
+                None => break,
+            }
+        }
+        own.retain(|&before, _| before >= to);
+        at == to
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT value FROM settings WHERE key = ?1")
+            .map_err(|e| e.to_string())?;
+        let mut rows = stmt.query(params![key]).map_err(|e| e.to_string())?;
+        match rows.next().map_err(|e| e.to_string())? {
+            Some(row) => Ok(Some(row.get(0).map_err(|e| e.to_string())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<(), String> {
+        self.conn()?
+            .execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn get_all_settings(&self) -> Result<HashMap<String, String>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT key, value FROM settings")
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?;
+
+        let mut settings = HashMap::new();
+        for row in rows {
+            let (key, value) = row.map_err(|e| e.to_string())?;
+            settings.insert(key, value);
+        }
+        Ok(settings)
+    }
+
     pub fn get_portal_config(&self) -> Result<PortalConfig, String> {
-        let mut stmt = self
-            .conn
+        let conn = self.conn()?;
+        let mut stmt = conn
             .prepare("SELECT value FROM portal_config WHERE key = 'config'")
             .map_err(|e| e.to_string())?;
 
@@ -244,7 +1102,7 @@ impl Database {
 
     pub fn set_portal_config(&self, config: &PortalConfig) -> Result<(), String> {
         let value = serde_json::to_string(config).map_err(|e| e.to_string())?;
-        self.conn
+        self.conn()?
             .execute(
                 "INSERT OR REPLACE INTO portal_config (key, value) VALUES ('config', ?1)",
                 params![value],
@@ -253,3 +1111,130 @@ impl Database {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_pre_versioning_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orca.db");
+        {
+            // Schema from before migrations were versioned: no folders column, no
+            // user_version, and a duplicate path left over from an old bug
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE projects (
+                    id TEXT PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    path TEXT NOT NULL,
+                    last_opened TEXT NOT NULL
+                 );
+                 INSERT INTO projects VALUES ('old', 'app', '/src/app', '2024-01-01T00:00:00Z');
+                 INSERT INTO projects VALUES ('new', 'app', '/src/app', '2024-06-01T00:00:00Z');",
+            )
+            .unwrap();
+        }
+
+        let db = Database::new(path.clone()).unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        let projects = db.get_all_projects().unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].id, "new");
+        assert!(projects[0].folders.is_none());
+        assert!(projects[0].tags.is_empty());
+        assert!(!projects[0].pinned);
+
+        db.set_project_tags("new", &["work".to_string()]).unwrap();
+        db.set_setting("theme", "dark").unwrap();
+        assert_eq!(db.get_setting("theme").unwrap().as_deref(), Some("dark"));
+        assert_eq!(db.get_projects_by_tag("work").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn refuses_database_from_newer_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orca.db");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+        }
+        assert!(Database::new(path).is_err());
+    }
+
+    #[test]
+    fn export_leaves_out_env_unless_asked_and_imports_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().join("orca.db")).unwrap();
+        db.conn()
+            .unwrap()
+            .execute(
+                "INSERT INTO projects (id, name, path, last_opened) VALUES ('p', 'app', '/src/app', '2024-01-01T00:00:00Z')",
+                [],
+            )
+            .unwrap();
+        let env = HashMap::from([("API_KEY".to_string(), "secret".to_string())]);
+        db.set_project_shell_settings("p", None, &[], &env).unwrap();
+        db.save_terminal_profile(&TerminalProfile {
+            name: "deploy".to_string(),
+            shell: String::new(),
+            args: Vec::new(),
+            cwd: None,
+            env: env.clone(),
+            is_assistant: false,
+        })
+        .unwrap();
+
+        let scrubbed = dir.path().join("scrubbed.db");
+        db.export_to(&scrubbed, false).unwrap();
+        let with_env = dir.path().join("with-env.db");
+        db.export_to(&with_env, true).unwrap();
+
+        let other = Database::new(dir.path().join("other.db")).unwrap();
+        other.import_from(&scrubbed).unwrap();
+        assert_eq!(other.get_all_projects().unwrap()[0].path, "/src/app");
+        assert!(other.get_all_projects().unwrap()[0].env.is_empty());
+        assert!(other.get_terminal_profile("deploy").unwrap().unwrap().env.is_empty());
+
+        other.import_from(&with_env).unwrap();
+        assert_eq!(other.get_all_projects().unwrap()[0].env, env);
+        assert_eq!(other.get_terminal_profile("deploy").unwrap().unwrap().env, env);
+    }
+
+    #[test]
+    fn projects_revision_tells_own_writes_from_another_instance() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orca.db");
+        let db = Database::new(path.clone()).unwrap();
+        let other_instance = Connection::open(&path).unwrap();
+        let insert = |id: &str| {
+            other_instance
+                .execute(
+                    "INSERT INTO projects (id, name, path, last_opened) VALUES (?1, ?1, ?1, '2024-01-01T00:00:00Z')",
+                    params![id],
+                )
+                .unwrap();
+        };
+
+        let start = db.projects_revision().unwrap();
+        db.set_setting("theme", "dark").unwrap();
+        assert_eq!(db.projects_revision().unwrap(), start);
+
+        insert("a");
+        let after_external = db.projects_revision().unwrap();
+        assert!(!db.is_own_projects_change(start, after_external));
+
+        db.set_project_pinned("a", true).unwrap();
+        let after_own = db.projects_revision().unwrap();
+        assert!(db.is_own_projects_change(after_external, after_own));
+
+        // An external change sandwiched between our own writes isn't hidden by them
+        db.set_project_pinned("a", false).unwrap();
+        insert("b");
+        db.remove_project("b").unwrap();
+        assert!(!db.is_own_projects_change(after_own, db.projects_revision().unwrap()));
+    }
+}
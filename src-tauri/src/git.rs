@@ -1,8 +1,25 @@
-use crate::{Branch, Commit, DiffHunk, DiffLine, FileDiff, GitStatus, cmd_no_window};
+use crate::{Branch, Commit, ConflictRegion, ContributorActivity, ContributorStats, DailyCommitCount, DiffHunk, DiffLine, FileDiff, GitStatus, cmd_no_window};
 use git2::{DiffOptions, Repository, StatusOptions};
 
 pub struct GitService;
 
+/// Returned by `commit` when user.name/user.email aren't configured, so the UI can prompt for them
+const MISSING_IDENTITY_ERROR: &str =
+    "MISSING_IDENTITY: Git needs your name and email before committing. Set user.name and user.email.";
+
+/// Returned by `undo_last_commit` on a repo's first commit, so the UI can disable the action
+const INITIAL_COMMIT_ERROR: &str = "INITIAL_COMMIT: Cannot undo: this is the initial commit";
+
+/// Returned by `restore_file_from_ref` when the file doesn't exist at that ref, so the UI can
+/// offer to delete the local copy instead
+const NOT_IN_REF_ERROR: &str = "NOT_IN_REF";
+
+/// Returned by `delete_branch` for a branch with unmerged commits, so the UI can ask before forcing
+const NOT_MERGED_ERROR: &str = "NOT_MERGED";
+
+/// Returned by `commit` when the result would be identical to HEAD and `allow_empty` is off
+const NOTHING_TO_COMMIT_ERROR: &str = "NOTHING_TO_COMMIT: There are no changes to commit.";
+
 impl GitService {
     pub fn is_git_repo(path: &str) -> Result<bool, String> {
         Ok(Repository::open(path).is_ok())
@@ -27,9 +44,15 @@ impl GitService {
 
         let statuses = repo.statuses(Some(&mut opts)).map_err(|e| e.to_string())?;
 
+        let submodule_paths: std::collections::HashSet<String> = repo
+            .submodules()
+            .map(|subs| subs.iter().map(|s| s.path().to_string_lossy().to_string()).collect())
+            .unwrap_or_default();
+
         let mut staged = Vec::new();
         let mut unstaged = Vec::new();
         let mut untracked = Vec::new();
+        let mut modified_submodules = Vec::new();
 
         for entry in statuses.iter() {
             let status = entry.status();
@@ -43,7 +66,11 @@ impl GitService {
                 staged.push(path.clone());
             }
             if status.is_wt_modified() || status.is_wt_deleted() || status.is_wt_renamed() {
-                unstaged.push(path.clone());
+                if submodule_paths.contains(&path) {
+                    modified_submodules.push(path.clone());
+                } else {
+                    unstaged.push(path.clone());
+                }
             }
             if status.is_wt_new() {
                 untracked.push(path);
@@ -57,6 +84,7 @@ impl GitService {
             staged,
             unstaged,
             untracked,
+            modified_submodules,
         })
     }
 
@@ -88,113 +116,46 @@ impl GitService {
         Ok((ahead as u32, behind as u32))
     }
 
-    pub fn get_diff(repo_path: &str) -> Result<Vec<FileDiff>, String> {
-        use std::cell::RefCell;
-        use std::collections::HashMap;
-
+    /// Working tree changes against HEAD. Renames are matched at `rename_threshold` percent
+    /// similarity (git's default is 50); `find_copies` also reports copies of modified files.
+    pub fn get_diff(
+        repo_path: &str,
+        ignore_whitespace: bool,
+        context_lines: Option<u32>,
+        rename_threshold: Option<u16>,
+        find_copies: bool,
+    ) -> Result<Vec<FileDiff>, String> {
         let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
 
         // Get diff between HEAD and working directory
         let head = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
 
-        let mut opts = DiffOptions::new();
+        let mut opts = Self::diff_options(ignore_whitespace, context_lines);
         opts.include_untracked(true);
         opts.recurse_untracked_dirs(true);
         opts.show_untracked_content(true);
 
-        let diff = repo
+        let mut diff = repo
             .diff_tree_to_workdir_with_index(head.as_ref(), Some(&mut opts))
             .map_err(|e| e.to_string())?;
+        Self::find_renames(&mut diff, rename_threshold, find_copies)?;
 
-        // Use RefCell to allow interior mutability
-        let diffs: RefCell<HashMap<String, FileDiff>> = RefCell::new(HashMap::new());
-
-        diff.foreach(
-            &mut |delta, _| {
-                let path = delta
-                    .new_file()
-                    .path()
-                    .or_else(|| delta.old_file().path())
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_default();
-
-                let status = match delta.status() {
-                    git2::Delta::Added | git2::Delta::Untracked => "added",
-                    git2::Delta::Deleted => "deleted",
-                    git2::Delta::Modified => "modified",
-                    git2::Delta::Renamed => "renamed",
-                    _ => "modified",
-                }
-                .to_string();
-
-                diffs.borrow_mut().insert(path.clone(), FileDiff {
-                    path,
-                    status,
-                    hunks: Vec::new(),
-                });
-
-                true
-            },
-            None,
-            Some(&mut |delta, hunk| {
-                let path = delta
-                    .new_file()
-                    .path()
-                    .or_else(|| delta.old_file().path())
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_default();
-
-                if let Some(file_diff) = diffs.borrow_mut().get_mut(&path) {
-                    file_diff.hunks.push(DiffHunk {
-                        old_start: hunk.old_start(),
-                        old_lines: hunk.old_lines(),
-                        new_start: hunk.new_start(),
-                        new_lines: hunk.new_lines(),
-                        lines: Vec::new(),
-                    });
-                }
-
-                true
-            }),
-            Some(&mut |delta, _hunk, line| {
-                let path = delta
-                    .new_file()
-                    .path()
-                    .or_else(|| delta.old_file().path())
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_default();
-
-                let line_type = match line.origin() {
-                    '+' => "addition",
-                    '-' => "deletion",
-                    _ => "context",
-                }
-                .to_string();
-
-                let content = String::from_utf8_lossy(line.content()).to_string();
-
-                if let Some(file_diff) = diffs.borrow_mut().get_mut(&path) {
-                    if let Some(hunk) = file_diff.hunks.last_mut() {
-                        hunk.lines.push(DiffLine {
-                            line_type,
-                            content: content.trim_end_matches('\n').to_string(),
-                            old_line_no: line.old_lineno(),
-                            new_line_no: line.new_lineno(),
-                        });
-                    }
-                }
-
-                true
-            }),
-        )
-        .map_err(|e| e.to_string())?;
+        Self::collect_file_diffs(&repo, &diff)
+    }
 
-        let mut result: Vec<FileDiff> = diffs.into_inner().into_values().collect();
-        result.sort_by(|a, b| a.path.cmp(&b.path));
-        Ok(result)
+    /// Reject messages git would refuse (or that would leave a blank subject in the log)
+    pub fn validate_commit_message(message: &str) -> Result<(), String> {
+        if message.trim().is_empty() {
+            return Err("Commit message is empty".to_string());
+        }
+        Ok(())
     }
 
-    pub fn commit(repo_path: &str, message: &str, files: Option<Vec<String>>) -> Result<(), String> {
+    /// Commit `files` (or every change). With `allow_empty`, a commit whose tree matches HEAD
+    /// is still created, which is handy for re-triggering CI.
+    pub fn commit(repo_path: &str, message: &str, files: Option<Vec<String>>, allow_empty: bool) -> Result<(), String> {
+        Self::validate_commit_message(message)?;
+
         // If a merge is in progress, use CLI git commit which handles unmerged index state
         let merge_head = std::path::Path::new(repo_path).join(".git").join("MERGE_HEAD");
         if merge_head.exists() {
@@ -238,6 +199,12 @@ impl GitService {
                 .map_err(|e| format!("Failed to run git commit: {}", e))?;
             if !output.status.success() {
                 let stderr = String::from_utf8_lossy(&output.stderr);
+                if stderr.contains("Please tell me who you are") {
+                    return Err(MISSING_IDENTITY_ERROR.to_string());
+                }
+                if String::from_utf8_lossy(&output.stdout).contains("nothing to commit") {
+                    return Err(NOTHING_TO_COMMIT_ERROR.to_string());
+                }
                 return Err(format!("git commit failed: {}", stderr.trim()));
             }
             return Ok(());
@@ -302,12 +269,25 @@ impl GitService {
         let tree_id = index.write_tree().map_err(|e| e.to_string())?;
         let tree = repo.find_tree(tree_id).map_err(|e| e.to_string())?;
 
-        let signature = repo.signature().map_err(|e| e.to_string())?;
+        let signature = repo.signature().map_err(|e| {
+            if e.code() == git2::ErrorCode::NotFound {
+                MISSING_IDENTITY_ERROR.to_string()
+            } else {
+                e.to_string()
+            }
+        })?;
 
         let parent = repo
             .head()
             .ok()
             .and_then(|h| h.peel_to_commit().ok());
+        let unchanged = match &parent {
+            Some(parent) => parent.tree_id() == tree_id,
+            None => tree.is_empty(),
+        };
+        if unchanged && !allow_empty {
+            return Err(NOTHING_TO_COMMIT_ERROR.to_string());
+        }
         let parents: Vec<&git2::Commit> = parent.iter().collect();
 
         repo.commit(
@@ -323,6 +303,61 @@ impl GitService {
         Ok(())
     }
 
+    /// Append `Co-authored-by:` lines for `coauthors` ("Name <email>") and, with `sign_off`,
+    /// a `Signed-off-by:` line for the configured identity. Trailers already present are skipped.
+    pub fn add_commit_trailers(
+        repo_path: &str,
+        message: &str,
+        coauthors: &[String],
+        sign_off: bool,
+    ) -> Result<String, String> {
+        let mut trailers: Vec<String> = coauthors
+            .iter()
+            .map(|c| c.trim())
+            .filter(|c| !c.is_empty())
+            .map(|c| format!("Co-authored-by: {}", c))
+            .collect();
+        if sign_off {
+            let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+            let signature = repo.signature().map_err(|e| {
+                if e.code() == git2::ErrorCode::NotFound {
+                    MISSING_IDENTITY_ERROR.to_string()
+                } else {
+                    e.to_string()
+                }
+            })?;
+            trailers.push(format!(
+                "Signed-off-by: {} <{}>",
+                signature.name().unwrap_or(""),
+                signature.email().unwrap_or("")
+            ));
+        }
+
+        let message = message.trim_end();
+        let existing: std::collections::HashSet<String> =
+            message.lines().map(|l| l.trim().to_lowercase()).collect();
+        let mut seen = std::collections::HashSet::new();
+        trailers.retain(|t| {
+            let key = t.to_lowercase();
+            !existing.contains(&key) && seen.insert(key)
+        });
+        if trailers.is_empty() {
+            return Ok(message.to_string());
+        }
+
+        // Join an existing trailer block at the end of the message instead of starting a new one
+        let last_paragraph = message.rsplit("\n\n").next().unwrap_or("");
+        let ends_with_trailers = message.contains("\n\n")
+            && !last_paragraph.trim().is_empty()
+            && last_paragraph.lines().all(|l| {
+                l.split_once(": ")
+                    .map(|(key, _)| !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+                    .unwrap_or(false)
+            });
+        let separator = if ends_with_trailers { "\n" } else { "\n\n" };
+        Ok(format!("{}{}{}", message, separator, trailers.join("\n")))
+    }
+
     pub fn flatten_nested_repo(repo_path: &str, nested_path: &str) -> Result<(), String> {
         let repo_root = std::path::Path::new(repo_path);
         let nested = repo_root.join(nested_path);
@@ -381,7 +416,94 @@ impl GitService {
         Ok(())
     }
 
-    pub fn get_branches(repo_path: &str) -> Result<Vec<Branch>, String> {
+    pub fn list_submodules(repo_path: &str) -> Result<Vec<crate::SubmoduleInfo>, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let submodules = repo.submodules().map_err(|e| e.to_string())?;
+
+        Ok(submodules
+            .iter()
+            .map(|sub| {
+                let head_sha = sub.workdir_id().map(|id| id.to_string());
+                let recorded_sha = sub.index_id().or_else(|| sub.head_id()).map(|id| id.to_string());
+                crate::SubmoduleInfo {
+                    name: sub.name().unwrap_or_default().to_string(),
+                    path: sub.path().to_string_lossy().to_string(),
+                    url: sub.url().map(|u| u.to_string()),
+                    is_initialized: head_sha.is_some(),
+                    is_out_of_date: head_sha.is_some() && head_sha != recorded_sha,
+                    head_sha,
+                    recorded_sha,
+                }
+            })
+            .collect())
+    }
+
+    pub fn update_submodules(repo_path: &str, init: bool, recursive: bool) -> Result<(), String> {
+        let mut args = vec!["submodule", "update"];
+        if init {
+            args.push("--init");
+        }
+        if recursive {
+            args.push("--recursive");
+        }
+
+        let output = cmd_no_window("git")
+            .args(&args)
+            .current_dir(repo_path)
+            .output()
+            .map_err(|e| format!("Failed to run git submodule update: {}", e))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("git submodule update failed: {}", stderr));
+        }
+
+        Ok(())
+    }
+
+    /// Find git repositories nested below `path` (excluding `path` itself), up to `max_depth` levels deep.
+    pub fn find_nested_repos(path: &str, max_depth: usize) -> Result<Vec<String>, String> {
+        let root = std::path::Path::new(path);
+        if !root.is_dir() {
+            return Err(format!("Not a directory: {}", path));
+        }
+
+        fn walk(dir: &std::path::Path, depth: usize, max_depth: usize, found: &mut Vec<String>) {
+            if depth > max_depth {
+                return;
+            }
+            let entries = match std::fs::read_dir(dir) {
+                Ok(e) => e,
+                Err(_) => return,
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name == ".git" || name == "node_modules" || name == "target" || name == "__pycache__" {
+                    continue;
+                }
+                // Don't follow symlinks to avoid cycles and repos outside the project
+                let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                if !is_dir {
+                    continue;
+                }
+                let child = entry.path();
+                // .git may be a directory (regular repo) or a file (worktree/submodule)
+                if child.join(".git").exists() {
+                    found.push(child.to_string_lossy().to_string());
+                }
+                walk(&child, depth + 1, max_depth, found);
+            }
+        }
+
+        let mut found = Vec::new();
+        walk(root, 1, max_depth, &mut found);
+        found.sort();
+        Ok(found)
+    }
+
+    /// All local and remote branches. `include_ahead_behind` adds upstream ahead/behind
+    /// counts for local branches, which walks history per branch and is off by default.
+    pub fn get_branches(repo_path: &str, include_ahead_behind: bool) -> Result<Vec<Branch>, String> {
         let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
         let mut branches = Vec::new();
 
@@ -407,11 +529,22 @@ impl GitService {
                 .ok()
                 .and_then(|u| u.name().ok().flatten().map(|s| s.to_string()));
 
+            let (ahead, behind) = if include_ahead_behind && !is_remote && upstream.is_some() {
+                match Self::get_ahead_behind(&repo, &name) {
+                    Ok((ahead, behind)) => (Some(ahead), Some(behind)),
+                    Err(_) => (None, None),
+                }
+            } else {
+                (None, None)
+            };
+
             branches.push(Branch {
                 name,
                 is_head,
                 is_remote,
                 upstream,
+                ahead,
+                behind,
             });
         }
 
@@ -485,6 +618,69 @@ impl GitService {
         Ok(())
     }
 
+    /// Rename a local branch, keeping its upstream and moving HEAD along if it's checked out
+    pub fn rename_branch(repo_path: &str, old_name: &str, new_name: &str) -> Result<(), String> {
+        if !git2::Branch::name_is_valid(new_name).unwrap_or(false) {
+            return Err(format!("'{}' is not a valid branch name", new_name));
+        }
+
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let mut branch = repo
+            .find_branch(old_name, git2::BranchType::Local)
+            .map_err(|_| format!("Branch '{}' not found", old_name))?;
+        if repo.find_branch(new_name, git2::BranchType::Local).is_ok() {
+            return Err(format!("A branch named '{}' already exists", new_name));
+        }
+
+        let was_head = branch.is_head();
+        let upstream = branch
+            .upstream()
+            .ok()
+            .and_then(|u| u.name().ok().flatten().map(|s| s.to_string()));
+
+        let mut renamed = branch.rename(new_name, false).map_err(|e| e.to_string())?;
+
+        // libgit2 normally carries both over; make sure rather than leave a half-renamed branch
+        if upstream.is_some() && renamed.upstream().is_err() {
+            renamed.set_upstream(upstream.as_deref()).map_err(|e| e.to_string())?;
+        }
+        if was_head && !renamed.is_head() {
+            let refname = renamed.get().name().ok_or("Invalid branch name")?;
+            repo.set_head(refname).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Delete a local branch. Without `force` this behaves like `git branch -d` and refuses
+    /// when the branch has commits not in its upstream (or HEAD, if it has no upstream).
+    pub fn delete_branch(repo_path: &str, name: &str, force: bool) -> Result<(), String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let mut branch = repo
+            .find_branch(name, git2::BranchType::Local)
+            .map_err(|_| format!("Branch '{}' not found", name))?;
+
+        if branch.is_head() {
+            return Err(format!("Cannot delete '{}' while it's checked out", name));
+        }
+
+        if !force {
+            let tip = branch.get().target().ok_or("Could not get branch target")?;
+            let base = branch
+                .upstream()
+                .ok()
+                .and_then(|u| u.get().target())
+                .or_else(|| repo.head().ok().and_then(|h| h.target()));
+            let merged = base
+                .map(|base| base == tip || repo.graph_descendant_of(base, tip).unwrap_or(false))
+                .unwrap_or(false);
+            if !merged {
+                return Err(format!("{}: '{}' is not fully merged", NOT_MERGED_ERROR, name));
+            }
+        }
+
+        branch.delete().map_err(|e| e.to_string())
+    }
+
     pub fn get_history(repo_path: &str, limit: u32) -> Result<Vec<Commit>, String> {
         let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
         let mut commits = Vec::new();
@@ -506,32 +702,402 @@ impl GitService {
 
             let oid = oid.map_err(|e| e.to_string())?;
             let commit = repo.find_commit(oid).map_err(|e| e.to_string())?;
+            commits.push(Self::commit_info(&commit));
+        }
 
-            let id = oid.to_string();
-            let short_id = id[..7.min(id.len())].to_string();
-            let message = commit
-                .message()
-                .unwrap_or("")
-                .lines()
-                .next()
-                .unwrap_or("")
-                .to_string();
-            let author = commit.author().name().unwrap_or("").to_string();
-            let author_email = commit.author().email().unwrap_or("").to_string();
-            let timestamp = commit.time().seconds().to_string();
-
-            commits.push(Commit {
-                id,
-                short_id,
-                message,
-                author,
-                author_email,
-                timestamp,
-                summary: None,
+        Ok(commits)
+    }
+
+    fn commit_info(commit: &git2::Commit) -> Commit {
+        let id = commit.id().to_string();
+        let short_id = id[..7.min(id.len())].to_string();
+        let message = commit
+            .message()
+            .unwrap_or("")
+            .lines()
+            .next()
+            .unwrap_or("")
+            .to_string();
+        let author = commit.author().name().unwrap_or("").to_string();
+        let author_email = commit.author().email().unwrap_or("").to_string();
+        let time = commit.time();
+        let timestamp = time.seconds().to_string();
+        let timezone_offset = time.offset_minutes();
+        let iso_timestamp = chrono::FixedOffset::east_opt(timezone_offset * 60)
+            .and_then(|tz| chrono::TimeZone::timestamp_opt(&tz, time.seconds(), 0).single())
+            .map(|dt| dt.to_rfc3339())
+            .unwrap_or_default();
+
+        Commit {
+            id,
+            short_id,
+            message,
+            author,
+            author_email,
+            timestamp,
+            timezone_offset,
+            iso_timestamp,
+            summary: None,
+        }
+    }
+
+    /// Commits from every branch and tag in topological order, with parents and the refs
+    /// pointing at each, so the history view can draw lanes and merges
+    pub fn get_commit_graph(repo_path: &str, limit: u32) -> Result<Vec<crate::GraphCommit>, String> {
+        use std::collections::{HashMap, HashSet};
+
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+
+        let mut refs_by_commit: HashMap<git2::Oid, Vec<crate::CommitRef>> = HashMap::new();
+        for reference in repo.references().map_err(|e| e.to_string())?.flatten() {
+            // Skips symbolic refs like origin/HEAD, which would just duplicate the branch
+            if reference.kind() != Some(git2::ReferenceType::Direct) {
+                continue;
+            }
+            let ref_type = if reference.is_branch() {
+                "local"
+            } else if reference.is_remote() {
+                "remote"
+            } else if reference.is_tag() {
+                "tag"
+            } else {
+                continue;
+            };
+            let (Some(name), Ok(commit)) = (reference.shorthand(), reference.peel_to_commit()) else {
+                continue;
+            };
+            refs_by_commit.entry(commit.id()).or_default().push(crate::CommitRef {
+                name: name.to_string(),
+                ref_type: ref_type.to_string(),
             });
         }
 
-        Ok(commits)
+        let head_oid = repo.head().ok().and_then(|h| h.target());
+        let mut revwalk = repo.revwalk().map_err(|e| e.to_string())?;
+        if let Some(oid) = head_oid {
+            revwalk.push(oid).map_err(|e| e.to_string())?;
+        }
+        for oid in refs_by_commit.keys() {
+            revwalk.push(*oid).map_err(|e| e.to_string())?;
+        }
+        revwalk
+            .set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
+            .map_err(|e| e.to_string())?;
+
+        // Topological order visits children before parents, so reachability from HEAD can
+        // be carried down as we go instead of doing a second walk
+        let mut reachable: HashSet<git2::Oid> = head_oid.into_iter().collect();
+        let mut graph = Vec::new();
+        for oid in revwalk.take(limit as usize) {
+            let oid = oid.map_err(|e| e.to_string())?;
+            let commit = repo.find_commit(oid).map_err(|e| e.to_string())?;
+
+            let reachable_from_head = reachable.contains(&oid);
+            if reachable_from_head {
+                reachable.extend(commit.parent_ids());
+            }
+
+            graph.push(crate::GraphCommit {
+                commit: Self::commit_info(&commit),
+                parents: commit.parent_ids().map(|id| id.to_string()).collect(),
+                refs: refs_by_commit.remove(&oid).unwrap_or_default(),
+                reachable_from_head,
+            });
+        }
+
+        Ok(graph)
+    }
+
+    /// Full messages of commits reachable from `head` but not `base` (like `git log base..head`),
+    /// oldest first, capped at `limit`
+    pub fn get_commit_messages_between(
+        repo_path: &str,
+        base: &str,
+        head: &str,
+        limit: usize,
+    ) -> Result<Vec<String>, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let resolve = |spec: &str| {
+            repo.revparse_single(spec)
+                .and_then(|o| o.peel_to_commit())
+                .map(|c| c.id())
+                .map_err(|_| format!("Could not resolve '{}' to a commit", spec))
+        };
+        let mut revwalk = repo.revwalk().map_err(|e| e.to_string())?;
+        revwalk.push(resolve(head)?).map_err(|e| e.to_string())?;
+        revwalk.hide(resolve(base)?).map_err(|e| e.to_string())?;
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE).map_err(|e| e.to_string())?;
+
+        let mut messages = Vec::new();
+        for oid in revwalk.take(limit) {
+            let oid = oid.map_err(|e| e.to_string())?;
+            let commit = repo.find_commit(oid).map_err(|e| e.to_string())?;
+            messages.push(commit.message().unwrap_or("").trim().to_string());
+        }
+        Ok(messages)
+    }
+
+    pub fn get_reflog(
+        repo_path: &str,
+        ref_name: &str,
+        limit: usize,
+    ) -> Result<Vec<crate::ReflogEntry>, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        // Accept short branch names as well as full refs
+        let full_name = if ref_name == "HEAD" || ref_name.starts_with("refs/") {
+            ref_name.to_string()
+        } else {
+            repo.resolve_reference_from_short_name(ref_name)
+                .ok()
+                .and_then(|r| r.name().map(|n| n.to_string()))
+                .ok_or_else(|| format!("Could not resolve reference '{}'", ref_name))?
+        };
+        let reflog = repo.reflog(&full_name).map_err(|e| e.to_string())?;
+
+        Ok(reflog
+            .iter()
+            .take(limit)
+            .enumerate()
+            .map(|(index, entry)| {
+                let new_id = entry.id_new().to_string();
+                let committer = entry.committer();
+                crate::ReflogEntry {
+                    index,
+                    old_id: entry.id_old().to_string(),
+                    short_id: new_id[..7.min(new_id.len())].to_string(),
+                    new_id,
+                    author: committer.name().unwrap_or("").to_string(),
+                    author_email: committer.email().unwrap_or("").to_string(),
+                    message: entry.message().unwrap_or("").to_string(),
+                    timestamp: committer.when().seconds().to_string(),
+                }
+            })
+            .collect())
+    }
+
+    pub fn get_head_info(repo_path: &str) -> Result<Option<crate::HeadInfo>, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let head = match repo.head() {
+            Ok(h) => h,
+            Err(_) => return Ok(None), // Unborn branch
+        };
+        let commit = head.peel_to_commit().map_err(|e| e.to_string())?;
+        let sha = commit.id().to_string();
+
+        let is_detached = repo.head_detached().unwrap_or(false);
+        let branch = if is_detached {
+            None
+        } else {
+            head.shorthand().map(|s| s.to_string())
+        };
+
+        // Annotated tags point at a tag object, so peel before comparing
+        let mut tags_here = Vec::new();
+        repo.tag_foreach(|oid, name| {
+            let points_here = repo
+                .find_object(oid, None)
+                .and_then(|o| o.peel_to_commit())
+                .map(|c| c.id() == commit.id())
+                .unwrap_or(false);
+            if points_here {
+                let name = String::from_utf8_lossy(name);
+                tags_here.push(name.trim_start_matches("refs/tags/").to_string());
+            }
+            true
+        })
+        .map_err(|e| e.to_string())?;
+        tags_here.sort();
+
+        Ok(Some(crate::HeadInfo {
+            short_sha: sha[..7.min(sha.len())].to_string(),
+            sha,
+            branch,
+            is_detached,
+            tags_here,
+            subject: commit.summary().unwrap_or("").to_string(),
+        }))
+    }
+
+    pub fn get_contributor_activity(
+        repo_path: &str,
+        since: Option<i64>,
+        until: Option<i64>,
+    ) -> Result<ContributorActivity, String> {
+        const MAX_COMMITS: usize = 10_000;
+
+        struct CommitInfo {
+            oid: git2::Oid,
+            name: String,
+            email: String,
+            seconds: i64,
+            date: String,
+        }
+
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let mut infos = Vec::new();
+        let mut truncated = false;
+
+        if let Ok(head) = repo.head() {
+            let oid = head.target().ok_or("Failed to get HEAD target")?;
+            let mut revwalk = repo.revwalk().map_err(|e| e.to_string())?;
+            revwalk.push(oid).map_err(|e| e.to_string())?;
+            revwalk.set_sorting(git2::Sort::TIME).map_err(|e| e.to_string())?;
+
+            for oid in revwalk {
+                let oid = oid.map_err(|e| e.to_string())?;
+                let commit = repo.find_commit(oid).map_err(|e| e.to_string())?;
+                let time = commit.time();
+                if until.is_some_and(|u| time.seconds() > u) {
+                    continue;
+                }
+                // Time-sorted walk, so everything after this is older too
+                if since.is_some_and(|s| time.seconds() < s) {
+                    break;
+                }
+                if infos.len() >= MAX_COMMITS {
+                    truncated = true;
+                    break;
+                }
+
+                // Bucket by the author's local day, not UTC
+                let date = chrono::FixedOffset::east_opt(time.offset_minutes() * 60)
+                    .and_then(|tz| chrono::TimeZone::timestamp_opt(&tz, time.seconds(), 0).single())
+                    .map(|dt| dt.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default();
+                let author = commit.author();
+                infos.push(CommitInfo {
+                    oid,
+                    name: author.name().unwrap_or("").to_string(),
+                    email: author.email().unwrap_or("").to_string(),
+                    seconds: time.seconds(),
+                    date,
+                });
+            }
+        }
+
+        // Diffing dominates, so split it across threads. git2::Repository isn't Sync,
+        // so each worker opens its own handle.
+        let oids: Vec<git2::Oid> = infos.iter().map(|c| c.oid).collect();
+        let workers = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4)
+            .min(8);
+        let chunk_size = oids.len().div_ceil(workers).max(1);
+        let line_stats: Vec<(usize, usize)> = std::thread::scope(|scope| {
+            let handles: Vec<_> = oids
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || Self::commit_line_stats(repo_path, chunk)))
+                .collect();
+            let mut stats = Vec::with_capacity(oids.len());
+            for handle in handles {
+                let chunk_stats = handle
+                    .join()
+                    .map_err(|_| "Diff worker panicked".to_string())??;
+                stats.extend(chunk_stats);
+            }
+            Ok::<_, String>(stats)
+        })?;
+
+        let mut by_author: std::collections::HashMap<String, ContributorStats> =
+            std::collections::HashMap::new();
+        let mut daily: std::collections::BTreeMap<String, usize> = std::collections::BTreeMap::new();
+        for (info, (additions, deletions)) in infos.iter().zip(line_stats) {
+            // Newest commit comes first, so its name wins for authors with several spellings
+            let entry = by_author
+                .entry(info.email.to_lowercase())
+                .or_insert_with(|| ContributorStats {
+                    name: info.name.clone(),
+                    email: info.email.clone(),
+                    commits: 0,
+                    additions: 0,
+                    deletions: 0,
+                    first_commit: info.seconds,
+                    last_commit: info.seconds,
+                });
+            entry.commits += 1;
+            entry.additions += additions;
+            entry.deletions += deletions;
+            entry.first_commit = entry.first_commit.min(info.seconds);
+            entry.last_commit = entry.last_commit.max(info.seconds);
+
+            *daily.entry(info.date.clone()).or_default() += 1;
+        }
+
+        let mut contributors: Vec<ContributorStats> = by_author.into_values().collect();
+        contributors.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.name.cmp(&b.name)));
+
+        Ok(ContributorActivity {
+            contributors,
+            daily: daily
+                .into_iter()
+                .map(|(date, count)| DailyCommitCount { date, count })
+                .collect(),
+            total_commits: infos.len(),
+            truncated,
+        })
+    }
+
+    /// Lines added/removed by each commit against its first parent. Merges count as zero,
+    /// matching `git log --shortstat`.
+    fn commit_line_stats(repo_path: &str, oids: &[git2::Oid]) -> Result<Vec<(usize, usize)>, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let mut stats = Vec::with_capacity(oids.len());
+        for oid in oids {
+            let commit = repo.find_commit(*oid).map_err(|e| e.to_string())?;
+            if commit.parent_count() > 1 {
+                stats.push((0, 0));
+                continue;
+            }
+            let tree = commit.tree().map_err(|e| e.to_string())?;
+            let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+            let diff = repo
+                .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+                .map_err(|e| e.to_string())?;
+            let diff_stats = diff.stats().map_err(|e| e.to_string())?;
+            stats.push((diff_stats.insertions(), diff_stats.deletions()));
+        }
+        Ok(stats)
+    }
+
+    pub fn check_large_files(repo_path: &str, threshold_bytes: u64) -> Result<Vec<crate::LargeFile>, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let workdir = repo.workdir().ok_or("Repository has no working directory")?;
+
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(true);
+        opts.recurse_untracked_dirs(true);
+        let statuses = repo.statuses(Some(&mut opts)).map_err(|e| e.to_string())?;
+
+        let mut large = Vec::new();
+        for entry in statuses.iter() {
+            let status = entry.status();
+            if status.is_wt_deleted() || status.is_index_deleted() || status.is_ignored() {
+                continue;
+            }
+            let Some(path) = entry.path() else { continue };
+            let Ok(metadata) = std::fs::metadata(workdir.join(path)) else { continue };
+            if !metadata.is_file() || metadata.len() <= threshold_bytes {
+                continue;
+            }
+            // Files already routed through LFS only add a small pointer to history
+            let is_lfs = repo
+                .get_attr(std::path::Path::new(path), "filter", git2::AttrCheckFlags::FILE_THEN_INDEX)
+                .ok()
+                .flatten()
+                == Some("lfs");
+            if is_lfs {
+                continue;
+            }
+
+            let staged = status.is_index_new() || status.is_index_modified() || status.is_index_renamed();
+            large.push(crate::LargeFile {
+                path: path.to_string(),
+                size: metadata.len(),
+                staged,
+            });
+        }
+        large.sort_by(|a, b| b.size.cmp(&a.size));
+        Ok(large)
     }
 
     pub fn discard_file(repo_path: &str, file_path: &str) -> Result<(), String> {
@@ -648,6 +1214,57 @@ impl GitService {
         Ok(())
     }
 
+    /// Bring one file (or directory) back to how it is at `git_ref` without touching the rest
+    /// of the tree, like `git restore --source <ref>`. With `stage` the index is updated too.
+    /// If the path doesn't exist at `git_ref`, the local copy is only removed when
+    /// `delete_if_missing` is set.
+    pub fn restore_file_from_ref(
+        repo_path: &str,
+        file_path: &str,
+        git_ref: &str,
+        stage: bool,
+        delete_if_missing: bool,
+    ) -> Result<(), String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let tree = repo
+            .revparse_single(git_ref)
+            .and_then(|o| o.peel_to_tree())
+            .map_err(|_| format!("Could not resolve '{}'", git_ref))?;
+
+        if tree.get_path(std::path::Path::new(file_path)).is_err() {
+            let full_path = std::path::Path::new(repo_path).join(file_path);
+            if !full_path.exists() {
+                return Err(format!("{} doesn't exist at {} or locally", file_path, git_ref));
+            }
+            if !delete_if_missing {
+                return Err(format!("{}: {} doesn't exist at {}", NOT_IN_REF_ERROR, file_path, git_ref));
+            }
+            if full_path.is_dir() {
+                std::fs::remove_dir_all(&full_path).map_err(|e| e.to_string())?;
+            } else {
+                std::fs::remove_file(&full_path).map_err(|e| e.to_string())?;
+            }
+            if stage {
+                let mut index = repo.index().map_err(|e| e.to_string())?;
+                let _ = index.remove_path(std::path::Path::new(file_path));
+                let _ = index.remove_dir(std::path::Path::new(file_path), 0);
+                index.write().map_err(|e| e.to_string())?;
+            }
+            return Ok(());
+        }
+
+        // Checkout rather than writing the blob ourselves so eol/autocrlf filters and file
+        // modes are applied the same way git would
+        let mut checkout = git2::build::CheckoutBuilder::new();
+        checkout
+            .path(file_path)
+            .disable_pathspec_match(true)
+            .force()
+            .update_index(stage);
+        repo.checkout_tree(tree.as_object(), Some(&mut checkout))
+            .map_err(|e| e.to_string())
+    }
+
     pub fn checkout_commit(repo_path: &str, commit_id: &str) -> Result<(), String> {
         let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
 
@@ -733,10 +1350,12 @@ impl GitService {
         Ok(())
     }
 
-    pub fn get_commit_diff(repo_path: &str, commit_id: &str) -> Result<Vec<FileDiff>, String> {
-        use std::cell::RefCell;
-        use std::collections::HashMap;
-
+    pub fn get_commit_diff(
+        repo_path: &str,
+        commit_id: &str,
+        ignore_whitespace: bool,
+        context_lines: Option<u32>,
+    ) -> Result<Vec<FileDiff>, String> {
         let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
         let oid = git2::Oid::from_str(commit_id).map_err(|e| e.to_string())?;
         let commit = repo.find_commit(oid).map_err(|e| e.to_string())?;
@@ -748,10 +1367,115 @@ impl GitService {
             None
         };
 
-        let diff = repo
-            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+        let mut opts = Self::diff_options(ignore_whitespace, context_lines);
+        let mut diff = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut opts))
+            .map_err(|e| e.to_string())?;
+        Self::find_renames(&mut diff, None, false)?;
+
+        Self::collect_file_diffs(&repo, &diff)
+    }
+
+    /// Diff between two arbitrary refs (branches, tags, SHAs). With `merge_base`, compares
+    /// `head` against where it diverged from `base`, like `git diff base...head`.
+    pub fn diff_refs(
+        repo_path: &str,
+        base: &str,
+        head: &str,
+        merge_base: bool,
+        ignore_whitespace: bool,
+        context_lines: Option<u32>,
+    ) -> Result<Vec<FileDiff>, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let resolve = |spec: &str| {
+            repo.revparse_single(spec)
+                .and_then(|o| o.peel_to_commit())
+                .map_err(|_| format!("Could not resolve '{}' to a commit", spec))
+        };
+        let base_commit = resolve(base)?;
+        let head_commit = resolve(head)?;
+
+        let base_commit = if merge_base {
+            let oid = repo
+                .merge_base(base_commit.id(), head_commit.id())
+                .map_err(|_| format!("'{}' and '{}' have no common ancestor", base, head))?;
+            repo.find_commit(oid).map_err(|e| e.to_string())?
+        } else {
+            base_commit
+        };
+
+        // Identical commits (or head already contained in base for a merge-base compare)
+        if base_commit.id() == head_commit.id() {
+            return Ok(Vec::new());
+        }
+
+        let base_tree = base_commit.tree().map_err(|e| e.to_string())?;
+        let head_tree = head_commit.tree().map_err(|e| e.to_string())?;
+        let mut opts = Self::diff_options(ignore_whitespace, context_lines);
+        let mut diff = repo
+            .diff_tree_to_tree(Some(&base_tree), Some(&head_tree), Some(&mut opts))
             .map_err(|e| e.to_string())?;
+        Self::find_renames(&mut diff, None, false)?;
+
+        Self::collect_file_diffs(&repo, &diff)
+    }
+
+    /// Everything committed on the current branch since it diverged from `base`
+    /// (the repo's default branch when not given)
+    pub fn get_branch_changes(repo_path: &str, base: Option<&str>) -> Result<Vec<FileDiff>, String> {
+        let base = match base.filter(|b| !b.is_empty()) {
+            Some(b) => b.to_string(),
+            None => {
+                let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+                Self::default_branch(&repo).ok_or("Could not determine the default branch")?
+            }
+        };
+        Self::diff_refs(repo_path, &base, "HEAD", true, false, None)
+    }
+
+    /// The branch `origin/HEAD` points at, falling back to a local or remote main/master
+    fn default_branch(repo: &Repository) -> Option<String> {
+        if let Ok(reference) = repo.find_reference("refs/remotes/origin/HEAD") {
+            if let Some(target) = reference.symbolic_target() {
+                return Some(target.trim_start_matches("refs/remotes/").to_string());
+            }
+        }
+        ["main", "master", "origin/main", "origin/master"]
+            .into_iter()
+            .find(|name| repo.revparse_single(name).is_ok())
+            .map(String::from)
+    }
+
+    /// Shared display options for the diff views. `context_lines` defaults to git's 3.
+    fn diff_options(ignore_whitespace: bool, context_lines: Option<u32>) -> DiffOptions {
+        let mut opts = DiffOptions::new();
+        if ignore_whitespace {
+            opts.ignore_whitespace(true);
+            opts.ignore_whitespace_eol(true);
+        }
+        if let Some(lines) = context_lines {
+            opts.context_lines(lines);
+        }
+        opts
+    }
 
+    /// Pair deleted and added files into renames (and copies) so a moved file is one entry.
+    /// Untracked files count too, so a move shows up before it's staged.
+    fn find_renames(diff: &mut git2::Diff, threshold: Option<u16>, find_copies: bool) -> Result<(), String> {
+        let mut opts = git2::DiffFindOptions::new();
+        opts.renames(true).for_untracked(true).copies(find_copies);
+        if let Some(threshold) = threshold {
+            opts.rename_threshold(threshold.min(100)).copy_threshold(threshold.min(100));
+        }
+        diff.find_similar(Some(&mut opts)).map_err(|e| e.to_string())
+    }
+
+    /// Convert a git2 diff into per-file hunks and lines, sorted by path
+    fn collect_file_diffs(repo: &Repository, diff: &git2::Diff) -> Result<Vec<FileDiff>, String> {
+        use std::cell::RefCell;
+        use std::collections::HashMap;
+
+        // Use RefCell to allow interior mutability
         let diffs: RefCell<HashMap<String, FileDiff>> = RefCell::new(HashMap::new());
 
         diff.foreach(
@@ -768,19 +1492,42 @@ impl GitService {
                     git2::Delta::Deleted => "deleted",
                     git2::Delta::Modified => "modified",
                     git2::Delta::Renamed => "renamed",
+                    git2::Delta::Copied => "copied",
                     _ => "modified",
                 }
                 .to_string();
+                let old_path = matches!(delta.status(), git2::Delta::Renamed | git2::Delta::Copied)
+                    .then(|| delta.old_file().path().map(|p| p.to_string_lossy().to_string()))
+                    .flatten();
 
+                let is_image = is_image_path(&path);
                 diffs.borrow_mut().insert(path.clone(), FileDiff {
                     path,
+                    old_path,
                     status,
                     hunks: Vec::new(),
+                    is_binary: delta.flags().is_binary(),
+                    is_image,
+                    lfs_old: Self::diff_file_lfs_pointer(repo, &delta.old_file()),
+                    lfs_new: Self::diff_file_lfs_pointer(repo, &delta.new_file()),
                 });
 
                 true
             },
-            None,
+            Some(&mut |delta, _binary| {
+                let path = delta
+                    .new_file()
+                    .path()
+                    .or_else(|| delta.old_file().path())
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_default();
+
+                if let Some(file_diff) = diffs.borrow_mut().get_mut(&path) {
+                    file_diff.is_binary = true;
+                }
+
+                true
+            }),
             Some(&mut |delta, hunk| {
                 let path = delta
                     .new_file()
@@ -825,6 +1572,7 @@ impl GitService {
                             content: content.trim_end_matches('\n').to_string(),
                             old_line_no: line.old_lineno(),
                             new_line_no: line.new_lineno(),
+                            highlights: Vec::new(),
                         });
                     }
                 }
@@ -834,35 +1582,290 @@ impl GitService {
         )
         .map_err(|e| e.to_string())?;
 
-        let mut result: Vec<FileDiff> = diffs.into_inner().into_values().collect();
-        result.sort_by(|a, b| a.path.cmp(&b.path));
-        Ok(result)
+        let mut result: Vec<FileDiff> = diffs.into_inner().into_values().collect();
+        result.sort_by(|a, b| a.path.cmp(&b.path));
+        for file in result.iter_mut().filter(|f| f.lfs_old.is_some() || f.lfs_new.is_some()) {
+            file.hunks.clear();
+        }
+        for hunk in result.iter_mut().flat_map(|f| f.hunks.iter_mut()) {
+            Self::add_intraline_highlights(hunk);
+        }
+        Ok(result)
+    }
+
+    /// LFS pointer for one side of a delta. Tree and index sides come from the object
+    /// database; worktree files usually aren't stored there, so read them from disk.
+    fn diff_file_lfs_pointer(repo: &Repository, file: &git2::DiffFile) -> Option<crate::LfsPointer> {
+        if file.id().is_zero() || file.size() > LFS_POINTER_MAX_SIZE as u64 {
+            return None;
+        }
+        match repo.find_blob(file.id()) {
+            Ok(blob) => parse_lfs_pointer(blob.content()),
+            Err(_) => {
+                let content = std::fs::read(repo.workdir()?.join(file.path()?)).ok()?;
+                parse_lfs_pointer(&content)
+            }
+        }
+    }
+
+    /// Pair each run of deletions with the additions directly after it and mark the
+    /// word-level ranges that differ. Lines that are mostly rewritten are left unmarked.
+    fn add_intraline_highlights(hunk: &mut DiffHunk) {
+        const MAX_LINE_CHARS: usize = 1000;
+
+        let lines = &mut hunk.lines;
+        let mut i = 0;
+        while i < lines.len() {
+            let del_start = i;
+            while i < lines.len() && lines[i].line_type == "deletion" {
+                i += 1;
+            }
+            let add_start = i;
+            while i < lines.len() && lines[i].line_type == "addition" {
+                i += 1;
+            }
+            if del_start == add_start {
+                i += 1;
+                continue;
+            }
+
+            let pairs = (add_start - del_start).min(i - add_start);
+            for k in 0..pairs {
+                let (old, new) = (&lines[del_start + k].content, &lines[add_start + k].content);
+                if old.len() > MAX_LINE_CHARS || new.len() > MAX_LINE_CHARS {
+                    continue;
+                }
+                let diff = similar::TextDiff::from_words(old.as_str(), new.as_str());
+                if diff.ratio() < 0.5 {
+                    continue;
+                }
+
+                let (mut old_ranges, mut new_ranges) = (Vec::new(), Vec::new());
+                let (mut old_pos, mut new_pos) = (0, 0);
+                for change in diff.iter_all_changes() {
+                    let len = change.value().chars().count();
+                    match change.tag() {
+                        similar::ChangeTag::Equal => {
+                            old_pos += len;
+                            new_pos += len;
+                        }
+                        similar::ChangeTag::Delete => {
+                            push_range(&mut old_ranges, old_pos, old_pos + len);
+                            old_pos += len;
+                        }
+                        similar::ChangeTag::Insert => {
+                            push_range(&mut new_ranges, new_pos, new_pos + len);
+                            new_pos += len;
+                        }
+                    }
+                }
+                lines[del_start + k].highlights = old_ranges;
+                lines[add_start + k].highlights = new_ranges;
+            }
+        }
+    }
+
+    pub fn get_remote_url(repo_path: &str) -> Result<String, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let remote = repo
+            .find_remote("origin")
+            .map_err(|_| "No 'origin' remote found".to_string())?;
+        let url = remote
+            .url()
+            .ok_or_else(|| "Remote URL is not valid UTF-8".to_string())?
+            .to_string();
+
+        // Convert SSH URLs to HTTPS
+        let url = if url.starts_with("git@") {
+            // git@github.com:user/repo.git -> https://github.com/user/repo.git
+            let url = url.trim_start_matches("git@");
+            let url = url.replacen(':', "/", 1);
+            format!("https://{}", url)
+        } else if let Some(rest) = url.strip_prefix("ssh://") {
+            // ssh://git@host:22/user/repo.git -> https://host/user/repo.git
+            let rest = rest.split_once('@').map(|(_, r)| r).unwrap_or(rest);
+            let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+            let host = host.split(':').next().unwrap_or(host);
+            format!("https://{}/{}", host, path)
+        } else {
+            url
+        };
+
+        // Strip trailing .git
+        let url = url.strip_suffix(".git").unwrap_or(&url).to_string();
+
+        Ok(url)
+    }
+
+    // === Remote operations ===
+
+    pub fn list_remotes(repo_path: &str) -> Result<Vec<crate::RemoteInfo>, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let names = repo.remotes().map_err(|e| e.to_string())?;
+        let mut remotes = Vec::new();
+        for name in names.iter().flatten() {
+            let remote = repo.find_remote(name).map_err(|e| e.to_string())?;
+            let fetch_url = remote.url().map(|u| u.to_string());
+            remotes.push(crate::RemoteInfo {
+                name: name.to_string(),
+                // Push URL falls back to the fetch URL unless pushurl is set
+                push_url: remote.pushurl().map(|u| u.to_string()).or_else(|| fetch_url.clone()),
+                fetch_url,
+            });
+        }
+        Ok(remotes)
+    }
+
+    fn validate_remote_name(repo: &Repository, name: &str) -> Result<(), String> {
+        if !git2::Remote::is_valid_name(name) {
+            return Err(format!("'{}' is not a valid remote name", name));
+        }
+        if repo.find_remote(name).is_ok() {
+            return Err(format!("A remote named '{}' already exists", name));
+        }
+        Ok(())
+    }
+
+    pub fn add_remote(repo_path: &str, name: &str, url: &str) -> Result<(), String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        Self::validate_remote_name(&repo, name)?;
+        if url.trim().is_empty() {
+            return Err("Remote URL cannot be empty".to_string());
+        }
+        repo.remote(name, url.trim()).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn remove_remote(repo_path: &str, name: &str) -> Result<(), String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        repo.find_remote(name)
+            .map_err(|_| format!("No remote named '{}'", name))?;
+        repo.remote_delete(name).map_err(|e| e.to_string())
+    }
+
+    pub fn rename_remote(repo_path: &str, old_name: &str, new_name: &str) -> Result<(), String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        repo.find_remote(old_name)
+            .map_err(|_| format!("No remote named '{}'", old_name))?;
+        Self::validate_remote_name(&repo, new_name)?;
+        let problems = repo.remote_rename(old_name, new_name).map_err(|e| e.to_string())?;
+        // Non-default fetch refspecs can't be renamed automatically; git leaves them as-is too
+        for problem in problems.iter().flatten() {
+            log::warn!("Refspec not renamed for remote {}: {}", new_name, problem);
+        }
+        Ok(())
+    }
+
+    pub fn set_remote_url(repo_path: &str, name: &str, url: &str) -> Result<(), String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        repo.find_remote(name)
+            .map_err(|_| format!("No remote named '{}'", name))?;
+        if url.trim().is_empty() {
+            return Err("Remote URL cannot be empty".to_string());
+        }
+        repo.remote_set_url(name, url.trim()).map_err(|e| e.to_string())
+    }
+
+    /// Effective value of a config key (local overriding global), `None` if unset
+    pub fn get_git_config(repo_path: &str, key: &str) -> Result<Option<String>, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let config = repo.config().map_err(|e| e.to_string())?;
+        match config.get_string(key) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// The repo's `.git/config`, or the user's global config when `global`
+    fn writable_config(repo_path: &str, global: bool) -> Result<git2::Config, String> {
+        if global {
+            // find_global fails when ~/.gitconfig doesn't exist yet; opening creates it
+            let path = git2::Config::find_global()
+                .ok()
+                .or_else(|| dirs::home_dir().map(|h| h.join(".gitconfig")))
+                .ok_or("Could not locate global git config")?;
+            git2::Config::open(&path).map_err(|e| e.to_string())
+        } else {
+            let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+            repo.config()
+                .and_then(|c| c.open_level(git2::ConfigLevel::Local))
+                .map_err(|e| e.to_string())
+        }
     }
 
-    pub fn get_remote_url(repo_path: &str) -> Result<String, String> {
+    /// Set a key in the repo's `.git/config`, or in the user's global config when `global`
+    pub fn set_git_config(repo_path: &str, key: &str, value: &str, global: bool) -> Result<(), String> {
+        let mut config = Self::writable_config(repo_path, global)?;
+        config.set_str(key, value).map_err(|e| e.to_string())
+    }
+
+    /// All `alias.*` entries visible to the repo (local overriding global), sorted by name
+    pub fn get_git_aliases(repo_path: &str) -> Result<Vec<crate::GitAlias>, String> {
         let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
-        let remote = repo
-            .find_remote("origin")
-            .map_err(|_| "No 'origin' remote found".to_string())?;
-        let url = remote
-            .url()
-            .ok_or_else(|| "Remote URL is not valid UTF-8".to_string())?
-            .to_string();
+        let config = repo
+            .config()
+            .and_then(|mut c| c.snapshot())
+            .map_err(|e| e.to_string())?;
+        let mut aliases = std::collections::BTreeMap::new();
+        let mut entries = config.entries(Some(r"^alias\.")).map_err(|e| e.to_string())?;
+        // Entries come lowest-priority level first, so later ones win
+        while let Some(entry) = entries.next() {
+            let entry = entry.map_err(|e| e.to_string())?;
+            if let (Some(name), Some(value)) = (entry.name(), entry.value()) {
+                aliases.insert(name.trim_start_matches("alias.").to_string(), value.to_string());
+            }
+        }
+        Ok(aliases
+            .into_iter()
+            .map(|(name, command)| crate::GitAlias { name, command })
+            .collect())
+    }
 
-        // Convert SSH URLs to HTTPS
-        let url = if url.starts_with("git@") {
-            // git@github.com:user/repo.git -> https://github.com/user/repo.git
-            let url = url.trim_start_matches("git@");
-            let url = url.replacen(':', "/", 1);
-            format!("https://{}", url)
-        } else {
-            url
-        };
+    /// Create or update `alias.<name>`; an empty `command` removes the alias
+    pub fn set_git_alias(repo_path: &str, name: &str, command: &str, global: bool) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(format!("'{}' is not a valid alias name", name));
+        }
+        let key = format!("alias.{}", name);
+        if command.trim().is_empty() {
+            let mut config = Self::writable_config(repo_path, global)?;
+            return match config.remove(&key) {
+                Err(e) if e.code() != git2::ErrorCode::NotFound => Err(e.to_string()),
+                _ => Ok(()),
+            };
+        }
+        Self::set_git_config(repo_path, &key, command.trim(), global)
+    }
 
-        // Strip trailing .git
-        let url = url.strip_suffix(".git").unwrap_or(&url).to_string();
+    /// Web URL comparing `base...head` on the remote's host (GitHub, GitLab, Bitbucket,
+    /// Azure DevOps; anything else gets the GitHub/Gitea form)
+    pub fn get_compare_url(repo_path: &str, base: &str, head: &str) -> Result<String, String> {
+        let url = Self::get_remote_url(repo_path)?;
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!("Remote is not hosted on the web: {}", url));
+        }
 
-        Ok(url)
+        let base = encode_ref(base);
+        let head = encode_ref(head);
+        let host = url
+            .split("://")
+            .nth(1)
+            .and_then(|rest| rest.split('/').next())
+            .unwrap_or("")
+            .to_lowercase();
+
+        let compare = if host.contains("gitlab") {
+            format!("{}/-/compare/{}...{}", url, base, head)
+        } else if host.contains("bitbucket") {
+            format!("{}/branches/compare/{}%0D{}", url, head, base)
+        } else if host.contains("dev.azure.com") || host.contains("visualstudio.com") {
+            format!("{}/branchCompare?baseVersion=GB{}&targetVersion=GB{}", url, base, head)
+        } else {
+            format!("{}/compare/{}...{}", url, base, head)
+        };
+        Ok(compare)
     }
 
     /// List all worktrees using `git worktree list --porcelain`
@@ -1110,6 +2113,47 @@ impl GitService {
         Ok(stashes)
     }
 
+    /// What applying stash `index` would change in the current working tree, limited to the
+    /// files the stash touches. Additions are the stash's content, deletions are current content.
+    pub fn diff_stash_against_worktree(repo_path: &str, index: usize) -> Result<Vec<FileDiff>, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let stash_ref = format!("stash@{{{}}}", index);
+        let stash = repo
+            .revparse_single(&stash_ref)
+            .and_then(|o| o.peel_to_commit())
+            .map_err(|_| format!("Stash {} not found", index))?;
+        let stash_tree = stash.tree().map_err(|e| e.to_string())?;
+        let base_tree = stash
+            .parent(0)
+            .and_then(|p| p.tree())
+            .map_err(|e| e.to_string())?;
+
+        let stash_changes = repo
+            .diff_tree_to_tree(Some(&base_tree), Some(&stash_tree), None)
+            .map_err(|e| e.to_string())?;
+        let mut opts = Self::diff_options(false, None);
+        let mut has_paths = false;
+        for delta in stash_changes.deltas() {
+            for file in [delta.old_file(), delta.new_file()] {
+                if let Some(path) = file.path() {
+                    opts.pathspec(path);
+                    has_paths = true;
+                }
+            }
+        }
+        if !has_paths {
+            return Ok(Vec::new());
+        }
+        opts.disable_pathspec_match(true);
+        // Worktree is the "old" side so the diff reads as "what the stash would bring in"
+        opts.reverse(true);
+
+        let diff = repo
+            .diff_tree_to_workdir_with_index(Some(&stash_tree), Some(&mut opts))
+            .map_err(|e| e.to_string())?;
+        Self::collect_file_diffs(&repo, &diff)
+    }
+
     pub fn stash_apply(repo_path: &str, index: usize) -> Result<(), String> {
         let stash_ref = format!("stash@{{{}}}", index);
         let output = cmd_no_window("git")
@@ -1195,6 +2239,45 @@ impl GitService {
         Ok("ok".to_string())
     }
 
+    pub fn preview_merge(repo_path: &str, branch: &str) -> Result<crate::MergePreview, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let ours = repo
+            .head()
+            .and_then(|h| h.peel_to_commit())
+            .map_err(|e| format!("Failed to resolve HEAD: {}", e))?;
+        let theirs = repo
+            .revparse_single(branch)
+            .and_then(|o| o.peel_to_commit())
+            .map_err(|e| format!("Failed to resolve {}: {}", branch, e))?;
+
+        let merge_base = repo.merge_base(ours.id(), theirs.id()).ok();
+        let up_to_date = ours.id() == theirs.id() || merge_base == Some(theirs.id());
+        let can_fast_forward = !up_to_date && merge_base == Some(ours.id());
+
+        let mut conflicts = Vec::new();
+        if !up_to_date && !can_fast_forward {
+            let index = repo
+                .merge_commits(&ours, &theirs, None)
+                .map_err(|e| e.to_string())?;
+            if index.has_conflicts() {
+                for conflict in index.conflicts().map_err(|e| e.to_string())? {
+                    let conflict = conflict.map_err(|e| e.to_string())?;
+                    let entry = conflict.our.or(conflict.their).or(conflict.ancestor);
+                    if let Some(entry) = entry {
+                        conflicts.push(String::from_utf8_lossy(&entry.path).to_string());
+                    }
+                }
+            }
+        }
+
+        Ok(crate::MergePreview {
+            up_to_date,
+            can_fast_forward,
+            merge_base: merge_base.map(|oid| oid.to_string()),
+            conflicts,
+        })
+    }
+
     pub fn abort_merge(repo_path: &str) -> Result<(), String> {
         let output = cmd_no_window("git")
             .arg("-C")
@@ -1261,6 +2344,70 @@ impl GitService {
             .map_err(|e| format!("Failed to read file: {}", e))
     }
 
+    /// Split a conflicted file into plain text and conflict blocks, in order.
+    /// Joining the text with each block's chosen content rebuilds the file.
+    pub fn parse_conflict_chunks(content: &str) -> Vec<ConflictChunk> {
+        enum Section { Ours, Base, Theirs }
+
+        let mut chunks = Vec::new();
+        let mut text = String::new();
+        // Block being parsed, plus its raw text in case it turns out to be unterminated
+        let mut current: Option<(ConflictRegion, Section)> = None;
+        let mut raw = String::new();
+
+        for (index, line) in content.split_inclusive('\n').enumerate() {
+            let marker = line.trim_end_matches(['\r', '\n']);
+            if current.is_some() {
+                raw.push_str(line);
+            }
+            match current.as_mut() {
+                None if marker.starts_with("<<<<<<<") => {
+                    if !text.is_empty() {
+                        chunks.push(ConflictChunk::Text(std::mem::take(&mut text)));
+                    }
+                    let region = ConflictRegion {
+                        start_line: index + 1,
+                        ours_label: marker[7..].trim().to_string(),
+                        theirs_label: String::new(),
+                        ours: String::new(),
+                        base: None,
+                        theirs: String::new(),
+                    };
+                    current = Some((region, Section::Ours));
+                    raw = line.to_string();
+                }
+                None => text.push_str(line),
+                Some((region, section)) => {
+                    if marker.starts_with("|||||||") && matches!(section, Section::Ours) {
+                        region.base = Some(String::new());
+                        *section = Section::Base;
+                    } else if marker == "=======" && !matches!(section, Section::Theirs) {
+                        *section = Section::Theirs;
+                    } else if marker.starts_with(">>>>>>>") && matches!(section, Section::Theirs) {
+                        region.theirs_label = marker[7..].trim().to_string();
+                        let (region, _) = current.take().unwrap();
+                        chunks.push(ConflictChunk::Conflict(region));
+                    } else {
+                        match section {
+                            Section::Ours => region.ours.push_str(line),
+                            Section::Base => region.base.get_or_insert_with(String::new).push_str(line),
+                            Section::Theirs => region.theirs.push_str(line),
+                        }
+                    }
+                }
+            }
+        }
+
+        // An unterminated block isn't a real conflict; keep it as text
+        if current.is_some() {
+            text.push_str(&raw);
+        }
+        if !text.is_empty() {
+            chunks.push(ConflictChunk::Text(text));
+        }
+        chunks
+    }
+
     pub fn resolve_conflict_with_side(repo_path: &str, file_path: &str, side: &str) -> Result<(), String> {
         let side_flag = match side {
             "ours" => "--ours",
@@ -1324,17 +2471,25 @@ impl GitService {
     // === Undo last commit ===
 
     pub fn undo_last_commit(repo_path: &str) -> Result<(), String> {
+        Self::undo_last_commit_keep_message(repo_path).map(|_| ())
+    }
+
+    /// Soft-reset to HEAD's parent and return the undone commit's full message so it can
+    /// go back into the commit box
+    pub fn undo_last_commit_keep_message(repo_path: &str) -> Result<String, String> {
         let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
         let head = repo.head().map_err(|e| e.to_string())?;
         let commit = head.peel_to_commit().map_err(|e| e.to_string())?;
 
         if commit.parent_count() == 0 {
-            return Err("Cannot undo: this is the initial commit".to_string());
+            return Err(INITIAL_COMMIT_ERROR.to_string());
         }
 
+        let message = commit.message().unwrap_or("").to_string();
         let parent = commit.parent(0).map_err(|e| e.to_string())?;
         let parent_id = parent.id().to_string();
-        Self::reset_to_commit(repo_path, &parent_id, "soft")
+        Self::reset_to_commit(repo_path, &parent_id, "soft")?;
+        Ok(message)
     }
 
     // === Rebase operations ===
@@ -1417,6 +2572,58 @@ impl GitService {
         Ok("ok".to_string())
     }
 
+    /// In-progress operation: "merge", "rebase", "cherry-pick", "revert", "bisect",
+    /// "apply-mailbox", or "clean"
+    pub fn get_repo_state(repo_path: &str) -> Result<String, String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let state = match repo.state() {
+            git2::RepositoryState::Clean => "clean",
+            git2::RepositoryState::Merge => "merge",
+            git2::RepositoryState::Rebase
+            | git2::RepositoryState::RebaseInteractive
+            | git2::RepositoryState::RebaseMerge => "rebase",
+            git2::RepositoryState::CherryPick | git2::RepositoryState::CherryPickSequence => "cherry-pick",
+            git2::RepositoryState::Revert | git2::RepositoryState::RevertSequence => "revert",
+            git2::RepositoryState::Bisect => "bisect",
+            git2::RepositoryState::ApplyMailbox | git2::RepositoryState::ApplyMailboxOrRebase => "apply-mailbox",
+        };
+        Ok(state.to_string())
+    }
+
+    /// Abort whatever operation is in progress and return its name ("clean" if none was)
+    pub fn abort_current_operation(repo_path: &str) -> Result<String, String> {
+        let state = Self::get_repo_state(repo_path)?;
+        let args: &[&str] = match state.as_str() {
+            "clean" => return Ok(state),
+            "merge" => {
+                Self::abort_merge(repo_path)?;
+                return Ok(state);
+            }
+            "rebase" => {
+                Self::rebase_abort(repo_path)?;
+                return Ok(state);
+            }
+            "cherry-pick" => &["cherry-pick", "--abort"],
+            "revert" => &["revert", "--abort"],
+            "bisect" => &["bisect", "reset"],
+            _ => &["am", "--abort"],
+        };
+
+        let output = cmd_no_window("git")
+            .arg("-C")
+            .arg(repo_path)
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to run git: {}", e))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("git {} failed: {}", args.join(" "), stderr.trim()));
+        }
+        Ok(state)
+    }
+
     // === Tag operations ===
 
     pub fn list_tags(repo_path: &str) -> Result<Vec<(String, String, String)>, String> {
@@ -1488,6 +2695,88 @@ impl GitService {
         Ok(())
     }
 
+    // === Staging ===
+
+    /// `git add` for one path; a path that's gone from disk stages its deletion
+    pub fn stage_file(repo_path: &str, file_path: &str) -> Result<(), String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let mut index = repo.index().map_err(|e| e.to_string())?;
+
+        if std::path::Path::new(repo_path).join(file_path).exists() {
+            // add_path takes the path literally; add_all would treat `app/[id]/page.tsx` as a glob
+            index.add_path(std::path::Path::new(file_path)).map_err(|e| e.to_string())?;
+        } else {
+            index.remove_path(std::path::Path::new(file_path)).map_err(|e| e.to_string())?;
+        }
+        index.write().map_err(|e| e.to_string())
+    }
+
+    /// `git add -A`
+    pub fn stage_all(repo_path: &str) -> Result<(), String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let mut index = repo.index().map_err(|e| e.to_string())?;
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .map_err(|e| e.to_string())?;
+        // add_all only adds; update_all drops entries for files deleted from disk
+        index.update_all(["*"], None).map_err(|e| e.to_string())?;
+        index.write().map_err(|e| e.to_string())
+    }
+
+    /// `git reset HEAD -- <file>`: the index entry goes back to HEAD, the working copy is untouched
+    pub fn unstage_file(repo_path: &str, file_path: &str) -> Result<(), String> {
+        Self::unstage_paths(repo_path, &[file_path.to_string()])
+    }
+
+    pub fn unstage_all(repo_path: &str) -> Result<(), String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let statuses = repo.statuses(None).map_err(|e| e.to_string())?;
+
+        let mut staged = Vec::new();
+        for entry in statuses.iter() {
+            let status = entry.status();
+            if !(status.is_index_new()
+                || status.is_index_modified()
+                || status.is_index_deleted()
+                || status.is_index_renamed()
+                || status.is_index_typechange())
+            {
+                continue;
+            }
+            // A staged rename has to be reset on both sides
+            if let Some(old) = entry.head_to_index().and_then(|d| d.old_file().path()) {
+                staged.push(old.to_string_lossy().to_string());
+            }
+            if let Some(path) = entry.path() {
+                staged.push(path.to_string());
+            }
+        }
+        staged.sort();
+        staged.dedup();
+        if staged.is_empty() {
+            return Ok(());
+        }
+        Self::unstage_paths(repo_path, &staged)
+    }
+
+    fn unstage_paths(repo_path: &str, paths: &[String]) -> Result<(), String> {
+        let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+        let head = repo.head().and_then(|h| h.peel_to_commit());
+        match head {
+            Ok(head) => repo
+                .reset_default(Some(head.as_object()), paths)
+                .map_err(|e| e.to_string()),
+            // No commits yet: unstaging means dropping the entries from the index
+            Err(_) => {
+                let mut index = repo.index().map_err(|e| e.to_string())?;
+                for path in paths {
+                    let _ = index.remove_path(std::path::Path::new(path));
+                }
+                index.write().map_err(|e| e.to_string())
+            }
+        }
+    }
+
     // === Line-level staging ===
 
     pub fn stage_lines(repo_path: &str, file_path: &str, line_ranges: Vec<(u32, u32)>) -> Result<(), String> {
@@ -1579,12 +2868,15 @@ impl GitService {
             }
 
             if !in_header {
+                // Deletions have no line of their own in the new file; they're selected
+                // through the line they sit in front of, which is the first line of their
+                // replacement when there is one
                 let new_line_no = if line.starts_with('+') {
                     let n = current_new_line;
                     current_new_line += 1;
                     Some(n)
                 } else if line.starts_with('-') {
-                    None
+                    Some(current_new_line)
                 } else {
                     current_new_line += 1;
                     None
@@ -1619,16 +2911,20 @@ impl GitService {
         for (line, new_line_no) in lines {
             if line.starts_with('+') {
                 if let Some(n) = new_line_no {
+                    // Unselected additions aren't in the index, so they can't be context
+                    // either; leave them out and they stay unstaged in the working tree
                     if is_line_selected(*n) {
                         filtered_lines.push(std::borrow::Cow::Borrowed(line.as_str()));
-                    } else {
-                        // Convert unselected addition to context
-                        let ctx = format!(" {}", &line[1..]);
-                        filtered_lines.push(std::borrow::Cow::Owned(ctx));
                     }
                 }
             } else if line.starts_with('-') {
-                filtered_lines.push(std::borrow::Cow::Borrowed(line.as_str()));
+                match new_line_no {
+                    Some(n) if is_line_selected(*n) => {
+                        filtered_lines.push(std::borrow::Cow::Borrowed(line.as_str()));
+                    }
+                    // An unselected deletion stays in the index, so it becomes context
+                    _ => filtered_lines.push(std::borrow::Cow::Owned(format!(" {}", &line[1..]))),
+                }
             } else {
                 filtered_lines.push(std::borrow::Cow::Borrowed(line.as_str()));
             }
@@ -1677,51 +2973,137 @@ impl GitService {
 
     // === Image diff support ===
 
-    pub fn get_old_file_content(repo_path: &str, file_path: &str) -> Result<Vec<u8>, String> {
+    /// File bytes as of HEAD. `None` when the file is new or the repo has no commits yet.
+    pub fn get_old_file_content(repo_path: &str, file_path: &str) -> Result<Option<Vec<u8>>, String> {
         let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
-        let head = repo.head().map_err(|e| e.to_string())?;
-        let tree = head.peel_to_tree().map_err(|e| e.to_string())?;
-        let entry = tree.get_path(std::path::Path::new(file_path))
-            .map_err(|_| format!("File {} not found in HEAD", file_path))?;
+        let tree = match repo.head().and_then(|h| h.peel_to_tree()) {
+            Ok(tree) => tree,
+            Err(_) => return Ok(None),
+        };
+        let entry = match tree.get_path(std::path::Path::new(file_path)) {
+            Ok(entry) => entry,
+            Err(_) => return Ok(None),
+        };
         let blob = repo.find_blob(entry.id())
             .map_err(|e| format!("Failed to read blob: {}", e))?;
-        Ok(blob.content().to_vec())
+        // LFS-tracked files are committed as pointers; hand back the real file when git-lfs can
+        if is_lfs_pointer(blob.content()) {
+            if let Some(content) = repo.workdir().and_then(|dir| smudge_lfs_pointer(dir, blob.content())) {
+                return Ok(Some(content));
+            }
+        }
+        Ok(Some(blob.content().to_vec()))
     }
 
     // === Async network operations with timeouts ===
 
-    pub async fn clone_repo_async(url: &str, path: &str) -> Result<String, String> {
-        let child = tokio::process::Command::new("git")
-            .arg("clone")
+    /// `depth` of `None` or 0 is a full clone. `on_progress` is called for each progress
+    /// line git prints (counting, receiving, resolving...).
+    pub async fn clone_repo_async(
+        url: &str,
+        path: &str,
+        depth: Option<u32>,
+        branch: Option<&str>,
+        single_branch: bool,
+        on_progress: impl Fn(crate::CloneProgress) + Send + 'static,
+    ) -> Result<String, String> {
+        use tokio::io::AsyncReadExt;
+
+        let mut cmd = tokio::process::Command::new("git");
+        cmd.arg("clone").arg("--progress");
+        if let Some(depth) = depth.filter(|d| *d > 0) {
+            cmd.arg("--depth").arg(depth.to_string());
+        }
+        if let Some(branch) = branch.filter(|b| !b.is_empty()) {
+            cmd.arg("--branch").arg(branch);
+        }
+        if single_branch {
+            cmd.arg("--single-branch");
+        }
+        let mut child = cmd
+            .arg("--")
             .arg(url)
             .arg(path)
             .stdin(std::process::Stdio::null())
-            .stdout(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::piped())
             .env("GIT_TERMINAL_PROMPT", "0")
             .env("GIT_SSH_COMMAND", "ssh -o BatchMode=yes")
+            .kill_on_drop(true)
             .spawn()
             .map_err(|e| format!("Failed to run git: {}", e))?;
 
-        let output = tokio::time::timeout(std::time::Duration::from_secs(300), child.wait_with_output())
+        // git redraws progress with \r, so split on both line endings. Anything that isn't
+        // progress is kept for the error message.
+        let mut stderr = child.stderr.take().ok_or("Failed to capture git output")?;
+        let clone_path = path.to_string();
+        let reader = tokio::spawn(async move {
+            let mut buf = [0u8; 4096];
+            let mut line = Vec::new();
+            let mut messages = String::new();
+            loop {
+                let n = match stderr.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => n,
+                };
+                for &byte in &buf[..n] {
+                    if byte != b'\r' && byte != b'\n' {
+                        line.push(byte);
+                        continue;
+                    }
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let text = String::from_utf8_lossy(&line).to_string();
+                    match parse_clone_progress(&text) {
+                        Some((phase, percent, current, total)) => on_progress(crate::CloneProgress {
+                            path: clone_path.clone(),
+                            phase,
+                            percent,
+                            current,
+                            total,
+                        }),
+                        None => {
+                            messages.push_str(&text);
+                            messages.push('\n');
+                        }
+                    }
+                    line.clear();
+                }
+            }
+            if !line.is_empty() {
+                messages.push_str(&String::from_utf8_lossy(&line));
+            }
+            messages
+        });
+
+        let status = tokio::time::timeout(std::time::Duration::from_secs(300), child.wait())
             .await
             .map_err(|_| "git clone timed out after 300s".to_string())?
             .map_err(|e| format!("Failed to run git: {}", e))?;
+        let messages = reader.await.unwrap_or_default();
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(stderr.trim().to_string());
+        if !status.success() {
+            return Err(messages.trim().to_string());
         }
 
         Ok(path.to_string())
     }
 
-    pub async fn fetch_async(repo_path: &str, remote: &str) -> Result<(), String> {
-        let child = tokio::process::Command::new("git")
-            .arg("-C")
-            .arg(repo_path)
-            .arg("fetch")
-            .arg(remote)
+    /// Fetch `remote`, or every remote when `all`. `prune` drops remote-tracking refs
+    /// for branches deleted on the remote.
+    pub async fn fetch_async(repo_path: &str, remote: &str, all: bool, prune: bool) -> Result<(), String> {
+        let mut cmd = tokio::process::Command::new("git");
+        cmd.arg("-C").arg(repo_path).arg("fetch");
+        if prune {
+            cmd.arg("--prune");
+        }
+        if all {
+            cmd.arg("--all");
+        } else {
+            cmd.arg(remote);
+        }
+        let child = cmd
             .stdin(std::process::Stdio::null())
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
@@ -1942,6 +3324,35 @@ impl GitService {
         Ok(())
     }
 
+    /// `git push <remote> --delete <name>`
+    pub async fn delete_remote_branch_async(repo_path: &str, remote: &str, name: &str) -> Result<(), String> {
+        let child = tokio::process::Command::new("git")
+            .arg("-C")
+            .arg(repo_path)
+            .arg("push")
+            .arg(remote)
+            .arg("--delete")
+            .arg(name)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .env("GIT_SSH_COMMAND", "ssh -o BatchMode=yes")
+            .spawn()
+            .map_err(|e| format!("Failed to run git: {}", e))?;
+
+        let output = tokio::time::timeout(std::time::Duration::from_secs(120), child.wait_with_output())
+            .await
+            .map_err(|_| "git push --delete timed out after 120s".to_string())?
+            .map_err(|e| format!("Failed to run git: {}", e))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("git push --delete failed: {}", stderr.trim()));
+        }
+        Ok(())
+    }
+
     pub async fn push_tag_async(repo_path: &str, tag: &str, remote: &str) -> Result<(), String> {
         let child = tokio::process::Command::new("git")
             .arg("-C")
@@ -1969,3 +3380,241 @@ impl GitService {
         Ok(())
     }
 }
+
+/// A piece of a conflicted file, see `GitService::parse_conflict_chunks`
+pub enum ConflictChunk {
+    Text(String),
+    Conflict(ConflictRegion),
+}
+
+/// Percent-encode a ref name for use in a URL path, keeping `/` so `feature/x` stays readable
+fn encode_ref(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Append `start..end`, merging with the previous range when they touch
+fn push_range(ranges: &mut Vec<(usize, usize)>, start: usize, end: usize) {
+    match ranges.last_mut() {
+        Some(last) if last.1 == start => last.1 = end,
+        _ => ranges.push((start, end)),
+    }
+}
+
+/// LFS pointers are a few lines of text; anything larger is real content
+const LFS_POINTER_MAX_SIZE: usize = 1024;
+
+/// Whether `content` is a Git LFS pointer file rather than the file itself
+pub fn is_lfs_pointer(content: &[u8]) -> bool {
+    content.len() <= LFS_POINTER_MAX_SIZE && content.starts_with(b"version https://git-lfs.github.com/spec/")
+}
+
+pub fn parse_lfs_pointer(content: &[u8]) -> Option<crate::LfsPointer> {
+    if !is_lfs_pointer(content) {
+        return None;
+    }
+    let text = std::str::from_utf8(content).ok()?;
+    let mut oid = None;
+    let mut size = None;
+    for line in text.lines() {
+        if let Some(value) = line.strip_prefix("oid ") {
+            oid = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("size ") {
+            size = value.trim().parse().ok();
+        }
+    }
+    Some(crate::LfsPointer { oid: oid?, size: size? })
+}
+
+/// Real content for an LFS pointer via `git lfs smudge`, which reads the local LFS cache and
+/// downloads the object if needed. `None` when git-lfs isn't installed or can't get it.
+pub fn smudge_lfs_pointer(repo_workdir: &std::path::Path, pointer: &[u8]) -> Option<Vec<u8>> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = cmd_no_window("git")
+        .args(["lfs", "smudge"])
+        .current_dir(repo_workdir)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    // Pointers are well under the pipe buffer size, so writing before reading can't block
+    let written = child.stdin.take().map_or(false, |mut stdin| stdin.write_all(pointer).is_ok());
+    let output = child.wait_with_output().ok()?;
+    (written && output.status.success()).then_some(output.stdout)
+}
+
+fn is_image_path(path: &str) -> bool {
+    const IMAGE_EXTENSIONS: &[&str] = &[
+        "png", "jpg", "jpeg", "gif", "webp", "bmp", "ico", "svg", "avif", "tif", "tiff",
+    ];
+    std::path::Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Parse a git progress line such as `Receiving objects:  45% (450/1000), 1.2 MiB | 2 MiB/s`
+/// into (phase, percent, current, total)
+fn parse_clone_progress(line: &str) -> Option<(String, u32, Option<u64>, Option<u64>)> {
+    let line = line.trim_start_matches("remote:").trim();
+    let (phase, rest) = line.split_once(':')?;
+    let rest = rest.trim_start();
+    let (percent, rest) = rest.split_once('%')?;
+    let percent: u32 = percent.trim().parse().ok()?;
+
+    let counts = rest
+        .trim_start()
+        .strip_prefix('(')
+        .and_then(|r| r.split_once(')'))
+        .and_then(|(inner, _)| inner.split_once('/'));
+    let (current, total) = match counts {
+        Some((current, total)) => (current.trim().parse().ok(), total.trim().parse().ok()),
+        None => (None, None),
+    };
+    Some((phase.trim().to_string(), percent, current, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Fresh repo in a temp dir with a committer identity set, so commits work on CI
+    fn test_repo() -> (tempfile::TempDir, Repository) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        (dir, repo)
+    }
+
+    fn repo_path(dir: &tempfile::TempDir) -> &str {
+        dir.path().to_str().unwrap()
+    }
+
+    fn commit_file(repo: &Repository, name: &str, content: &str) {
+        std::fs::write(repo.workdir().unwrap().join(name), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = repo.signature().unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, "add file", &tree, &parents).unwrap();
+    }
+
+    fn index_content(repo: &Repository, name: &str) -> String {
+        let mut index = repo.index().unwrap();
+        index.read(true).unwrap();
+        let entry = index.get_path(Path::new(name), 0).unwrap();
+        String::from_utf8(repo.find_blob(entry.id).unwrap().content().to_vec()).unwrap()
+    }
+
+    fn numbered_lines(count: usize) -> Vec<String> {
+        (1..=count).map(|n| format!("line {}", n)).collect()
+    }
+
+    #[test]
+    fn stage_lines_stages_only_selected_lines_across_hunks() {
+        let (dir, repo) = test_repo();
+        let original = numbered_lines(30);
+        commit_file(&repo, "file.txt", &(original.join("\n") + "\n"));
+
+        // Three hunks: lines 2 and 4 changed, a line inserted after 12, line 26 changed
+        let mut modified = original.clone();
+        modified[1] = "changed 2".to_string();
+        modified[3] = "changed 4".to_string();
+        modified[25] = "changed 26".to_string();
+        modified.insert(12, "inserted".to_string());
+        std::fs::write(dir.path().join("file.txt"), modified.join("\n") + "\n").unwrap();
+
+        // New line 2 is the first change, new line 13 the insertion
+        GitService::stage_lines(repo_path(&dir), "file.txt", vec![(2, 2), (13, 13)]).unwrap();
+
+        let mut expected = original.clone();
+        expected[1] = "changed 2".to_string();
+        expected.insert(12, "inserted".to_string());
+        assert_eq!(index_content(&repo, "file.txt"), expected.join("\n") + "\n");
+    }
+
+    #[test]
+    fn commit_on_clean_tree_is_nothing_to_commit() {
+        let (dir, repo) = test_repo();
+        commit_file(&repo, "README.md", "readme\n");
+        let head = repo.head().unwrap().target().unwrap();
+
+        let err = GitService::commit(repo_path(&dir), "Nothing changed", None, false).unwrap_err();
+        assert!(err.starts_with("NOTHING_TO_COMMIT:"), "{}", err);
+        assert_eq!(repo.head().unwrap().target().unwrap(), head);
+
+        GitService::commit(repo_path(&dir), "Re-run CI", None, true).unwrap();
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(commit.message(), Some("Re-run CI"));
+        assert_eq!(commit.parent_id(0).unwrap(), head);
+    }
+
+    #[test]
+    fn commit_rejects_empty_message() {
+        let (dir, repo) = test_repo();
+        commit_file(&repo, "README.md", "readme\n");
+        std::fs::write(dir.path().join("README.md"), "changed\n").unwrap();
+        let head = repo.head().unwrap().target().unwrap();
+
+        for message in ["", "  \n\t"] {
+            assert!(GitService::commit(repo_path(&dir), message, None, false).is_err());
+        }
+        assert_eq!(repo.head().unwrap().target().unwrap(), head);
+    }
+
+    #[test]
+    fn git_config_local_round_trip() {
+        let (dir, _repo) = test_repo();
+        let path = repo_path(&dir);
+        assert_eq!(GitService::get_git_config(path, "orca.testkey").unwrap(), None);
+
+        GitService::set_git_config(path, "orca.testkey", "some value", false).unwrap();
+        assert_eq!(
+            GitService::get_git_config(path, "orca.testkey").unwrap().as_deref(),
+            Some("some value")
+        );
+        // Written to the repo's own config, not the user's
+        let local = git2::Config::open(&dir.path().join(".git").join("config")).unwrap();
+        assert_eq!(local.get_string("orca.testkey").unwrap(), "some value");
+    }
+
+    #[test]
+    fn stage_and_unstage_file_with_glob_characters() {
+        let (dir, repo) = test_repo();
+        commit_file(&repo, "README.md", "readme\n");
+        for path in ["app/[id]/page.tsx", "app/i/page.tsx"] {
+            let full = dir.path().join(path);
+            std::fs::create_dir_all(full.parent().unwrap()).unwrap();
+            std::fs::write(full, "export default function Page() {}\n").unwrap();
+        }
+
+        GitService::stage_file(repo_path(&dir), "app/[id]/page.tsx").unwrap();
+        let status = |path: &str| repo.status_file(Path::new(path)).unwrap();
+        assert_eq!(status("app/[id]/page.tsx"), git2::Status::INDEX_NEW);
+        // `[id]` read as a glob would have matched this one instead
+        assert_eq!(status("app/i/page.tsx"), git2::Status::WT_NEW);
+
+        GitService::unstage_file(repo_path(&dir), "app/[id]/page.tsx").unwrap();
+        assert_eq!(status("app/[id]/page.tsx"), git2::Status::WT_NEW);
+        assert_eq!(status("app/i/page.tsx"), git2::Status::WT_NEW);
+    }
+}
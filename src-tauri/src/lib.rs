@@ -11,6 +11,7 @@ use std::thread;
 use std::time::Duration;
 use tauri::Emitter;
 use tauri::Manager;
+use tokio_util::sync::CancellationToken;
 #[cfg(target_os = "macos")]
 use tauri::menu::{Menu, PredefinedMenuItem, Submenu};
 // use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
@@ -18,10 +19,15 @@ use tauri::menu::{Menu, PredefinedMenuItem, Submenu};
 use tauri::menu::MenuItemBuilder;
 use uuid::Uuid;
 
+mod commit_lint;
 mod database;
 mod git;
 mod github;
+mod logging;
 mod portal;
+mod profiling;
+mod secrets;
+mod shell_history;
 
 /// Create a `std::process::Command` that won't spawn a visible console window on Windows.
 pub fn cmd_no_window(program: &str) -> std::process::Command {
@@ -65,6 +71,17 @@ pub struct Project {
     #[serde(rename = "lastOpened")]
     pub last_opened: String,
     pub folders: Option<Vec<ProjectFolder>>,
+    #[serde(rename = "defaultShell", default)]
+    pub default_shell: Option<String>,
+    #[serde(rename = "startupCommands", default)]
+    pub startup_commands: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Pinned projects sort first regardless of when they were opened
+    #[serde(default)]
+    pub pinned: bool,
 }
 
 // Project file format for .orca files
@@ -73,6 +90,12 @@ pub struct ProjectFileData {
     pub version: u32,
     pub name: String,
     pub folders: Vec<ProjectFolder>,
+    #[serde(rename = "defaultShell", default, skip_serializing_if = "Option::is_none")]
+    pub default_shell: Option<String>,
+    #[serde(rename = "startupCommands", default, skip_serializing_if = "Vec::is_empty")]
+    pub startup_commands: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,13 +106,39 @@ pub struct GitStatus {
     pub staged: Vec<String>,
     pub unstaged: Vec<String>,
     pub untracked: Vec<String>,
+    /// Submodules whose checkout differs from the recorded commit or has local changes;
+    /// these are left out of `unstaged`
+    #[serde(rename = "modifiedSubmodules", default)]
+    pub modified_submodules: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileDiff {
     pub path: String,
+    /// Previous path for "renamed" and "copied" files
+    #[serde(rename = "oldPath", default, skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
     pub status: String,
     pub hunks: Vec<DiffHunk>,
+    #[serde(rename = "isBinary", default)]
+    pub is_binary: bool,
+    /// Image by extension; render before/after instead of text hunks
+    #[serde(rename = "isImage", default)]
+    pub is_image: bool,
+    /// Set when that side is a Git LFS pointer; hunks are dropped since they'd only show
+    /// pointer text, so render a placeholder instead
+    #[serde(rename = "lfsOld", default, skip_serializing_if = "Option::is_none")]
+    pub lfs_old: Option<LfsPointer>,
+    #[serde(rename = "lfsNew", default, skip_serializing_if = "Option::is_none")]
+    pub lfs_new: Option<LfsPointer>,
+}
+
+/// A Git LFS pointer: the object it stands for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LfsPointer {
+    /// `sha256:<hex>`
+    pub oid: String,
+    pub size: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -114,6 +163,9 @@ pub struct DiffLine {
     pub old_line_no: Option<u32>,
     #[serde(rename = "newLineNo")]
     pub new_line_no: Option<u32>,
+    /// Character ranges `(start, end)` that actually changed, for paired -/+ lines
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<(usize, usize)>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -124,6 +176,12 @@ pub struct Branch {
     #[serde(rename = "isRemote")]
     pub is_remote: bool,
     pub upstream: Option<String>,
+    /// Commits ahead of / behind the upstream; only filled in when requested and the
+    /// branch tracks one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ahead: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub behind: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -136,9 +194,169 @@ pub struct Commit {
     #[serde(rename = "authorEmail")]
     pub author_email: String,
     pub timestamp: String,
+    /// Author's UTC offset in minutes, from the commit signature
+    #[serde(rename = "timezoneOffset")]
+    pub timezone_offset: i32,
+    /// RFC 3339 timestamp in the author's original timezone
+    #[serde(rename = "isoTimestamp")]
+    pub iso_timestamp: String,
     pub summary: Option<String>,
 }
 
+/// A branch or tag pointing at a commit in the history graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitRef {
+    pub name: String,
+    /// "local", "remote" or "tag"
+    #[serde(rename = "type")]
+    pub ref_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphCommit {
+    #[serde(flatten)]
+    pub commit: Commit,
+    /// Parent commit ids, first parent first
+    pub parents: Vec<String>,
+    pub refs: Vec<CommitRef>,
+    #[serde(rename = "reachableFromHead")]
+    pub reachable_from_head: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContributorStats {
+    pub name: String,
+    pub email: String,
+    pub commits: usize,
+    pub additions: usize,
+    pub deletions: usize,
+    #[serde(rename = "firstCommit")]
+    pub first_commit: i64,
+    #[serde(rename = "lastCommit")]
+    pub last_commit: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyCommitCount {
+    pub date: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContributorActivity {
+    pub contributors: Vec<ContributorStats>,
+    pub daily: Vec<DailyCommitCount>,
+    #[serde(rename = "totalCommits")]
+    pub total_commits: usize,
+    /// The walk hit the commit cap before reaching `since`
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeadInfo {
+    pub sha: String,
+    #[serde(rename = "shortSha")]
+    pub short_sha: String,
+    pub branch: Option<String>,
+    #[serde(rename = "isDetached")]
+    pub is_detached: bool,
+    #[serde(rename = "tagsHere")]
+    pub tags_here: Vec<String>,
+    pub subject: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReflogEntry {
+    pub index: usize,
+    #[serde(rename = "oldId")]
+    pub old_id: String,
+    #[serde(rename = "newId")]
+    pub new_id: String,
+    #[serde(rename = "shortId")]
+    pub short_id: String,
+    pub author: String,
+    #[serde(rename = "authorEmail")]
+    pub author_email: String,
+    pub message: String,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloneProgress {
+    pub path: String,
+    pub phase: String,
+    pub percent: u32,
+    pub current: Option<u64>,
+    pub total: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LargeFile {
+    pub path: String,
+    pub size: u64,
+    pub staged: bool,
+}
+
+/// One `<<<<<<<` ... `>>>>>>>` block of a conflicted file. `base` is only present
+/// with `merge.conflictStyle = diff3`/`zdiff3`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConflictRegion {
+    /// 1-based line of the `<<<<<<<` marker
+    #[serde(rename = "startLine")]
+    pub start_line: usize,
+    #[serde(rename = "oursLabel")]
+    pub ours_label: String,
+    #[serde(rename = "theirsLabel")]
+    pub theirs_label: String,
+    pub ours: String,
+    pub base: Option<String>,
+    pub theirs: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitAlias {
+    pub name: String,
+    pub command: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteInfo {
+    pub name: String,
+    #[serde(rename = "fetchUrl")]
+    pub fetch_url: Option<String>,
+    #[serde(rename = "pushUrl")]
+    pub push_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergePreview {
+    #[serde(rename = "upToDate")]
+    pub up_to_date: bool,
+    #[serde(rename = "canFastForward")]
+    pub can_fast_forward: bool,
+    #[serde(rename = "mergeBase")]
+    pub merge_base: Option<String>,
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmoduleInfo {
+    pub name: String,
+    pub path: String,
+    pub url: Option<String>,
+    /// Commit checked out in the submodule; `None` until it's initialized
+    #[serde(rename = "headSha")]
+    pub head_sha: Option<String>,
+    /// Commit the superproject records for it (index, falling back to HEAD)
+    #[serde(rename = "recordedSha")]
+    pub recorded_sha: Option<String>,
+    #[serde(rename = "isInitialized")]
+    pub is_initialized: bool,
+    /// Checked out commit differs from the recorded one
+    #[serde(rename = "isOutOfDate")]
+    pub is_out_of_date: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorktreeInfo {
     pub name: String,
@@ -216,6 +434,11 @@ pub struct FileTreeNode {
     pub is_dir: bool,
     pub children: Option<Vec<FileTreeNode>>,
     pub modified: Option<f64>,
+    // File size in bytes; None for directories unless aggregated sizes were requested
+    pub size: Option<u64>,
+    // Set by get_directory_children, where `children` isn't loaded
+    #[serde(rename = "hasChildren", skip_serializing_if = "Option::is_none")]
+    pub has_children: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -226,6 +449,9 @@ pub struct ContentMatch {
     pub line: String,
     #[serde(rename = "absolutePath")]
     pub absolute_path: String,
+    // Character offsets of the first match within `line`
+    pub start: usize,
+    pub end: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -234,6 +460,16 @@ pub struct ContentSearchResult {
     pub truncated: bool,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileMatch {
+    pub path: String,
+    #[serde(rename = "absolutePath")]
+    pub absolute_path: String,
+    pub score: i64,
+    // Character indices in `path` that matched the query, for highlighting
+    pub positions: Vec<usize>,
+}
+
 // Terminal state management
 pub struct TerminalState {
     pub master: Box<dyn portable_pty::MasterPty + Send>,
@@ -243,9 +479,191 @@ pub struct TerminalState {
     pub terminal_type: String,  // "shell" or "assistant"
     pub output_buffer: Arc<Mutex<Vec<u8>>>,  // Buffer for recent output (for mobile attach)
     pub child_pid: Option<u32>,  // PID of the child shell process for explicit cleanup
+    pub recording: Arc<Mutex<Option<TerminalRecording>>>,  // Active asciinema recording, if any
+    pub cwd_from_osc: bool,  // Shell reports its cwd via OSC 7, so don't poll the process
+    pub notify_on_exit: bool,  // Show a desktop notification when the process exits
+}
+
+/// An in-progress asciinema v2 recording of a terminal's output
+pub struct TerminalRecording {
+    pub writer: std::io::BufWriter<std::fs::File>,
+    pub started: std::time::Instant,
+    pub utf8_pending: Vec<u8>,  // Partial UTF-8 character held back from the last read
+}
+
+impl TerminalRecording {
+    /// Append an output event (`[time, "o", data]`) to the cast file
+    pub fn write_output(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let text = decode_utf8_chunk(&mut self.utf8_pending, data);
+        if text.is_empty() {
+            return Ok(());
+        }
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let event = serde_json::json!([elapsed, "o", text]);
+        writeln!(self.writer, "{}", event)
+    }
 }
 
 const MAX_OUTPUT_BUFFER_SIZE: usize = 100 * 1024; // 100KB buffer
+const MAX_OSC_LENGTH: usize = 4096; // Longest OSC sequence we'll buffer across reads
+
+/// Pull complete OSC sequences (`ESC ] code ; payload` terminated by BEL or ST) out of
+/// terminal output. An unterminated sequence at the end of `data` is kept in `pending`
+/// so it can be completed by the next read.
+fn scan_osc_sequences(pending: &mut Vec<u8>, data: &[u8]) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    if pending.is_empty() && !data.contains(&0x1b) {
+        return found;
+    }
+
+    let mut buf = std::mem::take(pending);
+    buf.extend_from_slice(data);
+
+    let mut i = 0;
+    while i + 1 < buf.len() {
+        if buf[i] != 0x1b || buf[i + 1] != b']' {
+            i += 1;
+            continue;
+        }
+        let start = i + 2;
+        let mut end = None;
+        let mut j = start;
+        while j < buf.len() {
+            if buf[j] == 0x07 {
+                end = Some((j, j + 1));
+                break;
+            }
+            if buf[j] == 0x1b {
+                if j + 1 < buf.len() && buf[j + 1] == b'\\' {
+                    end = Some((j, j + 2));
+                    break;
+                }
+                if j + 1 < buf.len() {
+                    break; // Malformed: another escape began before this one ended
+                }
+            }
+            j += 1;
+        }
+        match end {
+            Some((body_end, next)) => {
+                let body = String::from_utf8_lossy(&buf[start..body_end]);
+                if let Some((code, payload)) = body.split_once(';') {
+                    found.push((code.to_string(), payload.to_string()));
+                }
+                i = next;
+            }
+            None if j >= buf.len() => {
+                // Sequence continues in the next read
+                if buf.len() - i <= MAX_OSC_LENGTH {
+                    *pending = buf[i..].to_vec();
+                }
+                break;
+            }
+            None => i = j,
+        }
+    }
+    if pending.is_empty() && buf.last() == Some(&0x1b) {
+        pending.push(0x1b);
+    }
+    found
+}
+
+/// Convert terminal output to a string without mangling a multi-byte UTF-8 character
+/// that straddles two reads. Incomplete trailing bytes are held in `pending` and
+/// prepended to the next chunk.
+fn decode_utf8_chunk(pending: &mut Vec<u8>, data: &[u8]) -> String {
+    let mut buf = std::mem::take(pending);
+    buf.extend_from_slice(data);
+
+    let mut cut = buf.len();
+    for back in 1..=buf.len().min(3) {
+        let b = buf[buf.len() - back];
+        if b & 0xC0 == 0x80 {
+            continue; // Continuation byte, keep looking for the lead byte
+        }
+        let needed = match b {
+            0xF0..=0xFF => 4,
+            0xE0..=0xEF => 3,
+            0xC0..=0xDF => 2,
+            _ => 1,
+        };
+        if needed > back {
+            cut = buf.len() - back;
+        }
+        break;
+    }
+
+    *pending = buf.split_off(cut);
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+/// Decode a terminal's `output_buffer` for replay. Trimming can cut a character at the
+/// front, and the newest read may end mid-character; both partial characters are dropped
+/// rather than shown as U+FFFD. The live stream delivers the trailing one intact.
+fn decode_output_buffer(buffer: &[u8]) -> String {
+    let start = buffer.iter().take(3).take_while(|b| *b & 0xC0 == 0x80).count();
+    decode_utf8_chunk(&mut Vec::new(), &buffer[start..])
+}
+
+/// Extract the path from an OSC 7 `file://host/path` payload
+fn parse_osc7_path(payload: &str) -> Option<String> {
+    let rest = payload.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+
+    // Percent-decode the path
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(b) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    let path = String::from_utf8(decoded).ok()?;
+
+    // Windows shells report file://host/C:/dir
+    #[cfg(target_os = "windows")]
+    let path = match path.as_bytes() {
+        [b'/', _, b':', ..] => path[1..].replace('/', "\\"),
+        _ => path,
+    };
+
+    Some(path)
+}
+
+/// Best-effort lookup of a process's current working directory, used when the
+/// shell doesn't report it via OSC 7
+fn process_cwd(pid: u32) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_link(format!("/proc/{}/cwd", pid))
+            .ok()
+            .map(|p| p.to_string_lossy().to_string())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let output = cmd_no_window("lsof")
+            .args(["-a", "-p", &pid.to_string(), "-d", "cwd", "-Fn"])
+            .stdin(std::process::Stdio::null())
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.strip_prefix('n').map(|p| p.to_string()))
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = pid;
+        None
+    }
+}
 
 // Terminal info for listing
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -257,25 +675,75 @@ pub struct TerminalInfo {
     pub terminal_type: String,
 }
 
-// Git watcher state - holds the debouncer and stop signal
-struct GitWatcher {
-    _debouncer: notify_debouncer_mini::Debouncer<notify::RecommendedWatcher>,
-    _stop_tx: std::sync::mpsc::Sender<()>,
+/// Payload of the `terminal-exited` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalExit {
+    #[serde(rename = "terminalId")]
+    pub terminal_id: String,
+    /// `None` when the process was killed by a signal or couldn't be waited on
+    pub code: Option<u32>,
+    /// Signal number that killed the process (Unix only)
+    pub signal: Option<i32>,
+}
+
+/// A terminal whose process exited in the last `RECENT_EXIT_TTL`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExitedTerminal {
+    #[serde(flatten)]
+    pub terminal: TerminalInfo,
+    pub code: Option<u32>,
+    pub signal: Option<i32>,
+}
+
+/// How long `list_recent_exits` keeps reporting a terminal after its process exits
+const RECENT_EXIT_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+// Per-terminal result for broadcast writes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalWriteResult {
+    pub id: String,
+    pub success: bool,
+    pub error: Option<String>,
 }
 
-// File system watcher state - watches project files for changes
-struct FileWatcher {
-    _debouncer: notify_debouncer_mini::Debouncer<notify::RecommendedWatcher>,
+// Project watcher state - one per project, covering both the working tree and git metadata.
+// watch_repo and watch_project_files share it and it's dropped once neither wants it.
+struct ProjectWatcher {
+    debouncer: notify_debouncer_mini::Debouncer<notify::RecommendedWatcher>,
     _stop_tx: std::sync::mpsc::Sender<()>,
+    extra_ignores: Vec<String>,  // Patterns from watch_project_files on top of the defaults
+    repo_watched: bool,
+    files_watched: bool,
+}
+
+/// Payload of the `project-files-changed`, `git-files-changed` and `fs-files-changed` events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectFilesChanged {
+    #[serde(rename = "projectPath")]
+    pub project_path: String,
+    /// Something inside .git changed (index, HEAD, refs), so git status needs a refetch
+    #[serde(rename = "gitChanged")]
+    pub git_changed: bool,
+    /// Working tree paths that changed, deduped and with ignored paths left out; these
+    /// need a file tree update
+    pub paths: Vec<String>,
 }
 
 struct AppState {
     terminals: Mutex<HashMap<String, TerminalState>>,
-    database: Mutex<Database>,
+    database: Database,
     portal_enabled: Mutex<bool>,
-    git_watchers: Mutex<HashMap<String, GitWatcher>>,
-    file_watchers: Mutex<HashMap<String, FileWatcher>>,
+    project_watchers: Mutex<HashMap<String, ProjectWatcher>>,
+    single_file_watchers: Mutex<HashMap<String, notify_debouncer_mini::Debouncer<notify::RecommendedWatcher>>>,  // watch_file path -> debouncer
     portal: Mutex<Option<Portal>>,
+    db_watcher_running: std::sync::atomic::AtomicBool,
+    ai_requests: Mutex<HashMap<String, CancellationToken>>,  // In-flight AI shell requests by request_id -> cancel token
+    ai_shell_sessions: Mutex<HashMap<String, AiShellSession>>,
+    fs_batch_depth: std::sync::atomic::AtomicUsize,  // Batch file operations in progress; file watchers hold events until 0
+    global_shortcuts: Mutex<HashMap<u32, String>>,  // Registered global shortcut id -> action
+    recent_exits: Mutex<HashMap<String, (ExitedTerminal, std::time::Instant)>>,  // Exited terminals, kept for RECENT_EXIT_TTL
+    active_project: Mutex<Option<String>>,  // Project the desktop is showing, mirrored to Portal
+    pending_opens: Mutex<Option<Vec<std::path::PathBuf>>>,  // Paths from the OS held until the frontend listens; None after
 }
 
 // Debug command to print to terminal
@@ -284,6 +752,23 @@ fn debug_log(message: String) {
     println!("[DEBUG] {}", message);
 }
 
+#[tauri::command]
+fn set_profiling(enabled: bool) {
+    profiling::set_enabled(enabled);
+}
+
+/// Per-command latency stats collected while profiling is enabled
+#[tauri::command]
+fn get_command_timings() -> Vec<profiling::CommandTiming> {
+    profiling::timings()
+}
+
+/// Recent entries from Orca's in-memory log, optionally filtered to `level` and above
+#[tauri::command]
+fn get_app_logs(level: Option<String>, limit: Option<usize>) -> Vec<logging::LogEntry> {
+    logging::recent(level.as_deref(), limit.unwrap_or(500))
+}
+
 #[tauri::command]
 fn get_home_dir() -> Result<String, String> {
     #[cfg(target_os = "windows")]
@@ -292,62 +777,124 @@ fn get_home_dir() -> Result<String, String> {
     { std::env::var("HOME").map_err(|_| "Could not find HOME directory".to_string()) }
 }
 
-/// Request microphone permission on macOS.
-/// This triggers the system permission dialog if not already granted.
+/// Result of `request_microphone_permission`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MicrophonePermission {
+    Authorized,
+    Denied,
+    /// Blocked by parental controls or MDM; the user can't change it
+    Restricted,
+    /// No audio input stack or device to ask for
+    Unavailable,
+}
+
+/// Request microphone access on macOS, showing the system dialog if the user hasn't been asked yet.
 #[cfg(target_os = "macos")]
 #[tauri::command]
-fn request_microphone_permission() -> Result<String, String> {
-    use std::process::Command;
-
-    // Use AppleScript to trigger the microphone permission dialog
-    // This is more reliable than using objc directly
-    let script = r#"
-        tell application "System Events"
-            -- This triggers the microphone permission check
-            set frontApp to name of first application process whose frontmost is true
-        end tell
-
-        -- Use osascript to check/request microphone access via a helper
-        do shell script "osascript -e 'tell application \"System Events\" to return (get volume settings)'"
-    "#;
-
-    // Alternative: Use tccutil or direct TCC database check
-    // For now, just try to trigger the permission by accessing audio
-    let output = Command::new("osascript")
-        .arg("-e")
-        .arg(r#"
-            use framework "AVFoundation"
-            set authStatus to current application's AVCaptureDevice's authorizationStatusForMediaType:(current application's AVMediaTypeAudio)
-            if authStatus = 0 then
-                -- Not determined, request access
-                current application's AVCaptureDevice's requestAccessForMediaType:(current application's AVMediaTypeAudio) completionHandler:(missing value)
-                return "requested"
-            else if authStatus = 3 then
-                return "authorized"
-            else if authStatus = 2 then
-                return "denied"
-            else if authStatus = 1 then
-                return "restricted"
-            else
-                return "unknown"
-            end if
-        "#)
-        .output()
-        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+async fn request_microphone_permission() -> Result<MicrophonePermission, String> {
+    use block2::RcBlock;
+    use objc2::runtime::Bool;
+    use objc2_av_foundation::{AVAuthorizationStatus, AVCaptureDevice, AVMediaTypeAudio};
+
+    // The block and media type aren't Send, so keep them out of scope across the await
+    let receiver = {
+        let media_type = unsafe { AVMediaTypeAudio }.ok_or("AVMediaTypeAudio is unavailable")?;
+        match unsafe { AVCaptureDevice::authorizationStatusForMediaType(media_type) } {
+            AVAuthorizationStatus::Authorized => return Ok(MicrophonePermission::Authorized),
+            AVAuthorizationStatus::Denied => return Ok(MicrophonePermission::Denied),
+            AVAuthorizationStatus::Restricted => return Ok(MicrophonePermission::Restricted),
+            _ => {}
+        }
 
-    if output.status.success() {
-        let result = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        Ok(result)
-    } else {
-        let err = String::from_utf8_lossy(&output.stderr);
-        Err(format!("osascript failed: {}", err))
+        let (sender, receiver) = tokio::sync::oneshot::channel::<bool>();
+        let sender = Mutex::new(Some(sender));
+        let handler = RcBlock::new(move |granted: Bool| {
+            if let Some(sender) = sender.lock().take() {
+                let _ = sender.send(granted.as_bool());
+            }
+        });
+        unsafe { AVCaptureDevice::requestAccessForMediaType_completionHandler(media_type, &handler) };
+        receiver
+    };
+
+    let granted = receiver.await.map_err(|_| "Microphone permission request was dropped".to_string())?;
+    Ok(if granted { MicrophonePermission::Authorized } else { MicrophonePermission::Denied })
+}
+
+/// Windows has no prompt for desktop apps; report the privacy toggles and open the
+/// Settings page when they block us so the user can turn access back on.
+#[cfg(target_os = "windows")]
+#[tauri::command]
+async fn request_microphone_permission() -> Result<MicrophonePermission, String> {
+    const CONSENT_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore\microphone";
+
+    // `reg query` prints e.g. "    Value    REG_SZ    Deny"
+    let denied = |key: String| {
+        cmd_no_window("reg")
+            .args(["query", &key, "/v", "Value"])
+            .output()
+            .map(|o| o.status.success() && String::from_utf8_lossy(&o.stdout).contains("Deny"))
+            .unwrap_or(false)
+    };
+
+    let blocked = tokio::task::spawn_blocking(move || {
+        denied(format!(r"HKLM\{}", CONSENT_KEY))
+            || denied(format!(r"HKCU\{}", CONSENT_KEY))
+            || denied(format!(r"HKCU\{}\NonPackaged", CONSENT_KEY))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?;
+
+    if blocked {
+        let _ = cmd_no_window("cmd")
+            .args(["/C", "start", "", "ms-settings:privacy-microphone"])
+            .spawn();
+        return Ok(MicrophonePermission::Denied);
     }
+    Ok(MicrophonePermission::Authorized)
+}
+
+/// Linux has no permission prompt outside sandboxes, so check that PipeWire or PulseAudio
+/// is running and has at least one capture source.
+#[cfg(target_os = "linux")]
+#[tauri::command]
+async fn request_microphone_permission() -> Result<MicrophonePermission, String> {
+    tokio::task::spawn_blocking(|| {
+        // pactl also talks to PipeWire through pipewire-pulse
+        if let Some(pactl) = find_command_path("pactl") {
+            let output = cmd_no_window(&pactl.to_string_lossy())
+                .args(["list", "short", "sources"])
+                .output();
+            if let Ok(output) = output {
+                if !output.status.success() {
+                    return MicrophonePermission::Unavailable;
+                }
+                // Monitor sources capture speaker output, not a microphone
+                let has_input = String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .filter_map(|line| line.split('\t').nth(1))
+                    .any(|name| !name.ends_with(".monitor"));
+                return if has_input { MicrophonePermission::Authorized } else { MicrophonePermission::Unavailable };
+            }
+        }
+
+        // Without pactl, fall back to looking for the sound server's socket
+        let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_default();
+        let running = !runtime_dir.is_empty()
+            && ["pipewire-0", "pulse/native"]
+                .iter()
+                .any(|socket| std::path::Path::new(&runtime_dir).join(socket).exists());
+        if running { MicrophonePermission::Authorized } else { MicrophonePermission::Unavailable }
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))
 }
 
-#[cfg(not(target_os = "macos"))]
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
 #[tauri::command]
-fn request_microphone_permission() -> Result<String, String> {
-    Ok("not_applicable".to_string())
+fn request_microphone_permission() -> Result<MicrophonePermission, String> {
+    Ok(MicrophonePermission::Unavailable)
 }
 
 /// Fetch secrets from macOS Keychain for environment variables.
@@ -356,6 +903,7 @@ fn request_microphone_permission() -> Result<String, String> {
 /// This runs in Orca's GUI context, so authorization dialogs appear properly.
 #[cfg(target_os = "macos")]
 fn fetch_keychain_env_vars() -> HashMap<String, String> {
+    let _timer = profiling::Timer::start("fetch_keychain_env_vars");
     let mut env_vars = HashMap::new();
 
     // First, dump keychain metadata to find items with "env/" prefix
@@ -429,61 +977,383 @@ fn fetch_keychain_env_vars() -> HashMap<String, String> {
     env_vars
 }
 
-// Terminal commands
-#[tauri::command]
-fn spawn_terminal(
-    shell: String,
-    cwd: String,
-    cols: Option<u16>,
-    rows: Option<u16>,
-    args: Option<Vec<String>>,
-    is_assistant: Option<bool>,
-    app_handle: tauri::AppHandle,
-    state: tauri::State<Arc<AppState>>,
-) -> Result<String, String> {
-    let id = Uuid::new_v4().to_string();
-    let pty_system = native_pty_system();
+/// Linux equivalent of the macOS Keychain lookup: Secret Service items (GNOME Keyring,
+/// KWallet) whose `service` attribute or label starts with "env/", e.g. ones created with
+/// `secret-tool store --label=env/FOO service env/FOO`.
+#[cfg(target_os = "linux")]
+fn fetch_keychain_env_vars() -> HashMap<String, String> {
+    use dbus_secret_service::{EncryptionType, SecretService};
 
-    // Use provided dimensions or fall back to defaults
-    let initial_cols = cols.unwrap_or(80);
-    let initial_rows = rows.unwrap_or(24);
+    let _timer = profiling::Timer::start("fetch_keychain_env_vars");
+    let mut env_vars = HashMap::new();
 
-    let pty_pair = pty_system
-        .openpty(PtySize {
-            rows: initial_rows,
-            cols: initial_cols,
-            pixel_width: 0,
-            pixel_height: 0,
-        })
-        .map_err(|e| e.to_string())?;
+    let service = match SecretService::connect(EncryptionType::Plain) {
+        Ok(s) => s,
+        Err(e) => {
+            log::debug!("[Keychain] Secret Service unavailable: {}", e);
+            return env_vars;
+        }
+    };
+    let collection = match service.get_default_collection() {
+        Ok(c) => c,
+        Err(e) => {
+            log::debug!("[Keychain] No default collection: {}", e);
+            return env_vars;
+        }
+    };
+    let items = match collection.get_all_items() {
+        Ok(items) => items,
+        Err(e) => {
+            log::warn!("[Keychain] Failed to list items: {}", e);
+            return env_vars;
+        }
+    };
 
-    println!("DEBUG spawn_terminal - shell: {:?}, args: {:?}", shell, args);
+    for item in items {
+        let service_name = item
+            .get_attributes()
+            .ok()
+            .and_then(|attrs| attrs.get("service").cloned())
+            .filter(|s| s.starts_with("env/"))
+            .or_else(|| item.get_label().ok().filter(|l| l.starts_with("env/")));
+        let service_name = match service_name {
+            Some(name) => name,
+            None => continue,
+        };
 
-    let mut cmd = if shell.is_empty() {
-        // Use default shell
-        // On Windows, always use powershell.exe (SHELL env var is a Unix convention
-        // and may be set to invalid paths like /usr/bin/bash by Git Bash)
-        #[cfg(target_os = "windows")]
-        let shell_path = "powershell.exe".to_string();
-        #[cfg(not(target_os = "windows"))]
-        let shell_path = std::env::var("SHELL").unwrap_or_else(|_| {
-            #[cfg(target_os = "macos")]
-            { "/bin/zsh".to_string() }
-            #[cfg(target_os = "linux")]
-            { "/bin/bash".to_string() }
-        });
-        println!("DEBUG spawn_terminal - using shell: {:?}", shell_path);
-        CommandBuilder::new(shell_path)
-    } else if let Some(ref arg_list) = args {
-        // Args provided separately - use them directly (handles paths with spaces)
-        let command = &shell;
-        let resolved_command = if command.contains('/') || command.contains('\\') {
-            Some(command.to_string())
-        } else {
-            find_command_path(command).map(|p| p.to_string_lossy().to_string())
+        // A locked item prompts the desktop's unlock dialog, same as the macOS auth dialog
+        let secret = item.ensure_unlocked().and_then(|_| item.get_secret());
+        match secret {
+            Ok(bytes) => {
+                let secret = String::from_utf8_lossy(&bytes).trim().to_string();
+                let env_name = service_name.strip_prefix("env/").unwrap_or(&service_name).to_string();
+                if !env_name.is_empty() && !secret.is_empty() {
+                    log::info!("[Keychain] Loaded secret for {}", env_name);
+                    env_vars.insert(env_name, secret);
+                }
+            }
+            Err(e) => log::warn!("[Keychain] Failed to get {}: {}", service_name, e),
+        }
+    }
+
+    env_vars
+}
+
+/// Windows equivalent of the macOS Keychain lookup: generic credentials in Credential
+/// Manager whose target name starts with "env/", e.g. `cmdkey /generic:env/FOO /user:orca /pass:...`.
+#[cfg(target_os = "windows")]
+fn fetch_keychain_env_vars() -> HashMap<String, String> {
+    use windows_sys::Win32::Security::Credentials::{CredEnumerateW, CredFree, CREDENTIALW};
+
+    let _timer = profiling::Timer::start("fetch_keychain_env_vars");
+    let mut env_vars = HashMap::new();
+
+    let filter: Vec<u16> = "env/*".encode_utf16().chain(std::iter::once(0)).collect();
+    let mut count: u32 = 0;
+    let mut creds: *mut *mut CREDENTIALW = std::ptr::null_mut();
+    // Fails with ERROR_NOT_FOUND when there are no matching credentials
+    if unsafe { CredEnumerateW(filter.as_ptr(), 0, &mut count, &mut creds) } == 0 {
+        return env_vars;
+    }
+
+    for i in 0..count as usize {
+        // SAFETY: CredEnumerateW returned `count` valid credential pointers, freed below
+        let cred = unsafe { &**creds.add(i) };
+        if cred.TargetName.is_null() || cred.CredentialBlob.is_null() {
+            continue;
+        }
+        let target = unsafe {
+            let len = (0..).take_while(|&n| *cred.TargetName.add(n) != 0).count();
+            String::from_utf16_lossy(std::slice::from_raw_parts(cred.TargetName, len))
         };
+        let blob = unsafe { std::slice::from_raw_parts(cred.CredentialBlob, cred.CredentialBlobSize as usize) };
 
-        println!("DEBUG spawn_terminal - resolved command: {:?}", resolved_command);
+        // cmdkey and most tools store UTF-16; fall back to UTF-8 for blobs written as bytes
+        let secret = if blob.len() % 2 == 0 && blob.contains(&0) {
+            let wide: Vec<u16> = blob.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&wide)
+        } else {
+            String::from_utf8_lossy(blob).into_owned()
+        };
+        let secret = secret.trim_end_matches('\0').trim().to_string();
+
+        let env_name = target.strip_prefix("env/").unwrap_or(&target).to_string();
+        if !env_name.is_empty() && !secret.is_empty() {
+            log::info!("[Keychain] Loaded secret for {}", env_name);
+            env_vars.insert(env_name, secret);
+        }
+    }
+
+    unsafe { CredFree(creds as *const std::ffi::c_void) };
+    env_vars
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+fn fetch_keychain_env_vars() -> HashMap<String, String> {
+    HashMap::new()
+}
+
+/// Walk up from `dir` looking for any of `names`
+fn find_upwards(dir: &std::path::Path, names: &[&str]) -> Option<std::path::PathBuf> {
+    dir.ancestors()
+        .flat_map(|d| names.iter().map(move |n| d.join(n)))
+        .find(|p| p.is_file())
+}
+
+/// Resolve the per-directory toolchain environment (direnv, mise, asdf, nvm) for `cwd`.
+/// Returns only the variables that differ from Orca's own environment.
+fn project_environment(cwd: &str) -> Result<HashMap<String, String>, String> {
+    let dir = std::path::Path::new(cwd);
+    let path = get_augmented_path();
+
+    // direnv: `.envrc` is authoritative when present (and must be allowed)
+    if find_upwards(dir, &[".envrc"]).is_some() {
+        if let Some(direnv) = find_command_path("direnv") {
+            let output = cmd_no_window(&direnv.to_string_lossy())
+                .args(["export", "json"])
+                .current_dir(dir)
+                .env("PATH", &path)
+                .output()
+                .map_err(|e| format!("Failed to run direnv: {}", e))?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if stderr.contains("is blocked") {
+                    return Err(format!("DIRENV_BLOCKED: run `direnv allow` in {}", cwd));
+                }
+                return Err(format!("direnv failed: {}", stderr.trim()));
+            }
+            // Empty output means nothing changes; `null` values mean "unset", which we can't express
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if stdout.trim().is_empty() {
+                return Ok(HashMap::new());
+            }
+            let diff: HashMap<String, Option<String>> =
+                serde_json::from_str(&stdout).map_err(|e| format!("Failed to parse direnv output: {}", e))?;
+            return Ok(diff.into_iter().filter_map(|(k, v)| Some((k, v?))).collect());
+        }
+    }
+
+    let tool_files = [".mise.toml", "mise.toml", ".tool-versions", ".nvmrc", ".node-version", ".python-version"];
+    if find_upwards(dir, &tool_files).is_none() {
+        return Ok(HashMap::new());
+    }
+
+    // mise understands .tool-versions, .nvmrc and friends, so prefer it over piecing PATH together
+    if let Some(mise) = find_command_path("mise") {
+        let output = cmd_no_window(&mise.to_string_lossy())
+            .args(["env", "--json"])
+            .current_dir(dir)
+            .env("PATH", &path)
+            .output()
+            .map_err(|e| format!("Failed to run mise: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("mise failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        let env: HashMap<String, String> = serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("Failed to parse mise output: {}", e))?;
+        return Ok(env
+            .into_iter()
+            .filter(|(k, v)| std::env::var(k).ok().as_ref() != Some(v))
+            .collect());
+    }
+
+    // Without mise, fall back to asdf shims and the nvm install matching .nvmrc
+    let home = dirs::home_dir().unwrap_or_default();
+    let separator = if cfg!(windows) { ";" } else { ":" };
+    let mut prepend: Vec<String> = Vec::new();
+
+    if find_upwards(dir, &[".tool-versions"]).is_some() {
+        let asdf_dir = std::env::var("ASDF_DATA_DIR")
+            .map(std::path::PathBuf::from)
+            .unwrap_or_else(|_| home.join(".asdf"));
+        let shims = asdf_dir.join("shims");
+        if shims.is_dir() {
+            prepend.push(shims.to_string_lossy().to_string());
+        }
+    }
+
+    if let Some(nvmrc) = find_upwards(dir, &[".nvmrc", ".node-version"]) {
+        let wanted = std::fs::read_to_string(&nvmrc).unwrap_or_default();
+        let wanted = wanted.trim().trim_start_matches('v');
+        let nvm_dir = std::env::var("NVM_DIR")
+            .map(std::path::PathBuf::from)
+            .unwrap_or_else(|_| home.join(".nvm"));
+        // Pick the newest installed version matching the (possibly partial) spec, e.g. "20" or "20.11"
+        let best = std::fs::read_dir(nvm_dir.join("versions/node"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|e| e.file_name().into_string().ok())
+            .filter(|name| {
+                let version = name.trim_start_matches('v');
+                !wanted.is_empty() && (version == wanted || version.starts_with(&format!("{}.", wanted)))
+            })
+            .max_by_key(|name| {
+                name.trim_start_matches('v')
+                    .split('.')
+                    .map(|p| p.parse::<u64>().unwrap_or(0))
+                    .collect::<Vec<_>>()
+            });
+        if let Some(version) = best {
+            let bin = nvm_dir.join("versions/node").join(version).join("bin");
+            prepend.push(bin.to_string_lossy().to_string());
+        }
+    }
+
+    let mut env = HashMap::new();
+    if !prepend.is_empty() {
+        env.insert("PATH".to_string(), format!("{}{}{}", prepend.join(separator), separator, path));
+    }
+    Ok(env)
+}
+
+/// Parse a dotenv file: `KEY=value` lines with optional `export ` prefixes, single- or
+/// double-quoted values, and `#` comments. Malformed lines are skipped.
+fn parse_env_file(content: &str) -> Vec<(String, String)> {
+    let mut vars = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map(str::trim_start).unwrap_or(line);
+        let Some((key, raw)) = line.split_once('=') else { continue };
+        let key = key.trim();
+        let valid_key = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            continue;
+        }
+
+        let raw = raw.trim();
+        let value = if let Some(rest) = raw.strip_prefix('"') {
+            // Double quotes allow the usual escapes; anything after the closing quote is ignored
+            let mut value = String::new();
+            let mut chars = rest.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => match chars.next() {
+                        Some('n') => value.push('\n'),
+                        Some('r') => value.push('\r'),
+                        Some('t') => value.push('\t'),
+                        Some(other) => value.push(other),
+                        None => break,
+                    },
+                    c => value.push(c),
+                }
+            }
+            value
+        } else if let Some(rest) = raw.strip_prefix('\'') {
+            // Single quotes are literal
+            rest.split('\'').next().unwrap_or("").to_string()
+        } else {
+            // Unquoted: a ` #` starts a trailing comment
+            raw.split(" #").next().unwrap_or("").trim_end().to_string()
+        };
+        vars.push((key.to_string(), value));
+    }
+    vars
+}
+
+/// Environment that direnv/mise/asdf/nvm would set up for `cwd`
+#[tauri::command]
+async fn resolve_project_environment(cwd: String) -> Result<HashMap<String, String>, String> {
+    let _timer = profiling::Timer::start("resolve_project_environment");
+    tokio::task::spawn_blocking(move || project_environment(&cwd))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// How long direnv/mise get to export a directory's environment before a terminal opens without it
+const PROJECT_ENV_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Terminal commands
+#[tauri::command]
+async fn spawn_terminal(
+    shell: String,
+    cwd: String,
+    cols: Option<u16>,
+    rows: Option<u16>,
+    args: Option<Vec<String>>,
+    is_assistant: Option<bool>,
+    project_id: Option<String>,
+    env: Option<HashMap<String, String>>,
+    use_project_env: Option<bool>,
+    load_env_file: Option<bool>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<String, String> {
+    let _timer = profiling::Timer::start("spawn_terminal");
+    let id = Uuid::new_v4().to_string();
+
+    // direnv/mise/asdf toolchains for the directory, so the right node/python is on PATH.
+    // These run external tools (and may hang on a prompt), so keep them off the command thread
+    let project_env = if use_project_env.unwrap_or(false) {
+        let env_cwd = cwd.clone();
+        let task = tokio::task::spawn_blocking(move || project_environment(&env_cwd));
+        Some(
+            tokio::time::timeout(PROJECT_ENV_TIMEOUT, task)
+                .await
+                .map_err(|_| "Timed out loading the project environment".to_string())
+                .and_then(|joined| joined.map_err(|e| format!("Task failed: {}", e)))
+                .and_then(|result| result),
+        )
+    } else {
+        None
+    };
+
+    // Per-project shell settings apply when opening a plain shell for that project
+    let project = project_id
+        .as_deref()
+        .and_then(|pid| state.database.get_project(pid).ok().flatten());
+    let is_plain_shell = shell.is_empty();
+    let shell = match project.as_ref().and_then(|p| p.default_shell.clone()) {
+        Some(default_shell) if is_plain_shell => default_shell,
+        _ => shell,
+    };
+    let pty_system = native_pty_system();
+
+    // Use provided dimensions or fall back to defaults
+    let initial_cols = cols.unwrap_or(80);
+    let initial_rows = rows.unwrap_or(24);
+
+    let pty_pair = pty_system
+        .openpty(PtySize {
+            rows: initial_rows,
+            cols: initial_cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .map_err(|e| e.to_string())?;
+
+    println!("DEBUG spawn_terminal - shell: {:?}, args: {:?}", shell, args);
+
+    let mut cmd = if shell.is_empty() {
+        // Use default shell
+        // On Windows, always use powershell.exe (SHELL env var is a Unix convention
+        // and may be set to invalid paths like /usr/bin/bash by Git Bash)
+        #[cfg(target_os = "windows")]
+        let shell_path = "powershell.exe".to_string();
+        #[cfg(not(target_os = "windows"))]
+        let shell_path = std::env::var("SHELL").unwrap_or_else(|_| {
+            #[cfg(target_os = "macos")]
+            { "/bin/zsh".to_string() }
+            #[cfg(target_os = "linux")]
+            { "/bin/bash".to_string() }
+        });
+        println!("DEBUG spawn_terminal - using shell: {:?}", shell_path);
+        CommandBuilder::new(shell_path)
+    } else if let Some(ref arg_list) = args {
+        // Args provided separately - use them directly (handles paths with spaces)
+        let command = &shell;
+        let resolved_command = if command.contains('/') || command.contains('\\') {
+            Some(command.to_string())
+        } else {
+            find_command_path(command).map(|p| p.to_string_lossy().to_string())
+        };
+
+        println!("DEBUG spawn_terminal - resolved command: {:?}", resolved_command);
 
         if let Some(full_path) = resolved_command {
             let mut cmd = CommandBuilder::new(&full_path);
@@ -642,13 +1512,6 @@ fn spawn_terminal(
         if std::path::Path::new(&pyenv_root).exists() {
             cmd.env("PYENV_ROOT", &pyenv_root);
         }
-
-        // Pre-fetch Keychain secrets and set as environment variables
-        // This runs in Orca's GUI context, so authorization dialogs appear properly
-        let keychain_vars = fetch_keychain_env_vars();
-        for (key, value) in keychain_vars {
-            cmd.env(key, value);
-        }
     }
 
     #[cfg(target_os = "linux")]
@@ -702,6 +1565,52 @@ fn spawn_terminal(
         cmd.env("PATH", new_path);
     }
 
+    // Pre-fetch "env/*" secrets from the OS keychain and set them as environment variables.
+    // This runs in Orca's GUI context, so authorization dialogs appear properly
+    let keychain_vars = fetch_keychain_env_vars();
+
+    // The project's .env only fills in what neither Orca's environment nor the keychain sets
+    if load_env_file.unwrap_or(false) {
+        let env_path = std::path::Path::new(&cwd).join(".env");
+        match std::fs::read_to_string(&env_path) {
+            Ok(content) => {
+                for (key, value) in parse_env_file(&content) {
+                    if std::env::var_os(&key).is_none() && !keychain_vars.contains_key(&key) {
+                        cmd.env(key, value);
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("spawn_terminal - failed to read {}: {}", env_path.display(), e),
+        }
+    }
+
+    for (key, value) in keychain_vars {
+        cmd.env(key, value);
+    }
+
+    // A project environment failure (e.g. a blocked .envrc) shouldn't stop the terminal from opening
+    match project_env {
+        Some(Ok(dir_env)) => {
+            for (key, value) in dir_env {
+                cmd.env(key, value);
+            }
+        }
+        Some(Err(e)) => log::warn!("spawn_terminal - project environment: {}", e),
+        None => {}
+    }
+
+    // Project-level env overrides win over everything inherited above,
+    // and explicit env (e.g. from a terminal profile) wins over the project's
+    if let Some(ref p) = project {
+        for (key, value) in &p.env {
+            cmd.env(key, value);
+        }
+    }
+    for (key, value) in env.iter().flatten() {
+        cmd.env(key, value);
+    }
+
     // Destructure the PtyPair to separate master and slave
     let PtyPair { master: master_pty, slave: slave_pty } = pty_pair;
 
@@ -709,7 +1618,7 @@ fn spawn_terminal(
         .spawn_command(cmd)
         .map_err(|e| {
             let err_msg = format!("Failed to spawn terminal process: {}", e);
-            println!("ERROR spawn_terminal - {}", err_msg);
+            log::error!("spawn_terminal - {}", err_msg);
             err_msg
         })?;
 
@@ -720,7 +1629,18 @@ fn spawn_terminal(
     // the slave handle open prevents output from flowing to the master/reader.
     drop(slave_pty);
 
-    let writer = master_pty.take_writer().map_err(|e| e.to_string())?;
+    let mut writer = master_pty.take_writer().map_err(|e| e.to_string())?;
+
+    // Type the project's startup commands into the shell so they show in scrollback
+    if is_plain_shell {
+        if let Some(ref p) = project {
+            for command in &p.startup_commands {
+                let _ = writer.write_all(format!("{}\r", command).as_bytes());
+            }
+            let _ = writer.flush();
+        }
+    }
+
     let mut reader = master_pty.try_clone_reader().map_err(|e| e.to_string())?;
 
     let terminal_id = id.clone();
@@ -731,10 +1651,15 @@ fn spawn_terminal(
     let output_buffer: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::with_capacity(MAX_OUTPUT_BUFFER_SIZE)));
     let output_buffer_clone = output_buffer.clone();
 
+    let recording: Arc<Mutex<Option<TerminalRecording>>> = Arc::new(Mutex::new(None));
+    let recording_clone = recording.clone();
+
     // Spawn thread to read terminal output
     println!("DEBUG spawn_terminal - starting reader thread for terminal {}", terminal_id);
     thread::spawn(move || {
         let mut buffer = [0u8; 16384]; // Larger buffer for better throughput
+        let mut osc_pending: Vec<u8> = Vec::new();
+        let mut utf8_pending: Vec<u8> = Vec::new();
         let event_name = format!("terminal-output-{}", terminal_id);
         loop {
             match reader.read(&mut buffer) {
@@ -757,11 +1682,70 @@ fn spawn_terminal(
 
                         // Forward live output to mobile via portal
                         if let Some(ref portal) = *state_for_read.portal.lock() {
-                            let raw_data = String::from_utf8_lossy(&buffer[..n]);
+                            let raw_data = decode_utf8_chunk(&mut utf8_pending, &buffer[..n]);
                             crate::portal::forward_terminal_output(portal, &terminal_id, &raw_data);
                         }
                     }
 
+                    // Track cwd (OSC 7) and window title (OSC 0/2) reported by the shell
+                    for (code, payload) in scan_osc_sequences(&mut osc_pending, &buffer[..n]) {
+                        match code.as_str() {
+                            "7" => {
+                                let Some(new_cwd) = parse_osc7_path(&payload) else { continue };
+                                let changed = match state_for_read.terminals.lock().get_mut(&terminal_id) {
+                                    Some(t) => {
+                                        t.cwd_from_osc = true;
+                                        if t.cwd != new_cwd {
+                                            t.cwd = new_cwd.clone();
+                                            true
+                                        } else {
+                                            false
+                                        }
+                                    }
+                                    None => false,
+                                };
+                                if changed {
+                                    let _ = handle.emit("terminal-cwd-changed", serde_json::json!({
+                                        "terminalId": terminal_id,
+                                        "cwd": new_cwd
+                                    }));
+                                }
+                            }
+                            "0" | "2" => {
+                                let new_title: String = payload.chars().filter(|c| !c.is_control()).collect();
+                                let new_title = new_title.trim().to_string();
+                                if new_title.is_empty() {
+                                    continue;
+                                }
+                                let changed = match state_for_read.terminals.lock().get_mut(&terminal_id) {
+                                    Some(t) if t.title != new_title => {
+                                        t.title = new_title.clone();
+                                        true
+                                    }
+                                    _ => false,
+                                };
+                                if changed {
+                                    let _ = handle.emit("terminal-title-changed", serde_json::json!({
+                                        "terminalId": terminal_id,
+                                        "title": new_title
+                                    }));
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    // Tee output into the cast file if this terminal is being recorded
+                    {
+                        let mut rec = recording_clone.lock();
+                        if let Some(ref mut r) = *rec {
+                            if let Err(e) = r.write_output(&buffer[..n]) {
+                                log::warn!("Recording write failed for terminal {}: {}", terminal_id, e);
+                                *rec = None;
+                            }
+                        }
+                    }
+
                     // Use base64 encoding for efficient transfer (much smaller than JSON array)
                     let encoded = BASE64.encode(&buffer[..n]);
                     // Emit to terminal-specific event (for desktop Terminal component)
@@ -783,9 +1767,37 @@ fn spawn_terminal(
     // Spawn thread to wait for child exit
     let terminal_id_exit = id.clone();
     let state_clone = state.inner().clone();
+    let exit_handle = app_handle.clone();
     thread::spawn(move || {
-        let _ = child.wait();
-        state_clone.terminals.lock().remove(&terminal_id_exit);
+        let status = child.wait().ok();
+        let signal = status.as_ref().and_then(exit_signal);
+        let exit = TerminalExit {
+            terminal_id: terminal_id_exit.clone(),
+            code: status.filter(|_| signal.is_none()).map(|s| s.exit_code()),
+            signal,
+        };
+
+        let removed = state_clone.terminals.lock().remove(&terminal_id_exit);
+        if let Some(ref terminal) = removed {
+            let exited = ExitedTerminal {
+                terminal: TerminalInfo {
+                    id: terminal_id_exit.clone(),
+                    title: terminal.title.clone(),
+                    cwd: terminal.cwd.clone(),
+                    terminal_type: terminal.terminal_type.clone(),
+                },
+                code: exit.code,
+                signal: exit.signal,
+            };
+            let mut recent_exits = state_clone.recent_exits.lock();
+            recent_exits.retain(|_, (_, exited_at)| exited_at.elapsed() < RECENT_EXIT_TTL);
+            recent_exits.insert(terminal_id_exit, (exited, std::time::Instant::now()));
+        }
+
+        let _ = exit_handle.emit("terminal-exited", &exit);
+        if let Some(terminal) = removed.filter(|t| t.notify_on_exit) {
+            notify_terminal_exit(&exit_handle, &terminal.title, &exit);
+        }
     });
 
     // Determine title from shell command
@@ -819,6 +1831,9 @@ fn spawn_terminal(
         terminal_type,
         output_buffer,
         child_pid,
+        recording,
+        cwd_from_osc: false,
+        notify_on_exit: false,
     };
 
     state.terminals.lock().insert(id.clone(), terminal_state);
@@ -826,6 +1841,85 @@ fn spawn_terminal(
     Ok(id)
 }
 
+
+// App settings - generic key/value preferences shared with Portal
+#[tauri::command]
+fn get_setting(key: String, state: tauri::State<Arc<AppState>>) -> Result<Option<String>, String> {
+    state.database.get_setting(&key)
+}
+
+#[tauri::command]
+fn set_setting(key: String, value: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    if key.trim().is_empty() {
+        return Err("Setting key cannot be empty".to_string());
+    }
+    state.database.set_setting(&key, &value)
+}
+
+#[tauri::command]
+fn get_all_settings(state: tauri::State<Arc<AppState>>) -> Result<HashMap<String, String>, String> {
+    state.database.get_all_settings()
+}
+
+// Terminal profiles - named shell/args/cwd/env presets
+#[tauri::command]
+fn get_terminal_profiles(state: tauri::State<Arc<AppState>>) -> Result<Vec<database::TerminalProfile>, String> {
+    state.database.get_terminal_profiles()
+}
+
+#[tauri::command]
+fn save_terminal_profile(profile: database::TerminalProfile, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    if profile.name.trim().is_empty() {
+        return Err("Profile name cannot be empty".to_string());
+    }
+    state.database.save_terminal_profile(&profile)
+}
+
+#[tauri::command]
+fn delete_terminal_profile(name: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    state.database.delete_terminal_profile(&name)
+}
+
+/// Spawn a terminal from a saved profile. `cwd_override` takes precedence over the
+/// profile's cwd, which falls back to the home directory.
+#[tauri::command]
+async fn spawn_terminal_from_profile(
+    name: String,
+    cwd_override: Option<String>,
+    cols: Option<u16>,
+    rows: Option<u16>,
+    project_id: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<String, String> {
+    let profile = state
+        .database
+        .get_terminal_profile(&name)?
+        .ok_or_else(|| format!("Terminal profile not found: {}", name))?;
+
+    let cwd = cwd_override
+        .or(profile.cwd)
+        .or_else(|| dirs::home_dir().map(|h| h.to_string_lossy().to_string()))
+        .ok_or("No working directory for terminal")?;
+    let args = (!profile.args.is_empty()).then_some(profile.args);
+
+    spawn_terminal(
+        profile.shell,
+        cwd,
+        cols,
+        rows,
+        args,
+        Some(profile.is_assistant),
+        project_id,
+        Some(profile.env),
+        None,
+        None,
+        app_handle,
+        state,
+    )
+    .await
+}
+
 #[tauri::command]
 fn write_terminal(id: String, data: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
     let mut terminals = state.terminals.lock();
@@ -856,6 +1950,101 @@ fn write_terminal_bytes(id: String, data: Vec<u8>, state: tauri::State<Arc<AppSt
     }
 }
 
+/// Broadcast the same input to several terminals. A failed write to one terminal
+/// (e.g. one that has exited) is reported per-id and doesn't stop the others.
+#[tauri::command]
+fn write_terminals(ids: Vec<String>, data: String, state: tauri::State<Arc<AppState>>) -> Vec<TerminalWriteResult> {
+    let mut terminals = state.terminals.lock();
+    ids.into_iter()
+        .map(|id| {
+            let result = match terminals.get_mut(&id) {
+                Some(terminal) => terminal
+                    .writer
+                    .write_all(data.as_bytes())
+                    .and_then(|_| terminal.writer.flush())
+                    .map_err(|e| e.to_string()),
+                None => Err(format!("Terminal not found: {}", id)),
+            };
+            TerminalWriteResult {
+                id,
+                success: result.is_ok(),
+                error: result.err(),
+            }
+        })
+        .collect()
+}
+
+/// Override a terminal's title. Programs can still change it later via OSC 0/2.
+#[tauri::command]
+fn set_terminal_title(
+    id: String,
+    title: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Arc<AppState>>,
+) -> Result<(), String> {
+    {
+        let mut terminals = state.terminals.lock();
+        let terminal = terminals.get_mut(&id).ok_or_else(|| format!("Terminal not found: {}", id))?;
+        terminal.title = title.clone();
+    }
+    let _ = app_handle.emit("terminal-title-changed", serde_json::json!({
+        "terminalId": id,
+        "title": title
+    }));
+    Ok(())
+}
+
+/// Start teeing a terminal's output into an asciinema v2 `.cast` file
+#[tauri::command]
+fn start_terminal_recording(id: String, output_path: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    let terminals = state.terminals.lock();
+    let terminal = terminals.get(&id).ok_or_else(|| format!("Terminal not found: {}", id))?;
+
+    let mut rec = terminal.recording.lock();
+    if rec.is_some() {
+        return Err("Terminal is already being recorded".to_string());
+    }
+
+    let size = terminal.master.get_size().map_err(|e| e.to_string())?;
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let header = serde_json::json!({
+        "version": 2,
+        "width": size.cols,
+        "height": size.rows,
+        "timestamp": timestamp,
+        "title": terminal.title,
+        "env": { "TERM": "xterm-256color" },
+    });
+
+    let file = std::fs::File::create(&output_path).map_err(|e| format!("Failed to create recording file: {}", e))?;
+    let mut writer = std::io::BufWriter::new(file);
+    writeln!(writer, "{}", header).map_err(|e| e.to_string())?;
+
+    *rec = Some(TerminalRecording {
+        writer,
+        started: std::time::Instant::now(),
+        utf8_pending: Vec::new(),
+    });
+    Ok(())
+}
+
+/// Stop recording a terminal, flushing the cast file. The terminal keeps running.
+#[tauri::command]
+fn stop_terminal_recording(id: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    let terminals = state.terminals.lock();
+    let terminal = terminals.get(&id).ok_or_else(|| format!("Terminal not found: {}", id))?;
+    let recording = terminal.recording.lock().take();
+    drop(terminals);
+
+    match recording {
+        Some(mut r) => r.writer.flush().map_err(|e| e.to_string()),
+        None => Err("Terminal is not being recorded".to_string()),
+    }
+}
+
 #[tauri::command]
 fn resize_terminal(
     id: String,
@@ -913,39 +2102,223 @@ fn kill_terminals(ids: Vec<String>, state: tauri::State<Arc<AppState>>) -> Resul
     Ok(())
 }
 
-#[tauri::command]
-fn list_terminals(state: tauri::State<Arc<AppState>>) -> Vec<TerminalInfo> {
-    let terminals = state.terminals.lock();
-    println!("[list_terminals] Found {} terminals", terminals.len());
-    terminals
-        .iter()
-        .map(|(id, t)| {
-            println!("[list_terminals] Terminal: {} title={} cwd={} type={}", id, t.title, t.cwd, t.terminal_type);
-            TerminalInfo {
-                id: id.clone(),
-                title: t.title.clone(),
-                cwd: t.cwd.clone(),
-                terminal_type: t.terminal_type.clone(),
-            }
-        })
-        .collect()
+/// Portable signal names accepted by `signal_terminal`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TerminalSignal {
+    Interrupt,
+    Terminate,
+    Kill,
+    Quit,
 }
 
+/// Send a control signal to the process running in a terminal without tearing down the PTY.
+/// A terminal that is no longer tracked has already exited, which is not an error.
 #[tauri::command]
-fn clear_terminals(state: tauri::State<Arc<AppState>>) {
+fn signal_terminal(id: String, signal: TerminalSignal, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
     let mut terminals = state.terminals.lock();
-    let all: Vec<TerminalState> = terminals.drain().map(|(_, t)| t).collect();
-    println!("[clear_terminals] Killing {} terminals", all.len());
-    for terminal in all {
-        kill_terminal_process(terminal);
-    }
-}
+    let terminal = match terminals.get_mut(&id) {
+        Some(t) => t,
+        None => return Ok(()),
+    };
 
-#[tauri::command]
-fn get_terminal_buffer(id: String, state: tauri::State<Arc<AppState>>) -> Result<String, String> {
-    let terminals = state.terminals.lock();
-    if let Some(terminal) = terminals.get(&id) {
-        let buf = terminal.output_buffer.lock();
+    #[cfg(unix)]
+    {
+        let sig = match signal {
+            TerminalSignal::Interrupt => libc::SIGINT,
+            TerminalSignal::Terminate => libc::SIGTERM,
+            TerminalSignal::Kill => libc::SIGKILL,
+            TerminalSignal::Quit => libc::SIGQUIT,
+        };
+
+        // Prefer the PTY's foreground process group so Ctrl-C reaches the running
+        // command instead of the shell; fall back to the shell's own PID
+        let target = match terminal.master.process_group_leader() {
+            Some(pgid) if pgid > 0 => -pgid,
+            _ => match terminal.child_pid {
+                Some(pid) => pid as i32,
+                None => return Ok(()),
+            },
+        };
+
+        let result = unsafe { libc::kill(target, sig) };
+        if result != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ESRCH) {
+                return Ok(()); // Process already exited
+            }
+            return Err(format!("Failed to signal terminal: {}", err));
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        match signal {
+            TerminalSignal::Interrupt | TerminalSignal::Quit => {
+                // ConPTY turns ETX into a CTRL_C_EVENT for the attached console
+                terminal.writer.write_all(b"\x03").map_err(|e| e.to_string())?;
+                terminal.writer.flush().map_err(|e| e.to_string())?;
+            }
+            TerminalSignal::Terminate | TerminalSignal::Kill => {
+                let pid = match terminal.child_pid {
+                    Some(pid) => pid,
+                    None => return Ok(()),
+                };
+                let mut cmd = cmd_no_window("taskkill");
+                cmd.args(["/PID", &pid.to_string(), "/T"]);
+                if matches!(signal, TerminalSignal::Kill) {
+                    cmd.arg("/F");
+                }
+                let output = cmd
+                    .stdin(std::process::Stdio::null())
+                    .output()
+                    .map_err(|e| format!("Failed to run taskkill: {}", e))?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    if stderr.contains("not found") {
+                        return Ok(()); // Process already exited
+                    }
+                    return Err(format!("taskkill failed: {}", stderr.trim()));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Show a desktop notification when the process in terminal `id` exits
+#[tauri::command]
+fn set_terminal_notify_on_exit(id: String, enabled: bool, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    let mut terminals = state.terminals.lock();
+    let terminal = terminals.get_mut(&id).ok_or_else(|| format!("Terminal not found: {}", id))?;
+    terminal.notify_on_exit = enabled;
+    Ok(())
+}
+
+/// `strsignal` descriptions from glibc and the BSD libc on macOS, for the signals a
+/// terminal's process is realistically killed by
+#[cfg(unix)]
+const SIGNAL_DESCRIPTIONS: &[(&str, i32)] = &[
+    ("Hangup", libc::SIGHUP),
+    ("Interrupt", libc::SIGINT),
+    ("Quit", libc::SIGQUIT),
+    ("Illegal instruction", libc::SIGILL),
+    ("Trace/breakpoint trap", libc::SIGTRAP),
+    ("Trace/BPT trap", libc::SIGTRAP),
+    ("Aborted", libc::SIGABRT),
+    ("Abort trap", libc::SIGABRT),
+    ("Bus error", libc::SIGBUS),
+    ("Floating point exception", libc::SIGFPE),
+    ("Floating-point exception", libc::SIGFPE),
+    ("Killed", libc::SIGKILL),
+    ("User defined signal 1", libc::SIGUSR1),
+    ("Segmentation fault", libc::SIGSEGV),
+    ("User defined signal 2", libc::SIGUSR2),
+    ("Broken pipe", libc::SIGPIPE),
+    ("Alarm clock", libc::SIGALRM),
+    ("Terminated", libc::SIGTERM),
+    ("Stopped (signal)", libc::SIGSTOP),
+    ("Suspended (signal)", libc::SIGSTOP),
+    ("Stopped", libc::SIGTSTP),
+    ("Suspended", libc::SIGTSTP),
+    ("CPU time limit exceeded", libc::SIGXCPU),
+    ("Cputime limit exceeded", libc::SIGXCPU),
+    ("File size limit exceeded", libc::SIGXFSZ),
+    ("Filesize limit exceeded", libc::SIGXFSZ),
+    ("Virtual timer expired", libc::SIGVTALRM),
+    ("Profiling timer expired", libc::SIGPROF),
+    ("Bad system call", libc::SIGSYS),
+];
+
+/// Signal number that terminated the process. portable-pty only keeps the `strsignal`
+/// description, so map it back to the number.
+#[cfg(unix)]
+fn exit_signal(status: &portable_pty::ExitStatus) -> Option<i32> {
+    signal_number(status.signal()?)
+}
+
+#[cfg(unix)]
+fn signal_number(description: &str) -> Option<i32> {
+    if let Some(number) = description.strip_prefix("Signal ").and_then(|n| n.parse().ok()) {
+        return Some(number);
+    }
+    // macOS appends the number, e.g. "Killed: 9"
+    if let Some(number) = description.rsplit_once(": ").and_then(|(_, n)| n.parse().ok()) {
+        return Some(number);
+    }
+    SIGNAL_DESCRIPTIONS
+        .iter()
+        .find(|(name, _)| *name == description)
+        .map(|&(_, number)| number)
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &portable_pty::ExitStatus) -> Option<i32> {
+    None
+}
+
+fn notify_terminal_exit(app: &tauri::AppHandle, title: &str, exit: &TerminalExit) {
+    use tauri_plugin_notification::NotificationExt;
+
+    let body = match (exit.code, exit.signal) {
+        (Some(0), _) => "Finished successfully".to_string(),
+        (Some(code), _) => format!("Exited with code {}", code),
+        (None, Some(signal)) => format!("Killed by signal {}", signal),
+        (None, None) => "Exited".to_string(),
+    };
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        log::warn!("Failed to show exit notification: {}", e);
+    }
+}
+
+#[tauri::command]
+fn list_terminals(state: tauri::State<Arc<AppState>>) -> Vec<TerminalInfo> {
+    let mut terminals = state.terminals.lock();
+    println!("[list_terminals] Found {} terminals", terminals.len());
+    terminals
+        .iter_mut()
+        .map(|(id, t)| {
+            // Shells that don't emit OSC 7 get their cwd from the OS instead
+            if !t.cwd_from_osc {
+                if let Some(cwd) = t.child_pid.and_then(process_cwd) {
+                    t.cwd = cwd;
+                }
+            }
+            println!("[list_terminals] Terminal: {} title={} cwd={} type={}", id, t.title, t.cwd, t.terminal_type);
+            TerminalInfo {
+                id: id.clone(),
+                title: t.title.clone(),
+                cwd: t.cwd.clone(),
+                terminal_type: t.terminal_type.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Terminals that exited recently, so the UI can show how they ended
+#[tauri::command]
+fn list_recent_exits(state: tauri::State<Arc<AppState>>) -> Vec<ExitedTerminal> {
+    let mut recent_exits = state.recent_exits.lock();
+    recent_exits.retain(|_, (_, exited_at)| exited_at.elapsed() < RECENT_EXIT_TTL);
+    recent_exits.values().map(|(exited, _)| exited.clone()).collect()
+}
+
+#[tauri::command]
+fn clear_terminals(state: tauri::State<Arc<AppState>>) {
+    let mut terminals = state.terminals.lock();
+    let all: Vec<TerminalState> = terminals.drain().map(|(_, t)| t).collect();
+    println!("[clear_terminals] Killing {} terminals", all.len());
+    for terminal in all {
+        kill_terminal_process(terminal);
+    }
+}
+
+#[tauri::command]
+fn get_terminal_buffer(id: String, state: tauri::State<Arc<AppState>>) -> Result<String, String> {
+    let terminals = state.terminals.lock();
+    if let Some(terminal) = terminals.get(&id) {
+        let buf = terminal.output_buffer.lock();
         // Return base64-encoded buffer content
         Ok(BASE64.encode(&buf[..]))
     } else {
@@ -961,23 +2334,56 @@ fn is_git_repo(path: String) -> Result<bool, String> {
 
 #[tauri::command]
 async fn get_status(repo_path: String) -> Result<GitStatus, String> {
+    let _timer = profiling::Timer::start("get_status");
     tokio::task::spawn_blocking(move || GitService::get_status(&repo_path))
         .await
         .map_err(|e| format!("Task failed: {}", e))?
 }
 
 #[tauri::command]
-async fn get_diff(repo_path: String) -> Result<Vec<FileDiff>, String> {
-    tokio::task::spawn_blocking(move || GitService::get_diff(&repo_path))
-        .await
-        .map_err(|e| format!("Task failed: {}", e))?
+async fn get_diff(
+    repo_path: String,
+    ignore_whitespace: Option<bool>,
+    context_lines: Option<u32>,
+    rename_threshold: Option<u16>,
+    find_copies: Option<bool>,
+) -> Result<Vec<FileDiff>, String> {
+    let _timer = profiling::Timer::start("get_diff");
+    tokio::task::spawn_blocking(move || {
+        GitService::get_diff(
+            &repo_path,
+            ignore_whitespace.unwrap_or(false),
+            context_lines,
+            rename_threshold,
+            find_copies.unwrap_or(false),
+        )
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
 }
 
 #[tauri::command]
-async fn commit(repo_path: String, message: String, files: Option<Vec<String>>) -> Result<(), String> {
-    tokio::task::spawn_blocking(move || GitService::commit(&repo_path, &message, files))
-        .await
-        .map_err(|e| format!("Task failed: {}", e))?
+async fn commit(
+    repo_path: String,
+    message: String,
+    files: Option<Vec<String>>,
+    coauthors: Option<Vec<String>>,
+    sign_off: Option<bool>,
+    allow_empty: Option<bool>,
+) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        // Check before trailers are appended, or a blank message would pass as just trailers
+        GitService::validate_commit_message(&message)?;
+        let message = GitService::add_commit_trailers(
+            &repo_path,
+            &message,
+            &coauthors.unwrap_or_default(),
+            sign_off.unwrap_or(false),
+        )?;
+        GitService::commit(&repo_path, &message, files, allow_empty.unwrap_or(false))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
 }
 
 #[tauri::command]
@@ -995,8 +2401,30 @@ async fn add_as_submodule(repo_path: String, nested_path: String) -> Result<(),
 }
 
 #[tauri::command]
-fn get_branches(repo_path: String) -> Result<Vec<Branch>, String> {
-    GitService::get_branches(&repo_path)
+async fn list_submodules(repo_path: String) -> Result<Vec<SubmoduleInfo>, String> {
+    tokio::task::spawn_blocking(move || GitService::list_submodules(&repo_path))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+async fn update_submodules(repo_path: String, init: bool, recursive: bool) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || GitService::update_submodules(&repo_path, init, recursive))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+async fn find_nested_repos(path: String, max_depth: Option<usize>) -> Result<Vec<String>, String> {
+    tokio::task::spawn_blocking(move || GitService::find_nested_repos(&path, max_depth.unwrap_or(4)))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+fn get_branches(repo_path: String, include_ahead_behind: Option<bool>) -> Result<Vec<Branch>, String> {
+    let _timer = profiling::Timer::start("get_branches");
+    GitService::get_branches(&repo_path, include_ahead_behind.unwrap_or(false))
 }
 
 #[tauri::command]
@@ -1011,25 +2439,165 @@ fn create_branch(repo_path: String, name: String) -> Result<(), String> {
     GitService::create_branch(&repo_path, &name)
 }
 
+#[tauri::command]
+fn rename_branch(repo_path: String, old_name: String, new_name: String) -> Result<(), String> {
+    GitService::rename_branch(&repo_path, &old_name, &new_name)
+}
+
+#[tauri::command]
+fn delete_branch(repo_path: String, name: String, force: bool) -> Result<(), String> {
+    GitService::delete_branch(&repo_path, &name, force)
+}
+
+#[tauri::command]
+async fn delete_remote_branch(repo_path: String, remote: String, name: String) -> Result<(), String> {
+    GitService::delete_remote_branch_async(&repo_path, &remote, &name).await
+}
+
 #[tauri::command]
 async fn get_history(repo_path: String, limit: u32) -> Result<Vec<Commit>, String> {
+    let _timer = profiling::Timer::start("get_history");
     tokio::task::spawn_blocking(move || GitService::get_history(&repo_path, limit))
         .await
         .map_err(|e| format!("Task failed: {}", e))?
 }
 
 #[tauri::command]
-async fn get_commit_diff(repo_path: String, commit_id: String) -> Result<Vec<FileDiff>, String> {
-    tokio::task::spawn_blocking(move || GitService::get_commit_diff(&repo_path, &commit_id))
+async fn get_commit_graph(repo_path: String, limit: u32) -> Result<Vec<GraphCommit>, String> {
+    let _timer = profiling::Timer::start("get_commit_graph");
+    tokio::task::spawn_blocking(move || GitService::get_commit_graph(&repo_path, limit))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Reflog for `ref_name` (default HEAD), newest first. Lets users find commits orphaned
+/// by a hard reset and check them out again.
+#[tauri::command]
+async fn get_reflog(
+    repo_path: String,
+    ref_name: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<ReflogEntry>, String> {
+    tokio::task::spawn_blocking(move || {
+        GitService::get_reflog(&repo_path, ref_name.as_deref().unwrap_or("HEAD"), limit.unwrap_or(100))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Current HEAD position in one call. `None` for a repository with no commits yet.
+#[tauri::command]
+fn get_head_info(repo_path: String) -> Result<Option<HeadInfo>, String> {
+    GitService::get_head_info(&repo_path)
+}
+
+/// Per-author commit and line counts plus a daily commit series for the insights view.
+/// `since`/`until` are unix seconds.
+#[tauri::command]
+async fn get_contributor_activity(
+    repo_path: String,
+    since: Option<i64>,
+    until: Option<i64>,
+) -> Result<ContributorActivity, String> {
+    let _timer = profiling::Timer::start("get_contributor_activity");
+    tokio::task::spawn_blocking(move || {
+        GitService::get_contributor_activity(&repo_path, since, until)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+async fn get_commit_diff(
+    repo_path: String,
+    commit_id: String,
+    ignore_whitespace: Option<bool>,
+    context_lines: Option<u32>,
+) -> Result<Vec<FileDiff>, String> {
+    let _timer = profiling::Timer::start("get_commit_diff");
+    tokio::task::spawn_blocking(move || {
+        GitService::get_commit_diff(
+            &repo_path,
+            &commit_id,
+            ignore_whitespace.unwrap_or(false),
+            context_lines,
+        )
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+async fn diff_refs(
+    repo_path: String,
+    base: String,
+    head: String,
+    merge_base: Option<bool>,
+    ignore_whitespace: Option<bool>,
+    context_lines: Option<u32>,
+) -> Result<Vec<FileDiff>, String> {
+    let _timer = profiling::Timer::start("diff_refs");
+    tokio::task::spawn_blocking(move || {
+        GitService::diff_refs(
+            &repo_path,
+            &base,
+            &head,
+            merge_base.unwrap_or(false),
+            ignore_whitespace.unwrap_or(false),
+            context_lines,
+        )
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Files changed on the current branch since its merge-base with `base`
+/// (defaults to the repo's default branch)
+#[tauri::command]
+async fn get_branch_changes(repo_path: String, base: Option<String>) -> Result<Vec<FileDiff>, String> {
+    let _timer = profiling::Timer::start("get_branch_changes");
+    tokio::task::spawn_blocking(move || GitService::get_branch_changes(&repo_path, base.as_deref()))
         .await
         .map_err(|e| format!("Task failed: {}", e))?
 }
 
+/// Changed or staged files bigger than `threshold_bytes` (default 10 MB), largest first,
+/// so the commit UI can suggest LFS or .gitignore before they land in history
+#[tauri::command]
+async fn check_large_files(repo_path: String, threshold_bytes: Option<u64>) -> Result<Vec<LargeFile>, String> {
+    tokio::task::spawn_blocking(move || {
+        GitService::check_large_files(&repo_path, threshold_bytes.unwrap_or(10 * 1024 * 1024))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
 #[tauri::command]
 fn discard_file(repo_path: String, file_path: String) -> Result<(), String> {
     GitService::discard_file(&repo_path, &file_path)
 }
 
+#[tauri::command]
+async fn restore_file_from_ref(
+    repo_path: String,
+    file_path: String,
+    git_ref: String,
+    stage: Option<bool>,
+    delete_if_missing: Option<bool>,
+) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        GitService::restore_file_from_ref(
+            &repo_path,
+            &file_path,
+            &git_ref,
+            stage.unwrap_or(false),
+            delete_if_missing.unwrap_or(false),
+        )
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
 #[tauri::command]
 fn add_to_gitignore(repo_path: String, pattern: String) -> Result<(), String> {
     GitService::add_to_gitignore(&repo_path, &pattern)
@@ -1040,6 +2608,57 @@ fn get_remote_url(repo_path: String) -> Result<String, String> {
     GitService::get_remote_url(&repo_path)
 }
 
+#[tauri::command]
+fn get_git_config(repo_path: String, key: String) -> Result<Option<String>, String> {
+    GitService::get_git_config(&repo_path, &key)
+}
+
+#[tauri::command]
+fn set_git_config(repo_path: String, key: String, value: String, global: Option<bool>) -> Result<(), String> {
+    GitService::set_git_config(&repo_path, &key, &value, global.unwrap_or(false))
+}
+
+// Remote commands
+#[tauri::command]
+fn list_remotes(repo_path: String) -> Result<Vec<RemoteInfo>, String> {
+    GitService::list_remotes(&repo_path)
+}
+
+#[tauri::command]
+fn add_remote(repo_path: String, name: String, url: String) -> Result<(), String> {
+    GitService::add_remote(&repo_path, &name, &url)
+}
+
+#[tauri::command]
+fn remove_remote(repo_path: String, name: String) -> Result<(), String> {
+    GitService::remove_remote(&repo_path, &name)
+}
+
+#[tauri::command]
+fn rename_remote(repo_path: String, old_name: String, new_name: String) -> Result<(), String> {
+    GitService::rename_remote(&repo_path, &old_name, &new_name)
+}
+
+#[tauri::command]
+fn set_remote_url(repo_path: String, name: String, url: String) -> Result<(), String> {
+    GitService::set_remote_url(&repo_path, &name, &url)
+}
+
+#[tauri::command]
+fn get_git_aliases(repo_path: String) -> Result<Vec<GitAlias>, String> {
+    GitService::get_git_aliases(&repo_path)
+}
+
+#[tauri::command]
+fn set_git_alias(repo_path: String, name: String, command: String, global: Option<bool>) -> Result<(), String> {
+    GitService::set_git_alias(&repo_path, &name, &command, global.unwrap_or(false))
+}
+
+#[tauri::command]
+fn get_compare_url(repo_path: String, base: String, head: String) -> Result<String, String> {
+    GitService::get_compare_url(&repo_path, &base, &head)
+}
+
 #[tauri::command]
 fn discard_hunk(
     repo_path: String,
@@ -1068,92 +2687,228 @@ fn revert_commit(repo_path: String, commit_id: String) -> Result<(), String> {
     GitService::revert_commit(&repo_path, &commit_id)
 }
 
+/// Walk `root`, skipping the directories named in `skip` and, unless `show_hidden`, dotfiles.
+/// With `respect_gitignore` the repo's .gitignore files (nested and global) and
+/// .git/info/exclude apply as well; outside a git repo that leaves just `skip`.
+fn project_walk(
+    root: &std::path::Path,
+    show_hidden: bool,
+    respect_gitignore: bool,
+    skip: &'static [&'static str],
+    max_depth: usize,
+) -> ignore::Walk {
+    ignore::WalkBuilder::new(root)
+        .standard_filters(false)
+        .git_ignore(respect_gitignore)
+        .git_global(respect_gitignore)
+        .git_exclude(respect_gitignore)
+        .parents(respect_gitignore)
+        .follow_links(true)
+        .max_depth(Some(max_depth))
+        .filter_entry(move |entry| {
+            let name = entry.file_name().to_string_lossy();
+            (show_hidden || !name.starts_with('.')) && !skip.contains(&name.as_ref())
+        })
+        .build()
+}
+
 #[tauri::command]
-async fn get_file_tree(path: String, show_hidden: bool) -> Result<Vec<FileTreeNode>, String> {
+async fn get_file_tree(
+    path: String,
+    show_hidden: bool,
+    respect_gitignore: Option<bool>,
+    dir_sizes: Option<bool>,
+) -> Result<Vec<FileTreeNode>, String> {
+    let _timer = profiling::Timer::start("get_file_tree");
     tokio::task::spawn_blocking(move || {
         use std::fs;
         use std::path::Path;
 
         const FILE_COUNT_CAP: usize = 50_000;
 
-        fn build_tree(dir_path: &Path, base_path: &Path, depth: usize, show_hidden: bool, count: &mut usize) -> Result<Vec<FileTreeNode>, String> {
-            if depth > 10 || *count >= FILE_COUNT_CAP {
-                return Ok(vec![]);
-            }
-
-            let mut nodes = Vec::new();
-            let entries = fs::read_dir(dir_path).map_err(|e| e.to_string())?;
-
-            for entry in entries {
-                if *count >= FILE_COUNT_CAP {
-                    break;
+        fn sort_nodes(nodes: &mut [FileTreeNode]) {
+            nodes.sort_by(|a, b| {
+                match (a.is_dir, b.is_dir) {
+                    (true, false) => std::cmp::Ordering::Less,
+                    (false, true) => std::cmp::Ordering::Greater,
+                    _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
                 }
+            });
+        }
 
-                let entry = entry.map_err(|e| e.to_string())?;
-                let path = entry.path();
-                let name = entry.file_name().to_string_lossy().to_string();
-
-                if !show_hidden && name.starts_with('.') {
-                    continue;
-                }
+        // Hand the innermost open directory its finished children
+        fn close_dir(stack: &mut Vec<Vec<FileTreeNode>>) {
+            let mut children = stack.pop().unwrap_or_default();
+            sort_nodes(&mut children);
+            if let Some(dir) = stack.last_mut().and_then(|siblings| siblings.last_mut()) {
+                dir.children = Some(children);
+            }
+        }
 
-                if name == "node_modules" || name == "target" || name == "__pycache__" || name == "dist" || name == "build" {
-                    continue;
+        // Sum file sizes up into their directories (only what's in the tree, so capped trees undercount)
+        fn fill_dir_sizes(nodes: &mut [FileTreeNode]) -> u64 {
+            nodes.iter_mut().map(|node| {
+                if let Some(ref mut children) = node.children {
+                    node.size = Some(fill_dir_sizes(children));
                 }
+                node.size.unwrap_or(0)
+            }).sum()
+        }
 
-                *count += 1;
-
-                let relative_path = path.strip_prefix(base_path)
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_else(|_| name.clone());
+        let root = Path::new(&path);
+        fs::read_dir(root).map_err(|e| e.to_string())?;
 
-                let is_dir = path.is_dir();
-                let modified = fs::metadata(&path)
-                    .and_then(|m| m.modified())
-                    .ok()
-                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                    .map(|d| d.as_secs_f64());
-                let children = if is_dir {
-                    Some(build_tree(&path, base_path, depth + 1, show_hidden, count)?)
-                } else {
-                    None
-                };
+        // The walk is depth-first, so `stack` holds the children gathered so far for each
+        // directory between the root (stack[0]) and the current entry
+        let mut stack: Vec<Vec<FileTreeNode>> = vec![Vec::new()];
+        let mut count = 0usize;
+        let skip = &["node_modules", "target", "__pycache__", "dist", "build"];
+        for entry in project_walk(root, show_hidden, respect_gitignore.unwrap_or(false), skip, 11).flatten() {
+            if entry.depth() == 0 {
+                continue;
+            }
+            if count >= FILE_COUNT_CAP {
+                break;
+            }
+            count += 1;
+            while stack.len() > entry.depth() {
+                close_dir(&mut stack);
+            }
 
-                nodes.push(FileTreeNode {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let relative_path = entry.path().strip_prefix(root)
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|_| name.clone());
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            let metadata = entry.metadata().ok();
+            let modified = metadata.as_ref()
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs_f64());
+            let size = if is_dir { None } else { metadata.as_ref().map(|m| m.len()) };
+
+            if let Some(siblings) = stack.last_mut() {
+                siblings.push(FileTreeNode {
                     name,
                     path: relative_path,
                     is_dir,
-                    children,
+                    children: if is_dir { Some(Vec::new()) } else { None },
                     modified,
+                    size,
+                    has_children: None,
                 });
             }
-
-            nodes.sort_by(|a, b| {
-                match (a.is_dir, b.is_dir) {
-                    (true, false) => std::cmp::Ordering::Less,
-                    (false, true) => std::cmp::Ordering::Greater,
-                    _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-                }
-            });
-
-            Ok(nodes)
+            if is_dir {
+                stack.push(Vec::new());
+            }
         }
-
-        let mut count = 0usize;
-        let path = Path::new(&path);
-        build_tree(path, path, 0, show_hidden, &mut count)
+        while stack.len() > 1 {
+            close_dir(&mut stack);
+        }
+        let mut tree = stack.pop().unwrap_or_default();
+        sort_nodes(&mut tree);
+        if dir_sizes.unwrap_or(false) {
+            fill_dir_sizes(&mut tree);
+        }
+        Ok(tree)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Immediate children of `path` for lazily expanding the file tree
+#[tauri::command]
+async fn get_directory_children(path: String, show_hidden: bool, root: Option<String>) -> Result<Vec<FileTreeNode>, String> {
+    tokio::task::spawn_blocking(move || {
+        use std::fs;
+        use std::path::Path;
+
+        let dir_path = Path::new(&path);
+        // Node paths are relative to the tree root so they match get_file_tree's
+        let base_path = root.as_deref().map(Path::new).unwrap_or(dir_path);
+
+        let mut nodes = Vec::new();
+        for entry in fs::read_dir(dir_path).map_err(|e| e.to_string())? {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if !show_hidden && name.starts_with('.') {
+                continue;
+            }
+
+            if name == "node_modules" || name == "target" || name == "__pycache__" || name == "dist" || name == "build" {
+                continue;
+            }
+
+            let relative_path = path.strip_prefix(base_path)
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|_| name.clone());
+
+            let is_dir = path.is_dir();
+            let metadata = fs::metadata(&path).ok();
+            let modified = metadata.as_ref()
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs_f64());
+            let size = if is_dir { None } else { metadata.as_ref().map(|m| m.len()) };
+            // Only peek at the first entry - enough to decide whether to show an expander
+            let has_children = if is_dir {
+                fs::read_dir(&path)
+                    .map(|mut entries| entries.any(|e| {
+                        e.map(|e| show_hidden || !e.file_name().to_string_lossy().starts_with('.'))
+                            .unwrap_or(false)
+                    }))
+                    .unwrap_or(false)
+            } else {
+                false
+            };
+
+            nodes.push(FileTreeNode {
+                name,
+                path: relative_path,
+                is_dir,
+                children: None,
+                modified,
+                size,
+                has_children: Some(has_children),
+            });
+        }
+
+        nodes.sort_by(|a, b| {
+            match (a.is_dir, b.is_dir) {
+                (true, false) => std::cmp::Ordering::Less,
+                (false, true) => std::cmp::Ordering::Greater,
+                _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            }
+        });
+
+        Ok(nodes)
     })
     .await
     .map_err(|e| format!("Task failed: {}", e))?
 }
 
-fn search_file_contents_sync(path: String, query: String, show_hidden: bool, max_results: Option<usize>) -> Result<ContentSearchResult, String> {
+fn search_file_contents_sync(
+    path: String,
+    query: String,
+    show_hidden: bool,
+    max_results: Option<usize>,
+    regex: bool,
+    case_sensitive: bool,
+    respect_gitignore: bool,
+) -> Result<ContentSearchResult, String> {
     use std::fs;
     use std::io::{BufRead, BufReader};
     use std::path::Path;
 
     let max = max_results.unwrap_or(100);
-    let query_lower = query.to_lowercase();
+    // Plain queries are escaped so both modes share one matcher
+    let pattern = if regex { query } else { regex::escape(&query) };
+    let matcher = regex::RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid regex: {}", e))?;
     let mut matches: Vec<ContentMatch> = Vec::new();
     let mut truncated = false;
 
@@ -1167,115 +2922,234 @@ fn search_file_contents_sync(path: String, query: String, show_hidden: bool, max
         ".sqlite", ".db", ".pyc", ".class", ".o", ".a", ".wasm",
     ];
 
-    fn walk_dir(
-        dir_path: &Path,
-        base_path: &Path,
-        query_lower: &str,
-        show_hidden: bool,
-        binary_extensions: &[&str],
-        matches: &mut Vec<ContentMatch>,
-        max: usize,
-        truncated: &mut bool,
-        depth: usize,
-    ) {
-        if depth > 10 || *truncated {
-            return;
-        }
-
-        let entries = match fs::read_dir(dir_path) {
-            Ok(e) => e,
-            Err(_) => return,
+    let base = Path::new(&path);
+    let skip = &["node_modules", "target", "__pycache__", "dist", "build", ".git"];
+    'files: for entry in project_walk(base, show_hidden, respect_gitignore, skip, 11).flatten() {
+        if matches.len() >= max {
+            truncated = true;
+            break;
+        }
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        let name_lower = name.to_lowercase();
+        if binary_extensions.iter().any(|ext| name_lower.ends_with(ext)) {
+            continue;
+        }
+
+        if let Ok(metadata) = entry.metadata() {
+            if metadata.len() > 1_048_576 {
+                continue;
+            }
+        }
+
+        let file = match fs::File::open(path) {
+            Ok(f) => f,
+            Err(_) => continue,
         };
 
-        for entry in entries {
+        let reader = BufReader::new(file);
+        for (line_idx, line_result) in reader.lines().enumerate() {
             if matches.len() >= max {
-                *truncated = true;
-                return;
+                truncated = true;
+                break 'files;
             }
 
-            let entry = match entry {
-                Ok(e) => e,
-                Err(_) => continue,
+            let line = match line_result {
+                Ok(l) => l,
+                Err(_) => break,
             };
 
-            let path = entry.path();
-            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(m) = matcher.find(&line) {
+                let relative_path = path.strip_prefix(base)
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_else(|_| name.clone());
 
-            if !show_hidden && name.starts_with('.') {
-                continue;
+                let start = line[..m.start()].chars().count();
+                let end = start + m.as_str().chars().count();
+
+                matches.push(ContentMatch {
+                    path: relative_path,
+                    line_number: line_idx + 1,
+                    line: if line.len() > 500 { line[..500].to_string() } else { line },
+                    absolute_path: path.to_string_lossy().to_string(),
+                    start,
+                    end,
+                });
             }
+        }
+    }
 
-            if name == "node_modules" || name == "target" || name == "__pycache__" || name == "dist" || name == "build" || name == ".git" {
-                continue;
+    Ok(ContentSearchResult { matches, truncated })
+}
+
+#[tauri::command]
+async fn search_file_contents(
+    path: String,
+    query: String,
+    show_hidden: bool,
+    max_results: Option<usize>,
+    regex: Option<bool>,
+    case_sensitive: Option<bool>,
+    respect_gitignore: Option<bool>,
+) -> Result<ContentSearchResult, String> {
+    let _timer = profiling::Timer::start("search_file_contents");
+    tokio::task::spawn_blocking(move || {
+        search_file_contents_sync(
+            path,
+            query,
+            show_hidden,
+            max_results,
+            regex.unwrap_or(false),
+            case_sensitive.unwrap_or(false),
+            respect_gitignore.unwrap_or(false),
+        )
+    })
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Score `candidate` as an fzf-style subsequence match of `query` (already lowercased).
+/// Rewards consecutive runs, word boundaries and hits in the file name.
+fn fuzzy_score(query: &[char], candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let name_start = chars.iter().rposition(|c| *c == '/' || *c == '\\').map(|i| i + 1).unwrap_or(0);
+
+    let score_from = |start: usize| -> Option<(i64, Vec<usize>)> {
+        let mut positions = Vec::with_capacity(query.len());
+        let mut next = start;
+        for qc in query {
+            let found = (next..lower.len()).find(|&i| lower[i] == *qc)?;
+            positions.push(found);
+            next = found + 1;
+        }
+
+        let mut score: i64 = 0;
+        let mut prev: Option<usize> = None;
+        for &pos in &positions {
+            score += 16;
+            if pos > 0 && prev == Some(pos - 1) {
+                score += 24;
+            } else if let Some(p) = prev {
+                score -= (pos - p - 1).min(20) as i64;
             }
+            let at_boundary = pos == 0 || matches!(chars[pos - 1], '/' | '\\' | '_' | '-' | '.' | ' ');
+            let camel = pos > 0 && chars[pos].is_uppercase() && chars[pos - 1].is_lowercase();
+            if at_boundary || camel {
+                score += 20;
+            }
+            if pos >= name_start {
+                score += 10;
+            }
+            prev = Some(pos);
+        }
+        score -= (chars.len() / 8) as i64;
+        Some((score, positions))
+    };
 
-            if path.is_dir() {
-                walk_dir(&path, base_path, query_lower, show_hidden, binary_extensions, matches, max, truncated, depth + 1);
-            } else {
-                let name_lower = name.to_lowercase();
-                if binary_extensions.iter().any(|ext| name_lower.ends_with(ext)) {
-                    continue;
-                }
+    // Prefer matching entirely within the file name when possible
+    let full = score_from(0)?;
+    match score_from(name_start) {
+        Some(in_name) if name_start > 0 && in_name.0 > full.0 => Some(in_name),
+        _ => Some(full),
+    }
+}
 
-                if let Ok(metadata) = fs::metadata(&path) {
-                    if metadata.len() > 1_048_576 {
-                        continue;
-                    }
-                }
+fn find_files_sync(path: String, query: String, show_hidden: bool, max_results: usize) -> Result<Vec<FileMatch>, String> {
+    use std::fs;
+    use std::path::Path;
 
-                let file = match fs::File::open(&path) {
-                    Ok(f) => f,
-                    Err(_) => continue,
-                };
+    const MAX_DEPTH: usize = 12;
+    const MAX_FILES_SCANNED: usize = 100_000;
 
-                let reader = BufReader::new(file);
-                for (line_idx, line_result) in reader.lines().enumerate() {
-                    if matches.len() >= max {
-                        *truncated = true;
-                        return;
-                    }
+    let query: Vec<char> = query.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+    let base = Path::new(&path);
+    if !base.is_dir() {
+        return Err("Path does not exist or is not a directory".to_string());
+    }
 
-                    let line = match line_result {
-                        Ok(l) => l,
-                        Err(_) => break,
-                    };
-
-                    if line.to_lowercase().contains(query_lower) {
-                        let relative_path = path.strip_prefix(base_path)
-                            .map(|p| p.to_string_lossy().to_string())
-                            .unwrap_or_else(|_| name.clone());
-
-                        matches.push(ContentMatch {
-                            path: relative_path,
-                            line_number: line_idx + 1,
-                            line: if line.len() > 500 { line[..500].to_string() } else { line },
-                            absolute_path: path.to_string_lossy().to_string(),
-                        });
-                    }
+    let mut results: Vec<FileMatch> = Vec::new();
+    let mut scanned = 0usize;
+    let mut stack = vec![(base.to_path_buf(), 0usize)];
+
+    while let Some((dir, depth)) = stack.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            if scanned >= MAX_FILES_SCANNED {
+                break;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !show_hidden && name.starts_with('.') {
+                continue;
+            }
+            if matches!(name.as_str(), "node_modules" | "target" | "__pycache__" | "dist" | "build" | ".git") {
+                continue;
+            }
+
+            let entry_path = entry.path();
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            if is_dir {
+                if depth < MAX_DEPTH {
+                    stack.push((entry_path, depth + 1));
                 }
+                continue;
+            }
+
+            scanned += 1;
+            let relative = entry_path.strip_prefix(base)
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|_| name.clone());
+            if let Some((score, positions)) = fuzzy_score(&query, &relative) {
+                results.push(FileMatch {
+                    path: relative,
+                    absolute_path: entry_path.to_string_lossy().to_string(),
+                    score,
+                    positions,
+                });
             }
         }
     }
 
-    let base = Path::new(&path);
-    walk_dir(base, base, &query_lower, show_hidden, &binary_extensions, &mut matches, max, &mut truncated, 0);
-
-    Ok(ContentSearchResult { matches, truncated })
+    results.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.path.len().cmp(&b.path.len())));
+    results.truncate(max_results);
+    Ok(results)
 }
 
+/// Ctrl-P style quick open: fuzzy-match file paths under `path`
 #[tauri::command]
-async fn search_file_contents(path: String, query: String, show_hidden: bool, max_results: Option<usize>) -> Result<ContentSearchResult, String> {
-    tokio::task::spawn_blocking(move || search_file_contents_sync(path, query, show_hidden, max_results))
-        .await
-        .map_err(|e| format!("Task failed: {}", e))?
+async fn find_files(path: String, query: String, max_results: Option<usize>, show_hidden: Option<bool>) -> Result<Vec<FileMatch>, String> {
+    tokio::task::spawn_blocking(move || {
+        find_files_sync(path, query, show_hidden.unwrap_or(false), max_results.unwrap_or(50))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
 }
 
+/// Move a file or directory to the system trash. Pass `permanent` to delete outright.
+/// Errors prefixed with `TRASH_UNAVAILABLE` mean trashing isn't possible here; the
+/// frontend should ask before retrying with `permanent`.
 #[tauri::command]
-fn delete_file(path: String) -> Result<(), String> {
+fn delete_file(path: String, permanent: Option<bool>) -> Result<(), String> {
     use std::fs;
     use std::path::Path;
 
     let path = Path::new(&path);
+    if !path.exists() && !path.is_symlink() {
+        return Err(format!("Path does not exist: {}", path.display()));
+    }
+
+    if !permanent.unwrap_or(false) {
+        return trash::delete(path)
+            .map_err(|e| format!("TRASH_UNAVAILABLE: Could not move to trash: {}", e));
+    }
+
     if path.is_dir() {
         fs::remove_dir_all(path).map_err(|e| e.to_string())
     } else {
@@ -1283,6 +3157,91 @@ fn delete_file(path: String) -> Result<(), String> {
     }
 }
 
+/// Copy a file, or a directory tree recursively. If `dst` is an existing directory the
+/// source is copied into it. Returns the final destination path. Errors prefixed with
+/// `ALREADY_EXISTS` mean `overwrite` was needed; `COPY_FAILED` names the file that broke
+/// a partial copy.
+#[tauri::command]
+async fn copy_path(src: String, dst: String, overwrite: Option<bool>) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        use std::path::{Path, PathBuf};
+
+        let src = Path::new(&src);
+        if !src.exists() {
+            return Err(format!("Path does not exist: {}", src.display()));
+        }
+
+        let mut target = PathBuf::from(&dst);
+        if target.is_dir() {
+            let name = src.file_name().ok_or("Cannot copy a filesystem root")?;
+            target.push(name);
+        }
+
+        // Copying a file onto itself truncates it, so compare after resolving symlinks and `..`
+        if matches!((src.canonicalize(), target.canonicalize()), (Ok(a), Ok(b)) if a == b) {
+            return Err("Cannot copy a path onto itself".to_string());
+        }
+        if target.exists() && !overwrite.unwrap_or(false) {
+            return Err(format!("ALREADY_EXISTS: {}", target.display()));
+        }
+        if src.is_dir() {
+            let canonical_src = src.canonicalize().map_err(|e| e.to_string())?;
+            let canonical_parent = target
+                .parent()
+                .and_then(|p| p.canonicalize().ok())
+                .unwrap_or_default();
+            if canonical_parent.starts_with(&canonical_src) {
+                return Err("Cannot copy a directory into itself".to_string());
+            }
+        }
+
+        copy_recursive(src, &target).map_err(|(path, e)| {
+            format!("COPY_FAILED: {}: {}", path.display(), e)
+        })?;
+        Ok(target.to_string_lossy().to_string())
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Copy `src` to `dst`, preserving modified times. On failure returns the path that failed.
+fn copy_recursive(
+    src: &std::path::Path,
+    dst: &std::path::Path,
+) -> Result<(), (std::path::PathBuf, std::io::Error)> {
+    use std::fs;
+
+    let fail = |e| (src.to_path_buf(), e);
+    let metadata = fs::symlink_metadata(src).map_err(fail)?;
+
+    if metadata.is_dir() {
+        fs::create_dir_all(dst).map_err(fail)?;
+        for entry in fs::read_dir(src).map_err(fail)? {
+            let entry = entry.map_err(fail)?;
+            copy_recursive(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    if metadata.file_type().is_symlink() {
+        let link = fs::read_link(src).map_err(fail)?;
+        if dst.symlink_metadata().is_ok() {
+            fs::remove_file(dst).map_err(fail)?;
+        }
+        return std::os::unix::fs::symlink(link, dst).map_err(fail);
+    }
+
+    fs::copy(src, dst).map_err(fail)?;
+    // Best effort: not every filesystem lets us set times
+    if let Ok(modified) = metadata.modified() {
+        if let Ok(file) = fs::File::options().write(true).open(dst) {
+            let _ = file.set_modified(modified);
+        }
+    }
+    Ok(())
+}
+
 #[tauri::command]
 fn rename_file(old_path: String, new_path: String) -> Result<(), String> {
     use std::fs;
@@ -1290,6 +3249,72 @@ fn rename_file(old_path: String, new_path: String) -> Result<(), String> {
     fs::rename(&old_path, &new_path).map_err(|e| e.to_string())
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileOperationResult {
+    pub path: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+impl FileOperationResult {
+    fn from_result(path: String, result: Result<(), String>) -> Self {
+        Self {
+            path,
+            success: result.is_ok(),
+            error: result.err(),
+        }
+    }
+}
+
+/// Marks a batch file operation as running; file watchers coalesce events until it drops
+struct FsBatchGuard(Arc<AppState>);
+
+impl FsBatchGuard {
+    fn new(state: Arc<AppState>) -> Self {
+        state.fs_batch_depth.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Self(state)
+    }
+}
+
+impl Drop for FsBatchGuard {
+    fn drop(&mut self) {
+        self.0.fs_batch_depth.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+/// Delete several paths in one call. Failures are reported per path rather than aborting.
+#[tauri::command]
+fn delete_files(
+    paths: Vec<String>,
+    permanent: Option<bool>,
+    state: tauri::State<Arc<AppState>>,
+) -> Vec<FileOperationResult> {
+    let _batch = FsBatchGuard::new(state.inner().clone());
+    paths
+        .into_iter()
+        .map(|path| {
+            let result = delete_file(path.clone(), permanent);
+            FileOperationResult::from_result(path, result)
+        })
+        .collect()
+}
+
+/// Move several `(from, to)` pairs in one call. Results are keyed by the source path.
+#[tauri::command]
+fn move_files(
+    moves: Vec<(String, String)>,
+    state: tauri::State<Arc<AppState>>,
+) -> Vec<FileOperationResult> {
+    let _batch = FsBatchGuard::new(state.inner().clone());
+    moves
+        .into_iter()
+        .map(|(from, to)| {
+            let result = rename_file(from.clone(), to);
+            FileOperationResult::from_result(from, result)
+        })
+        .collect()
+}
+
 #[tauri::command]
 fn edit_file_line(file_path: String, line_number: usize, new_content: String, delete: Option<bool>) -> Result<(), String> {
     use std::fs;
@@ -1316,9 +3341,13 @@ fn edit_file_line(file_path: String, line_number: usize, new_content: String, de
         new_lines[idx] = new_content;
     }
 
-    // Write back with proper line endings
-    let new_content = new_lines.join("\n");
-    fs::write(path, new_content).map_err(|e| e.to_string())?;
+    // Write back with the file's own line endings and trailing newline
+    let line_ending = detect_line_ending(&content);
+    let mut new_content = new_lines.join(line_ending);
+    if content.ends_with('\n') {
+        new_content.push_str(line_ending);
+    }
+    atomic_write(path, new_content.as_bytes()).map_err(|e| e.to_string())?;
 
     Ok(())
 }
@@ -1366,13 +3395,41 @@ fn init_repo(path: String) -> Result<(), String> {
 }
 
 #[tauri::command]
-async fn clone_repo(url: String, path: String) -> Result<String, String> {
-    GitService::clone_repo_async(&url, &path).await
+async fn clone_repo(
+    url: String,
+    path: String,
+    depth: Option<u32>,
+    branch: Option<String>,
+    single_branch: Option<bool>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    GitService::clone_repo_async(
+        &url,
+        &path,
+        depth,
+        branch.as_deref(),
+        single_branch.unwrap_or(false),
+        move |progress| {
+            let _ = app_handle.emit("clone-progress", &progress);
+        },
+    )
+    .await
+}
+
+#[tauri::command]
+async fn fetch_remote(
+    repo_path: String,
+    remote: String,
+    fetch_all: Option<bool>,
+    prune: Option<bool>,
+) -> Result<(), String> {
+    GitService::fetch_async(&repo_path, &remote, fetch_all.unwrap_or(false), prune.unwrap_or(false)).await
 }
 
+/// Fetch `remote` with `--prune` so branches deleted upstream drop out of `get_branches`
 #[tauri::command]
-async fn fetch_remote(repo_path: String, remote: String) -> Result<(), String> {
-    GitService::fetch_async(&repo_path, &remote).await
+async fn prune_remote_branches(repo_path: String, remote: String) -> Result<(), String> {
+    GitService::fetch_async(&repo_path, &remote, false, true).await
 }
 
 #[tauri::command]
@@ -1412,6 +3469,13 @@ fn stash_list(repo_path: String) -> Result<Vec<Stash>, String> {
     }).collect())
 }
 
+#[tauri::command]
+async fn diff_stash_against_worktree(repo_path: String, index: usize) -> Result<Vec<FileDiff>, String> {
+    tokio::task::spawn_blocking(move || GitService::diff_stash_against_worktree(&repo_path, index))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
 #[tauri::command]
 fn stash_apply(repo_path: String, index: usize) -> Result<(), String> {
     GitService::stash_apply(&repo_path, index)
@@ -1433,6 +3497,14 @@ fn merge_branch(repo_path: String, branch: String, strategy: String) -> Result<S
     GitService::merge_branch(&repo_path, &branch, &strategy)
 }
 
+/// Dry-run a merge of `branch` into HEAD in memory; the working tree and index are untouched
+#[tauri::command]
+async fn preview_merge(repo_path: String, branch: String) -> Result<MergePreview, String> {
+    tokio::task::spawn_blocking(move || GitService::preview_merge(&repo_path, &branch))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
 #[tauri::command]
 fn abort_merge(repo_path: String) -> Result<(), String> {
     GitService::abort_merge(&repo_path)
@@ -1470,6 +3542,11 @@ fn undo_last_commit(repo_path: String) -> Result<(), String> {
     GitService::undo_last_commit(&repo_path)
 }
 
+#[tauri::command]
+fn undo_last_commit_keep_message(repo_path: String) -> Result<String, String> {
+    GitService::undo_last_commit_keep_message(&repo_path)
+}
+
 // Rebase commands
 #[tauri::command]
 fn rebase_onto(repo_path: String, onto_branch: String) -> Result<String, String> {
@@ -1486,6 +3563,16 @@ fn rebase_abort(repo_path: String) -> Result<(), String> {
     GitService::rebase_abort(&repo_path)
 }
 
+#[tauri::command]
+fn get_repo_state(repo_path: String) -> Result<String, String> {
+    GitService::get_repo_state(&repo_path)
+}
+
+#[tauri::command]
+fn abort_current_operation(repo_path: String) -> Result<String, String> {
+    GitService::abort_current_operation(&repo_path)
+}
+
 #[tauri::command]
 fn cherry_pick_commit(repo_path: String, commit_id: String) -> Result<String, String> {
     GitService::cherry_pick(&repo_path, &commit_id)
@@ -1517,6 +3604,35 @@ async fn push_tag(repo_path: String, tag: String, remote: String) -> Result<(),
     GitService::push_tag_async(&repo_path, &tag, &remote).await
 }
 
+// Staging area
+#[tauri::command]
+async fn stage_file(repo_path: String, file_path: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || GitService::stage_file(&repo_path, &file_path))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+async fn unstage_file(repo_path: String, file_path: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || GitService::unstage_file(&repo_path, &file_path))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+async fn stage_all(repo_path: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || GitService::stage_all(&repo_path))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+async fn unstage_all(repo_path: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || GitService::unstage_all(&repo_path))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
 // Line-level staging
 #[tauri::command]
 async fn stage_lines(repo_path: String, file_path: String, line_ranges: Vec<(u32, u32)>) -> Result<(), String> {
@@ -1526,15 +3642,48 @@ async fn stage_lines(repo_path: String, file_path: String, line_ranges: Vec<(u32
 }
 
 // Image diff
+/// Base64 of the file at HEAD, or `null` for a newly added file. An LFS file may have to be
+/// downloaded by `git lfs smudge`, so this runs off the main thread and gives up after a minute.
+#[tauri::command]
+async fn get_old_file_content(repo_path: String, file_path: String) -> Result<Option<String>, String> {
+    let task = tokio::task::spawn_blocking(move || GitService::get_old_file_content(&repo_path, &file_path));
+    let bytes = tokio::time::timeout(Duration::from_secs(60), task)
+        .await
+        .map_err(|_| "Timed out reading the file at HEAD".to_string())?
+        .map_err(|e| format!("Task failed: {}", e))??;
+    Ok(bytes.map(|b| BASE64.encode(b)))
+}
+
+// Secrets (OS keychain)
+#[tauri::command]
+fn store_secret(name: String, value: String) -> Result<(), String> {
+    secrets::store(&name, &value)
+}
+
+/// Whether a secret is stored; the value itself never goes back to the webview
+#[tauri::command]
+fn has_secret(name: String) -> Result<bool, String> {
+    Ok(secrets::get(&name)?.is_some())
+}
+
 #[tauri::command]
-fn get_old_file_content(repo_path: String, file_path: String) -> Result<String, String> {
-    let bytes = GitService::get_old_file_content(&repo_path, &file_path)?;
-    Ok(BASE64.encode(&bytes))
+fn delete_secret(name: String) -> Result<(), String> {
+    secrets::delete(&name)
 }
 
 // GitHub commands
+
+/// Token passed from the frontend, falling back to the one saved in the OS keychain
+fn resolve_github_token(token: String) -> Result<String, String> {
+    if !token.is_empty() {
+        return Ok(token);
+    }
+    secrets::github_token().ok_or_else(|| "No GitHub token provided".to_string())
+}
+
 #[tauri::command]
 async fn github_get_user(token: String) -> Result<GitHubUser, String> {
+    let token = resolve_github_token(token)?;
     let (login, name, avatar_url) = GitHubClient::get_user(&token).await?;
     Ok(GitHubUser { login, name, avatar_url })
 }
@@ -1620,6 +3769,7 @@ async fn github_list_pull_requests(
     repo: String,
     state: String,
 ) -> Result<Vec<PullRequest>, String> {
+    let token = resolve_github_token(token)?;
     let raw = GitHubClient::list_pull_requests(&token, &owner, &repo, &state).await?;
     Ok(raw.into_iter().map(|(number, title, body, state, author, head_ref, base_ref, created_at, updated_at, url, draft)| {
         PullRequest { number, title, body, state, author, head_ref, base_ref, created_at, updated_at, url, draft }
@@ -1636,6 +3786,7 @@ async fn github_create_pull_request(
     head: String,
     base: String,
 ) -> Result<PullRequest, String> {
+    let token = resolve_github_token(token)?;
     let (number, url) = GitHubClient::create_pull_request(&token, &owner, &repo, &title, &body, &head, &base).await?;
     Ok(PullRequest {
         number,
@@ -1659,6 +3810,7 @@ async fn github_get_pr_checks(
     repo: String,
     git_ref: String,
 ) -> Result<Vec<CheckRun>, String> {
+    let token = resolve_github_token(token)?;
     let raw = GitHubClient::get_pr_checks(&token, &owner, &repo, &git_ref).await?;
     Ok(raw.into_iter().map(|(name, status, conclusion, html_url)| {
         CheckRun { name, status, conclusion, html_url }
@@ -1673,6 +3825,7 @@ async fn github_merge_pull_request(
     pull_number: u64,
     merge_method: String,
 ) -> Result<String, String> {
+    let token = resolve_github_token(token)?;
     GitHubClient::merge_pull_request(&token, &owner, &repo, pull_number, &merge_method).await
 }
 
@@ -1717,87 +3870,10 @@ fn watch_repo(
     app_handle: tauri::AppHandle,
     state: tauri::State<Arc<AppState>>,
 ) -> Result<(), String> {
-    use notify::RecursiveMode;
-    use std::sync::mpsc;
-
-    // Check if already watching this repo
-    {
-        let watchers = state.git_watchers.lock();
-        if watchers.contains_key(&repo_path) {
-            return Ok(()); // Already watching
-        }
-    }
-
-    let git_dir = resolve_git_dir(&repo_path)?;
-
-    // Create channels for communication
-    let (event_tx, event_rx) = mpsc::channel::<()>();
-    let (stop_tx, stop_rx) = mpsc::channel::<()>();
-
-    // Spawn a thread to handle events and emit to frontend
-    let repo_path_for_thread = repo_path.clone();
-    let app_handle_clone = app_handle.clone();
-    thread::spawn(move || {
-        loop {
-            // Check for stop signal (non-blocking)
-            if stop_rx.try_recv().is_ok() {
-                break;
-            }
-
-            // Wait for events with timeout so we can check stop signal
-            match event_rx.recv_timeout(Duration::from_millis(100)) {
-                Ok(()) => {
-                    // Emit event to frontend (safe on this thread)
-                    if let Err(e) = app_handle_clone.emit("git-files-changed", &repo_path_for_thread) {
-                        println!("Failed to emit git-files-changed: {:?}", e);
-                    }
-                }
-                Err(mpsc::RecvTimeoutError::Timeout) => continue,
-                Err(mpsc::RecvTimeoutError::Disconnected) => break,
-            }
-        }
-    });
-
-    // Create a debounced watcher with 500ms delay to batch rapid changes
-    let event_tx_clone = event_tx.clone();
-    let mut debouncer = new_debouncer(
-        Duration::from_millis(500),
-        move |result: Result<Vec<notify_debouncer_mini::DebouncedEvent>, notify::Error>| {
-            match result {
-                Ok(events) => {
-                    // Check if any event is relevant
-                    let has_changes = events.iter().any(|e| {
-                        matches!(e.kind, DebouncedEventKind::Any)
-                    });
-
-                    if has_changes {
-                        // Send to the event thread (ignore errors if channel closed)
-                        let _ = event_tx_clone.send(());
-                    }
-                }
-                Err(e) => {
-                    println!("Git watcher error: {:?}", e);
-                }
-            }
-        },
-    ).map_err(|e| e.to_string())?;
-
-    // Watch the .git directory (especially index file which changes on most operations)
-    // Note: working directory watching is handled by watch_project_files to avoid
-    // duplicate recursive watchers that exhaust inotify limits on Linux.
-    if let Err(e) = debouncer.watcher().watch(&git_dir, RecursiveMode::Recursive) {
-        println!("Warning: failed to watch git directory {:?}: {}. Git status updates may not be live.", git_dir, e);
-        return Ok(());
-    }
-
-    // Store the watcher
-    let git_watcher = GitWatcher {
-        _debouncer: debouncer,
-        _stop_tx: stop_tx,
-    };
-    state.git_watchers.lock().insert(repo_path, git_watcher);
-
-    Ok(())
+    resolve_git_dir(&repo_path)?;
+    ensure_project_watcher(&repo_path, None, app_handle, state.inner(), |watcher| {
+        watcher.repo_watched = true;
+    })
 }
 
 #[tauri::command]
@@ -1805,7 +3881,13 @@ fn unwatch_repo(
     repo_path: String,
     state: tauri::State<Arc<AppState>>,
 ) -> Result<(), String> {
-    state.git_watchers.lock().remove(&repo_path);
+    let mut watchers = state.project_watchers.lock();
+    if let Some(watcher) = watchers.get_mut(&repo_path) {
+        watcher.repo_watched = false;
+        if !watcher.files_watched {
+            watchers.remove(&repo_path);
+        }
+    }
     Ok(())
 }
 
@@ -1850,38 +3932,122 @@ fn unlock_worktree(repo_path: String, worktree_path: String) -> Result<(), Strin
     GitService::unlock_worktree(&repo_path, &worktree_path)
 }
 
-// File system watcher commands - watches project files for changes (Issue #8)
-#[tauri::command]
-fn watch_project_files(
-    project_path: String,
-    app_handle: tauri::AppHandle,
-    state: tauri::State<Arc<AppState>>,
-) -> Result<(), String> {
+/// Directories the project watcher always skips, on top of .gitignore and the caller's list
+const WATCH_DEFAULT_IGNORES: &[&str] = &["node_modules", "target", "__pycache__", "dist", "build", ".git"];
+
+/// `path` relative to `root`, also accepting the resolved form of `root` since some
+/// backends (FSEvents) report /private/var for /var
+fn watch_relative<'a>(path: &'a std::path::Path, root: &std::path::Path, canonical_root: Option<&std::path::Path>) -> Option<&'a std::path::Path> {
+    path.strip_prefix(root)
+        .ok()
+        .or_else(|| canonical_root.and_then(|root| path.strip_prefix(root).ok()))
+}
+
+/// Ignore rules for one project watcher: the root .gitignore, .git/info/exclude, the
+/// defaults above and any extra patterns (gitignore syntax, e.g. `.venv` or `coverage/`)
+struct WatchIgnore {
+    matcher: ignore::gitignore::Gitignore,
+    root: PathBuf,
+    canonical_root: Option<PathBuf>,
+}
+
+impl WatchIgnore {
+    fn new(root: &std::path::Path, extra: &[String]) -> Self {
+        let mut builder = ignore::gitignore::GitignoreBuilder::new(root);
+        // Missing files are fine; broken lines are skipped by the builder
+        let _ = builder.add(root.join(".gitignore"));
+        let _ = builder.add(root.join(".git").join("info").join("exclude"));
+        for pattern in WATCH_DEFAULT_IGNORES.iter().copied().chain(extra.iter().map(String::as_str)) {
+            let _ = builder.add_line(None, pattern);
+        }
+        Self {
+            matcher: builder.build().unwrap_or_else(|_| ignore::gitignore::Gitignore::empty()),
+            root: root.to_path_buf(),
+            canonical_root: root.canonicalize().ok(),
+        }
+    }
+
+    fn is_ignored(&self, path: &std::path::Path, is_dir: bool) -> bool {
+        match watch_relative(path, &self.root, self.canonical_root.as_deref()) {
+            Some(relative) if !relative.as_os_str().is_empty() => {
+                self.matcher.matched_path_or_any_parents(relative, is_dir).is_ignore()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Files under the git dir whose changes mean status, branches or HEAD moved. Objects,
+/// logs and FETCH_HEAD churn on every fetch/gc without changing anything we show.
+fn is_git_metadata(relative: &std::path::Path) -> bool {
+    let first = relative.components().next().map(|c| c.as_os_str().to_string_lossy());
+    matches!(
+        first.as_deref(),
+        Some("HEAD" | "index" | "packed-refs" | "refs" | "MERGE_HEAD" | "ORIG_HEAD"
+            | "CHERRY_PICK_HEAD" | "REVERT_HEAD" | "REBASE_HEAD" | "rebase-merge" | "rebase-apply")
+    )
+}
+
+/// inotify needs one watch per directory, so a recursive watch would also register every
+/// ignored subtree (`.venv`, `node_modules`). Walk `dir` and watch only what we care about;
+/// nested .gitignore files are honored by the walker itself.
+#[cfg(target_os = "linux")]
+fn watch_project_dirs(watcher: &mut dyn notify::Watcher, dir: &std::path::Path, ignore: &Arc<WatchIgnore>) {
     use notify::RecursiveMode;
-    use std::path::Path;
-    use std::sync::mpsc;
 
-    // Check if already watching this project
-    {
-        let watchers = state.file_watchers.lock();
-        if watchers.contains_key(&project_path) {
-            return Ok(()); // Already watching
+    let filter = ignore.clone();
+    let walker = ignore::WalkBuilder::new(dir)
+        .hidden(false)
+        .ignore(false)
+        .parents(true)
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().map_or(false, |t| t.is_dir());
+            is_dir && !filter.is_ignored(entry.path(), true)
+        })
+        .build();
+
+    for entry in walker.flatten() {
+        if let Err(e) = watcher.watch(entry.path(), RecursiveMode::NonRecursive) {
+            log::warn!("Failed to watch {:?}: {}", entry.path(), e);
         }
     }
+}
 
-    let project_dir = Path::new(&project_path);
+/// Start the shared watcher for `project_path`. Emits `project-files-changed` for every
+/// batch, `git-files-changed` when .git changed and `fs-files-changed` when working tree
+/// files did, all with the same payload.
+fn start_project_watcher(
+    project_path: &str,
+    extra_ignores: &[String],
+    app_handle: tauri::AppHandle,
+    state: &Arc<AppState>,
+) -> Result<ProjectWatcher, String> {
+    use notify::RecursiveMode;
+    use std::collections::BTreeSet;
+    use std::path::Path;
+    use std::sync::mpsc;
+
+    let project_dir = Path::new(project_path);
     if !project_dir.exists() || !project_dir.is_dir() {
         return Err("Project path does not exist or is not a directory".to_string());
     }
 
-    // Create channels for communication
-    let (event_tx, event_rx) = mpsc::channel::<()>();
+    let ignore = Arc::new(WatchIgnore::new(project_dir, extra_ignores));
+    let git_dir = resolve_git_dir(project_path).ok();
+    let canonical_git_dir = git_dir.as_ref().and_then(|dir| dir.canonicalize().ok());
+
+    // Create channels for communication: (git metadata changed, working tree paths)
+    let (event_tx, event_rx) = mpsc::channel::<(bool, Vec<PathBuf>)>();
     let (stop_tx, stop_rx) = mpsc::channel::<()>();
 
     // Spawn a thread to handle events and emit to frontend
-    let project_path_for_thread = project_path.clone();
-    let app_handle_clone = app_handle.clone();
+    let project_path_for_thread = project_path.to_string();
+    let state_for_thread = state.clone();
+    #[cfg(target_os = "linux")]
+    let ignore_for_thread = ignore.clone();
     thread::spawn(move || {
+        let mut git_changed = false;
+        let mut changed_paths: BTreeSet<PathBuf> = BTreeSet::new();
         loop {
             // Check for stop signal (non-blocking)
             if stop_rx.try_recv().is_ok() {
@@ -1890,140 +4056,544 @@ fn watch_project_files(
 
             // Wait for events with timeout so we can check stop signal
             match event_rx.recv_timeout(Duration::from_millis(100)) {
-                Ok(()) => {
-                    // Emit event to frontend
-                    if let Err(e) = app_handle_clone.emit("fs-files-changed", &project_path_for_thread) {
-                        println!("Failed to emit fs-files-changed: {:?}", e);
+                Ok((git, paths)) => {
+                    // Directories created since we started need their own watches
+                    #[cfg(target_os = "linux")]
+                    if let Some(watcher) = state_for_thread.project_watchers.lock().get_mut(&project_path_for_thread) {
+                        for dir in paths.iter().filter(|p| p.is_dir()) {
+                            watch_project_dirs(watcher.debouncer.watcher(), dir, &ignore_for_thread);
+                        }
                     }
+                    git_changed |= git;
+                    changed_paths.extend(paths);
                 }
-                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
                 Err(mpsc::RecvTimeoutError::Disconnected) => break,
             }
+
+            // Hold off while a batch operation is running so it produces a single refresh
+            let batch_running = state_for_thread
+                .fs_batch_depth
+                .load(std::sync::atomic::Ordering::SeqCst)
+                > 0;
+            if (git_changed || !changed_paths.is_empty()) && !batch_running {
+                let payload = ProjectFilesChanged {
+                    project_path: project_path_for_thread.clone(),
+                    git_changed,
+                    paths: std::mem::take(&mut changed_paths)
+                        .into_iter()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .collect(),
+                };
+                git_changed = false;
+
+                if let Err(e) = app_handle.emit("project-files-changed", &payload) {
+                    log::warn!("Failed to emit project-files-changed: {:?}", e);
+                }
+                if payload.git_changed {
+                    let _ = app_handle.emit("git-files-changed", &payload);
+                }
+                if !payload.paths.is_empty() {
+                    let _ = app_handle.emit("fs-files-changed", &payload);
+                }
+            }
         }
     });
 
-    // Directories to ignore when watching
-    let ignore_dirs: std::collections::HashSet<&str> = [
-        "node_modules", "target", "__pycache__", "dist", "build", ".git"
-    ].iter().cloned().collect();
-
     // Create a debounced watcher with 500ms delay to batch rapid changes
-    let event_tx_clone = event_tx.clone();
-    let ignore_dirs_clone = ignore_dirs.clone();
+    let ignore_for_events = ignore.clone();
+    let git_dir_for_events = git_dir.clone();
     let mut debouncer = new_debouncer(
         Duration::from_millis(500),
         move |result: Result<Vec<notify_debouncer_mini::DebouncedEvent>, notify::Error>| {
             match result {
                 Ok(events) => {
-                    // Filter out events in ignored directories
-                    let has_relevant_changes = events.iter().any(|e| {
-                        if !matches!(e.kind, DebouncedEventKind::Any) {
-                            return false;
+                    let mut git_changed = false;
+                    let mut paths = Vec::new();
+                    for event in events.into_iter().filter(|e| matches!(e.kind, DebouncedEventKind::Any)) {
+                        let in_git_dir = git_dir_for_events.as_deref().and_then(|dir| {
+                            watch_relative(&event.path, dir, canonical_git_dir.as_deref())
+                        });
+                        match in_git_dir {
+                            Some(relative) => git_changed |= is_git_metadata(relative),
+                            // Other platforms watch recursively, so ignored paths still show up here
+                            None if !ignore_for_events.is_ignored(&event.path, event.path.is_dir()) => {
+                                paths.push(event.path)
+                            }
+                            None => {}
                         }
-                        // Check if path contains any ignored directory
-                        let path_str = e.path.to_string_lossy();
-                        !ignore_dirs_clone.iter().any(|dir| {
-                            path_str.contains(&format!("/{}/", dir)) ||
-                            path_str.contains(&format!("\\{}\\", dir)) ||
-                            path_str.ends_with(&format!("/{}", dir)) ||
-                            path_str.ends_with(&format!("\\{}", dir))
-                        })
-                    });
+                    }
 
-                    if has_relevant_changes {
-                        let _ = event_tx_clone.send(());
+                    if git_changed || !paths.is_empty() {
+                        let _ = event_tx.send((git_changed, paths));
                     }
                 }
                 Err(e) => {
-                    println!("File watcher error: {:?}", e);
+                    log::warn!("Project watcher error: {:?}", e);
                 }
             }
         },
     ).map_err(|e| e.to_string())?;
 
-    // Watch the project directory recursively
+    // Watch the working tree, skipping ignored subtrees where the backend allows it
+    #[cfg(target_os = "linux")]
+    watch_project_dirs(debouncer.watcher(), project_dir, &ignore);
+    #[cfg(not(target_os = "linux"))]
     if let Err(e) = debouncer.watcher().watch(project_dir, RecursiveMode::Recursive) {
-        println!("Warning: failed to watch project directory {:?}: {}. File change detection may not work.", project_dir, e);
-        return Ok(());
+        log::warn!("Failed to watch project directory {:?}: {}. File change detection may not work.", project_dir, e);
+    }
+
+    // Git metadata lives in the top of the git dir plus refs/; objects/ is never watched.
+    // A recursive tree watch elsewhere already sees a .git inside the project.
+    if let Some(git_dir) = git_dir.filter(|dir| cfg!(target_os = "linux") || !dir.starts_with(project_dir)) {
+        if let Err(e) = debouncer.watcher().watch(&git_dir, RecursiveMode::NonRecursive) {
+            log::warn!("Failed to watch git directory {:?}: {}. Git status updates may not be live.", git_dir, e);
+        }
+        let refs = git_dir.join("refs");
+        if refs.is_dir() {
+            let _ = debouncer.watcher().watch(&refs, RecursiveMode::Recursive);
+        }
     }
 
-    // Store the watcher
-    let file_watcher = FileWatcher {
-        _debouncer: debouncer,
+    Ok(ProjectWatcher {
+        debouncer,
         _stop_tx: stop_tx,
-    };
-    state.file_watchers.lock().insert(project_path, file_watcher);
+        extra_ignores: extra_ignores.to_vec(),
+        repo_watched: false,
+        files_watched: false,
+    })
+}
+
+/// Get the project's watcher, starting it or rebuilding it when `extra_ignores` differ from
+/// the running one's (`None` accepts whatever is running), and apply `mark` to it. The
+/// watcher is built outside the `project_watchers` lock since on Linux that walks the tree.
+fn ensure_project_watcher(
+    project_path: &str,
+    extra_ignores: Option<Vec<String>>,
+    app_handle: tauri::AppHandle,
+    state: &Arc<AppState>,
+    mark: impl FnOnce(&mut ProjectWatcher),
+) -> Result<(), String> {
+    if let Some(watcher) = state
+        .project_watchers
+        .lock()
+        .get_mut(project_path)
+        .filter(|w| extra_ignores.as_ref().map_or(true, |extra| *extra == w.extra_ignores))
+    {
+        mark(watcher);
+        return Ok(());
+    }
 
+    let extra_ignores = extra_ignores.unwrap_or_default();
+    let mut watcher = start_project_watcher(project_path, &extra_ignores, app_handle, state)?;
+    let replaced = {
+        let mut watchers = state.project_watchers.lock();
+        // Keep whichever of watch_repo/watch_project_files the old watcher was serving
+        if let Some(existing) = watchers.get(project_path) {
+            watcher.repo_watched = existing.repo_watched;
+            watcher.files_watched = existing.files_watched;
+        }
+        mark(&mut watcher);
+        watchers.insert(project_path.to_string(), watcher)
+    };
+    // Stop the old watcher after releasing the lock; its event thread takes it on Linux
+    drop(replaced);
     Ok(())
 }
 
+// File system watcher commands - watches project files for changes (Issue #8)
+#[tauri::command]
+fn watch_project_files(
+    project_path: String,
+    extra_ignores: Option<Vec<String>>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Arc<AppState>>,
+) -> Result<(), String> {
+    let extra_ignores = Some(extra_ignores.unwrap_or_default());
+    ensure_project_watcher(&project_path, extra_ignores, app_handle, state.inner(), |watcher| {
+        watcher.files_watched = true;
+    })
+}
+
 #[tauri::command]
 fn unwatch_project_files(
     project_path: String,
     state: tauri::State<Arc<AppState>>,
 ) -> Result<(), String> {
-    state.file_watchers.lock().remove(&project_path);
+    let mut watchers = state.project_watchers.lock();
+    if let Some(watcher) = watchers.get_mut(&project_path) {
+        watcher.files_watched = false;
+        if !watcher.repo_watched {
+            watchers.remove(&project_path);
+        }
+    }
+    Ok(())
+}
+
+/// Watch one file for outside edits and emit `file-changed` with its path. Cheaper than
+/// `watch_project_files` for the "modified on disk, reload?" prompt.
+#[tauri::command]
+fn watch_file(path: String, app_handle: tauri::AppHandle, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    use notify::RecursiveMode;
+
+    if state.single_file_watchers.lock().contains_key(&path) {
+        return Ok(());
+    }
+
+    let target = std::path::PathBuf::from(&path);
+    let file_name = target.file_name().ok_or("Not a file path")?.to_os_string();
+    let parent = target.parent().filter(|p| p.is_dir()).ok_or("Parent directory does not exist")?;
+
+    // Editors often save by writing a temp file and renaming it over the original, which
+    // replaces the inode; watching the directory and filtering by name survives that
+    let path_for_event = path.clone();
+    let mut debouncer = new_debouncer(
+        Duration::from_millis(300),
+        move |result: Result<Vec<notify_debouncer_mini::DebouncedEvent>, notify::Error>| match result {
+            Ok(events) => {
+                if events.iter().any(|e| e.path.file_name() == Some(file_name.as_os_str())) {
+                    let _ = app_handle.emit("file-changed", &path_for_event);
+                }
+            }
+            Err(e) => log::warn!("File watcher error: {:?}", e),
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    debouncer
+        .watcher()
+        .watch(parent, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch {}: {}", path, e))?;
+
+    state.single_file_watchers.lock().insert(path, debouncer);
+    Ok(())
+}
+
+#[tauri::command]
+fn unwatch_file(path: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    state.single_file_watchers.lock().remove(&path);
+    Ok(())
+}
+
+// Global shortcuts - system-wide hotkeys that work while Orca is in the background
+const GLOBAL_SHORTCUT_ACTIONS: &[&str] = &["new-terminal", "show-window"];
+
+fn run_global_shortcut_action(app: &tauri::AppHandle, action: &str) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+        // The frontend opens the terminal; "show-window" needs nothing more
+        let _ = window.emit("global-shortcut", action);
+    }
+}
+
+fn register_shortcut(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    accelerator: &str,
+    action: &str,
+) -> Result<(), String> {
+    use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+
+    if !GLOBAL_SHORTCUT_ACTIONS.contains(&action) {
+        return Err(format!("Unknown shortcut action: {}", action));
+    }
+    let shortcut: Shortcut = accelerator
+        .parse()
+        .map_err(|e| format!("Invalid shortcut '{}': {}", accelerator, e))?;
+
+    // Don't hold the lock across register(): the shortcut handler takes it on the main thread
+    let already_registered = state.global_shortcuts.lock().contains_key(&shortcut.id());
+    if !already_registered {
+        app.global_shortcut().register(shortcut).map_err(|e| {
+            format!("SHORTCUT_CONFLICT: {} is already in use by another application: {}", accelerator, e)
+        })?;
+    }
+    state.global_shortcuts.lock().insert(shortcut.id(), action.to_string());
+    Ok(())
+}
+
+/// Register a system-wide shortcut for `action` ("new-terminal" or "show-window") and
+/// remember it across restarts
+#[tauri::command]
+fn register_global_shortcut(
+    accelerator: String,
+    action: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Arc<AppState>>,
+) -> Result<(), String> {
+    register_shortcut(&app_handle, &state, &accelerator, &action)?;
+    state.database.save_global_shortcut(&accelerator, &action)
+}
+
+#[tauri::command]
+fn unregister_global_shortcut(
+    accelerator: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Arc<AppState>>,
+) -> Result<(), String> {
+    use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+
+    if let Ok(shortcut) = accelerator.parse::<Shortcut>() {
+        if state.global_shortcuts.lock().remove(&shortcut.id()).is_some() {
+            app_handle
+                .global_shortcut()
+                .unregister(shortcut)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    state.database.delete_global_shortcut(&accelerator)
+}
+
+#[tauri::command]
+fn get_global_shortcuts(state: tauri::State<Arc<AppState>>) -> Result<Vec<database::GlobalShortcut>, String> {
+    state.database.get_global_shortcuts()
+}
+
+// Database watcher - notifies the frontend when another instance changes the projects table.
+// Revision steps made by this process's own writes are skipped.
+#[tauri::command]
+fn watch_database(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Arc<AppState>>,
+) -> Result<(), String> {
+    use std::sync::atomic::Ordering;
+
+    // Only one poller is needed no matter how many windows ask for it
+    if state.db_watcher_running.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    let state_for_thread = state.inner().clone();
+    let mut last_revision = match state.database.projects_revision() {
+        Ok(r) => r,
+        Err(e) => {
+            state.db_watcher_running.store(false, Ordering::SeqCst);
+            return Err(e);
+        }
+    };
+    thread::spawn(move || {
+        loop {
+            thread::sleep(Duration::from_secs(1));
+            let revision = match state_for_thread.database.projects_revision() {
+                Ok(r) => r,
+                Err(e) => {
+                    log::warn!("Database watcher error: {}", e);
+                    continue;
+                }
+            };
+            if revision != last_revision {
+                if !state_for_thread.database.is_own_projects_change(last_revision, revision) {
+                    if let Err(e) = app_handle.emit("projects-changed", ()) {
+                        log::warn!("Failed to emit projects-changed: {:?}", e);
+                    }
+                }
+                last_revision = revision;
+            }
+        }
+    });
+
     Ok(())
 }
 
 // Project commands
 #[tauri::command]
 fn add_project(project: Project, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
-    let db = state.database.lock();
+    let db = &state.database;
     db.add_project(&project)
 }
 
 #[tauri::command]
 fn remove_project(id: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
-    let db = state.database.lock();
+    let db = &state.database;
     db.remove_project(&id)
 }
 
 #[tauri::command]
 fn get_project(id: String, state: tauri::State<Arc<AppState>>) -> Result<Option<Project>, String> {
-    let db = state.database.lock();
+    let db = &state.database;
     db.get_project(&id)
 }
 
 #[tauri::command]
 fn get_all_projects(state: tauri::State<Arc<AppState>>) -> Result<Vec<Project>, String> {
-    let db = state.database.lock();
+    let db = &state.database;
     db.get_all_projects()
 }
 
-// File system commands
+/// Replace a project's tags; blanks and duplicates are dropped
 #[tauri::command]
-async fn open_folder_dialog() -> Result<Option<String>, String> {
-    // Note: In a real implementation, you'd use a file dialog
-    // For now, we return None to indicate no folder was selected
-    // The actual dialog would be implemented using native APIs or a Tauri plugin
-    Ok(None)
+fn set_project_tags(id: String, tags: Vec<String>, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_string();
+        if !tag.is_empty() && !cleaned.contains(&tag) {
+            cleaned.push(tag);
+        }
+    }
+    state.database.set_project_tags(&id, &cleaned)
 }
 
+/// Default shell, startup commands and env overrides used when opening terminals in the project
 #[tauri::command]
-fn open_in_finder(path: String) -> Result<(), String> {
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("open")
-            .arg(&path)
-            .spawn()
-            .map_err(|e| e.to_string())?;
-    }
-    #[cfg(target_os = "windows")]
-    {
-        std::process::Command::new("explorer")
-            .arg(&path)
-            .spawn()
-            .map_err(|e| e.to_string())?;
+fn set_project_shell_settings(
+    id: String,
+    default_shell: Option<String>,
+    startup_commands: Vec<String>,
+    env: HashMap<String, String>,
+    state: tauri::State<Arc<AppState>>,
+) -> Result<(), String> {
+    let default_shell = default_shell.filter(|s| !s.trim().is_empty());
+    state.database.set_project_shell_settings(&id, default_shell.as_deref(), &startup_commands, &env)
+}
+
+#[tauri::command]
+fn set_project_pinned(id: String, pinned: bool, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    state.database.set_project_pinned(&id, pinned)
+}
+
+#[tauri::command]
+fn get_projects_by_tag(tag: String, state: tauri::State<Arc<AppState>>) -> Result<Vec<Project>, String> {
+    state.database.get_projects_by_tag(tag.trim())
+}
+
+// Prompt template commands
+#[tauri::command]
+fn save_prompt_template(name: String, text: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Template name cannot be empty".to_string());
     }
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("xdg-open")
-            .arg(&path)
-            .spawn()
-            .map_err(|e| e.to_string())?;
+    state.database.save_prompt_template(name.trim(), &text)
+}
+
+#[tauri::command]
+fn get_prompt_templates(state: tauri::State<Arc<AppState>>) -> Result<Vec<database::PromptTemplate>, String> {
+    state.database.get_prompt_templates()
+}
+
+#[tauri::command]
+fn delete_prompt_template(name: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    state.database.delete_prompt_template(&name)
+}
+
+/// Expand a saved prompt template's `{cwd}`, `{selection}` and `{request}` placeholders.
+/// Templates without `{request}` get the user's request appended.
+fn expand_prompt_template(
+    state: &AppState,
+    template: Option<&str>,
+    request: &str,
+    cwd: &str,
+    selection: Option<&str>,
+) -> Result<String, String> {
+    let Some(name) = template.filter(|t| !t.is_empty()) else {
+        return Ok(request.to_string());
+    };
+    let text = state
+        .database
+        .get_prompt_template(name)?
+        .ok_or_else(|| format!("Prompt template not found: {}", name))?;
+
+    let mut expanded = text
+        .replace("{cwd}", cwd)
+        .replace("{selection}", selection.unwrap_or(""));
+    if expanded.contains("{request}") {
+        expanded = expanded.replace("{request}", request);
+    } else if !request.trim().is_empty() {
+        expanded = format!("{}\n\n{}", expanded, request);
     }
-    Ok(())
+    Ok(expanded)
+}
+
+#[tauri::command]
+fn backup_database(dest_path: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    state.database.backup_to(std::path::Path::new(&dest_path))
+}
+
+#[tauri::command]
+fn get_database_stats(state: tauri::State<Arc<AppState>>) -> Result<database::DatabaseStats, String> {
+    state.database.stats()
+}
+
+#[tauri::command]
+async fn vacuum_database(state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    // VACUUM rewrites the whole file, keep it off the command thread
+    let state = state.inner().clone();
+    tokio::task::spawn_blocking(move || state.database.vacuum())
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Copy the database to `dest_path` for moving to another machine, minus Portal pairing secrets.
+/// Project and terminal profile env vars are only copied when `include_env` is set.
+#[tauri::command]
+async fn export_database(
+    dest_path: String,
+    include_env: Option<bool>,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let state = state.inner().clone();
+    let include_env = include_env.unwrap_or(false);
+    tokio::task::spawn_blocking(move || state.database.export_to(std::path::Path::new(&dest_path), include_env))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Replace projects, templates and settings with those in an exported database, immediately
+#[tauri::command]
+async fn import_database(
+    src_path: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let state = state.inner().clone();
+    tokio::task::spawn_blocking(move || state.database.import_from(std::path::Path::new(&src_path)))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))??;
+    // The database watcher skips our own writes, so tell every window here
+    let _ = app_handle.emit("projects-changed", ());
+    Ok(())
+}
+
+/// Stage a backup to replace the current database. Takes effect after restarting the app.
+#[tauri::command]
+fn restore_database(src_path: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    state.database.stage_restore(std::path::Path::new(&src_path))
+}
+
+// File system commands
+#[tauri::command]
+async fn open_folder_dialog() -> Result<Option<String>, String> {
+    // Note: In a real implementation, you'd use a file dialog
+    // For now, we return None to indicate no folder was selected
+    // The actual dialog would be implemented using native APIs or a Tauri plugin
+    Ok(None)
+}
+
+#[tauri::command]
+fn open_in_finder(path: String) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    #[cfg(target_os = "windows")]
+    {
+        std::process::Command::new("explorer")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        std::process::Command::new("xdg-open")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -2056,8 +4626,68 @@ fn reveal_in_file_manager(path: String) -> Result<(), String> {
     Ok(())
 }
 
+/// Editors tried when the user hasn't saved their own list, most common first
+fn default_editors() -> Vec<database::EditorConfig> {
+    let editor = |name: &str, command: &str, args: &[&str], terminal: bool| database::EditorConfig {
+        name: name.to_string(),
+        command: command.to_string(),
+        args: args.iter().map(|a| a.to_string()).collect(),
+        terminal,
+    };
+    vec![
+        editor("VS Code", "code", &["--goto", "{path}:{line}:{col}"], false),
+        editor("Cursor", "cursor", &["--goto", "{path}:{line}:{col}"], false),
+        editor("Zed", "zed", &["{path}:{line}:{col}"], false),
+        editor("IntelliJ IDEA", "idea", &["--line", "{line}", "--column", "{col}", "{path}"], false),
+        editor("Sublime Text", "subl", &["{path}:{line}:{col}"], false),
+        editor("Emacs", "emacs", &["+{line}:{col}", "{path}"], false),
+        editor("Neovim", "nvim", &["+call cursor({line}, {col})", "{path}"], true),
+        editor("Helix", "hx", &["{path}:{line}:{col}"], true),
+    ]
+}
+
+/// Saved editor list, or the defaults if none has been saved
+#[tauri::command]
+fn get_editors(state: tauri::State<Arc<AppState>>) -> Result<Vec<database::EditorConfig>, String> {
+    let editors = state.database.get_editors()?;
+    Ok(if editors.is_empty() { default_editors() } else { editors })
+}
+
+#[tauri::command]
+fn set_editors(editors: Vec<database::EditorConfig>, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    if editors.iter().any(|e| e.name.trim().is_empty() || e.command.trim().is_empty()) {
+        return Err("Each editor needs a name and a command".to_string());
+    }
+    state.database.set_editors(&editors)
+}
+
+/// Executable for an editor command, including app bundles GUI launches can't see on PATH
+fn resolve_editor_command(command: &str) -> Option<std::path::PathBuf> {
+    if let Some(path) = find_command_path(command) {
+        return Some(path);
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let bundled = match command {
+            "code" => Some("/Applications/Visual Studio Code.app/Contents/Resources/app/bin/code"),
+            "cursor" => Some("/Applications/Cursor.app/Contents/Resources/app/bin/cursor"),
+            "subl" => Some("/Applications/Sublime Text.app/Contents/SharedSupport/bin/subl"),
+            _ => None,
+        };
+        if let Some(path) = bundled.map(std::path::PathBuf::from).filter(|p| p.exists()) {
+            return Some(path);
+        }
+    }
+    None
+}
+
 #[tauri::command]
-fn open_file_in_editor(path: String, line: Option<u32>, column: Option<u32>) -> Result<(), String> {
+fn open_file_in_editor(
+    path: String,
+    line: Option<u32>,
+    column: Option<u32>,
+    state: tauri::State<Arc<AppState>>,
+) -> Result<(), String> {
     use std::path::Path;
 
     let file_path = Path::new(&path);
@@ -2065,73 +4695,33 @@ fn open_file_in_editor(path: String, line: Option<u32>, column: Option<u32>) ->
         return Err(format!("File not found: {}", path));
     }
 
-    let line_num = line.unwrap_or(1);
-    let col_num = column.unwrap_or(1);
-
-    // Try VS Code first (most common code editor with line number support)
-    let vscode_result = {
-        #[cfg(target_os = "macos")]
-        {
-            // Try 'code' command first, then fall back to VS Code app bundle
-            std::process::Command::new("code")
-                .arg("--goto")
-                .arg(format!("{}:{}:{}", path, line_num, col_num))
-                .spawn()
-                .or_else(|_| {
-                    std::process::Command::new("/Applications/Visual Studio Code.app/Contents/Resources/app/bin/code")
-                        .arg("--goto")
-                        .arg(format!("{}:{}:{}", path, line_num, col_num))
-                        .spawn()
-                })
-        }
-        #[cfg(target_os = "windows")]
-        {
-            std::process::Command::new("code")
-                .arg("--goto")
-                .arg(format!("{}:{}:{}", path, line_num, col_num))
-                .spawn()
-        }
-        #[cfg(target_os = "linux")]
-        {
-            std::process::Command::new("code")
-                .arg("--goto")
-                .arg(format!("{}:{}:{}", path, line_num, col_num))
-                .spawn()
-        }
-    };
+    let line_num = line.unwrap_or(1).to_string();
+    let col_num = column.unwrap_or(1).to_string();
 
-    if vscode_result.is_ok() {
-        return Ok(());
+    let mut editors = state.database.get_editors()?;
+    if editors.is_empty() {
+        editors = default_editors();
     }
 
-    // Try Cursor editor (VS Code fork)
-    let cursor_result = std::process::Command::new("cursor")
-        .arg("--goto")
-        .arg(format!("{}:{}:{}", path, line_num, col_num))
-        .spawn();
-
-    if cursor_result.is_ok() {
-        return Ok(());
-    }
+    // Try each editor in order; skip ones that aren't installed
+    for editor in &editors {
+        let Some(command) = resolve_editor_command(&editor.command) else { continue };
+        let args: Vec<String> = editor
+            .args
+            .iter()
+            .map(|a| a.replace("{path}", &path).replace("{line}", &line_num).replace("{col}", &col_num))
+            .collect();
 
-    // Try Zed editor
-    let zed_result = {
-        #[cfg(target_os = "macos")]
-        {
-            std::process::Command::new("zed")
-                .arg(format!("{}:{}", path, line_num))
-                .spawn()
-        }
-        #[cfg(not(target_os = "macos"))]
-        {
-            std::process::Command::new("zed")
-                .arg(format!("{}:{}", path, line_num))
-                .spawn()
+        let launched = if editor.terminal {
+            let mut argv = vec![command.to_string_lossy().to_string()];
+            argv.extend(args);
+            launch_in_terminal(&argv).is_ok()
+        } else {
+            std::process::Command::new(&command).args(&args).spawn().is_ok()
+        };
+        if launched {
+            return Ok(());
         }
-    };
-
-    if zed_result.is_ok() {
-        return Ok(());
     }
 
     // Fall back to system default application
@@ -2169,18 +4759,34 @@ fn open_in_terminal_editor(path: String, editor: String) -> Result<(), String> {
         return Err(format!("File not found: {}", path));
     }
 
-    // Escape the path for shell use
-    let escaped_path = path.replace("'", "'\\''");
+    // `editor` may carry its own flags, e.g. "nvim -R"
+    let mut argv: Vec<String> = editor.split_whitespace().map(str::to_string).collect();
+    if argv.is_empty() {
+        return Err("No editor given".to_string());
+    }
+    argv.push(path);
+    launch_in_terminal(&argv)
+}
+
+/// Run `argv` in a new window of the platform's terminal emulator
+fn launch_in_terminal(argv: &[String]) -> Result<(), String> {
+    // Single-quote each argument for the shell the terminal starts
+    #[cfg(not(target_os = "windows"))]
+    let command_line = argv
+        .iter()
+        .map(|a| format!("'{}'", a.replace('\'', "'\\''")))
+        .collect::<Vec<_>>()
+        .join(" ");
 
     #[cfg(target_os = "macos")]
     {
-        // Use osascript to open a new Terminal window with the editor
+        // Use osascript to open a new Terminal window with the command
         let script = format!(
             r#"tell application "Terminal"
                 activate
-                do script "{} '{}'"
+                do script "{}"
             end tell"#,
-            editor, escaped_path
+            command_line.replace('\\', "\\\\").replace('"', "\\\"")
         );
         std::process::Command::new("osascript")
             .arg("-e")
@@ -2191,9 +4797,10 @@ fn open_in_terminal_editor(path: String, editor: String) -> Result<(), String> {
 
     #[cfg(target_os = "windows")]
     {
-        // Open a new cmd window with the editor
+        // Open a new cmd window with the command
+        let quoted = argv.iter().map(|a| format!("\"{}\"", a)).collect::<Vec<_>>().join(" ");
         cmd_no_window("cmd")
-            .args(["/c", "start", "cmd", "/k", &format!("{} \"{}\"", editor, path)])
+            .args(["/c", "start", "cmd", "/k", &quoted])
             .spawn()
             .map_err(|e| e.to_string())?;
     }
@@ -2205,7 +4812,7 @@ fn open_in_terminal_editor(path: String, editor: String) -> Result<(), String> {
         // Check $TERMINAL env var first (user preference)
         if let Ok(user_term) = std::env::var("TERMINAL") {
             if std::process::Command::new(&user_term)
-                .args(["-e", &format!("{} '{}'", editor, escaped_path)])
+                .args(["-e", &command_line])
                 .spawn()
                 .is_ok()
             {
@@ -2222,23 +4829,20 @@ fn open_in_terminal_editor(path: String, editor: String) -> Result<(), String> {
 
             for term in terminals {
                 let result = match term {
-                    "gnome-terminal" => std::process::Command::new(term)
-                        .args(["--", &editor, &path])
+                    "gnome-terminal" | "kitty" | "foot" => std::process::Command::new(term)
+                        .arg("--")
+                        .args(argv)
                         .spawn(),
                     "konsole" | "alacritty" => std::process::Command::new(term)
-                        .args(["-e", &editor, &path])
-                        .spawn(),
-                    "kitty" => std::process::Command::new(term)
-                        .args(["--", &editor, &path])
+                        .arg("-e")
+                        .args(argv)
                         .spawn(),
                     "wezterm" => std::process::Command::new(term)
-                        .args(["start", "--", &editor, &path])
-                        .spawn(),
-                    "foot" => std::process::Command::new(term)
-                        .args(["--", &editor, &path])
+                        .args(["start", "--"])
+                        .args(argv)
                         .spawn(),
                     _ => std::process::Command::new(term)
-                        .args(["-e", &format!("{} '{}'", editor, escaped_path)])
+                        .args(["-e", &command_line])
                         .spawn(),
                 };
 
@@ -2292,14 +4896,17 @@ async fn get_shell_history(limit: Option<usize>) -> Result<Vec<String>, String>
     tokio::task::spawn_blocking(move || {
         let limit = limit.unwrap_or(500);
 
-        let mut history_paths: Vec<String> = Vec::new();
+        let mut history_paths: Vec<(&str, String)> = Vec::new();
 
         #[cfg(target_os = "windows")]
         {
             if let Ok(appdata) = std::env::var("APPDATA") {
-                history_paths.push(format!(
-                    "{}\\Microsoft\\Windows\\PowerShell\\PSReadLine\\ConsoleHost_history.txt",
-                    appdata
+                history_paths.push((
+                    "powershell",
+                    format!(
+                        "{}\\Microsoft\\Windows\\PowerShell\\PSReadLine\\ConsoleHost_history.txt",
+                        appdata
+                    ),
                 ));
             }
         }
@@ -2309,30 +4916,23 @@ async fn get_shell_history(limit: Option<usize>) -> Result<Vec<String>, String>
                 // Respect $SHELL: if user's shell is bash, try bash first
                 let shell = std::env::var("SHELL").unwrap_or_default();
                 if shell.ends_with("/zsh") {
-                    history_paths.push(format!("{}/.zsh_history", home));
-                    history_paths.push(format!("{}/.bash_history", home));
+                    history_paths.push(("zsh", format!("{}/.zsh_history", home)));
+                    history_paths.push(("bash", format!("{}/.bash_history", home)));
                 } else {
-                    history_paths.push(format!("{}/.bash_history", home));
-                    history_paths.push(format!("{}/.zsh_history", home));
+                    history_paths.push(("bash", format!("{}/.bash_history", home)));
+                    history_paths.push(("zsh", format!("{}/.zsh_history", home)));
                 }
             }
         }
 
-        for history_path in history_paths {
+        for (shell, history_path) in history_paths {
             let path = std::path::Path::new(&history_path);
             if path.exists() {
                 let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
-                let content = String::from_utf8_lossy(&bytes);
-                let mut commands: Vec<String> = content
-                    .lines()
-                    .filter_map(|line| {
-                        let cmd = if line.starts_with(':') {
-                            line.splitn(2, ';').nth(1).map(|s| s.to_string())
-                        } else {
-                            Some(line.to_string())
-                        };
-                        cmd.filter(|s| !s.trim().is_empty())
-                    })
+                let mut commands: Vec<String> = shell_history::parse_history(shell, &bytes)
+                    .into_iter()
+                    .map(|(command, _)| command)
+                    .filter(|command| !command.trim().is_empty())
                     .collect();
 
                 let mut seen = std::collections::HashSet::new();
@@ -2351,59 +4951,15 @@ async fn get_shell_history(limit: Option<usize>) -> Result<Vec<String>, String>
     .map_err(|e| format!("Task failed: {}", e))?
 }
 
-// Per-project shell history entry
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ShellHistoryEntry {
-    command: String,
-    project_path: String,
-    timestamp: i64,
-}
-
-// Get the path to Orca's shell history file
-fn get_orca_history_path() -> Option<PathBuf> {
-    dirs::home_dir().map(|home| home.join(".claude").join("shell_history.json"))
-}
-
 // Record a command to project-specific history
 #[tauri::command]
 async fn record_project_command(command: String, project_path: String) -> Result<(), String> {
     tokio::task::spawn_blocking(move || {
-        let command = command.trim().to_string();
+        let command = command.trim();
         if command.is_empty() {
             return Ok(());
         }
-
-        let history_path = get_orca_history_path().ok_or("Could not determine history path")?;
-
-        if let Some(parent) = history_path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-        }
-
-        let mut entries: Vec<ShellHistoryEntry> = if history_path.exists() {
-            let content = std::fs::read_to_string(&history_path).unwrap_or_default();
-            serde_json::from_str(&content).unwrap_or_default()
-        } else {
-            Vec::new()
-        };
-
-        let entry = ShellHistoryEntry {
-            command: command.clone(),
-            project_path: project_path.clone(),
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0),
-        };
-        entries.push(entry);
-
-        if entries.len() > 5000 {
-            entries = entries.split_off(entries.len() - 5000);
-        }
-
-        let content = serde_json::to_string(&entries).map_err(|e| e.to_string())?;
-        std::fs::write(&history_path, content).map_err(|e| e.to_string())?;
-
-        Ok(())
+        shell_history::record(command, &project_path)
     })
     .await
     .map_err(|e| format!("Task failed: {}", e))?
@@ -2413,19 +4969,12 @@ async fn record_project_command(command: String, project_path: String) -> Result
 #[tauri::command]
 fn get_project_shell_history(project_path: String, limit: Option<usize>) -> Result<Vec<String>, String> {
     let limit = limit.unwrap_or(500);
-    let history_path = get_orca_history_path().ok_or("Could not determine history path")?;
-
-    if !history_path.exists() {
-        return Ok(Vec::new());
-    }
-
-    let content = std::fs::read_to_string(&history_path).map_err(|e| e.to_string())?;
-    let entries: Vec<ShellHistoryEntry> = serde_json::from_str(&content).unwrap_or_default();
+    let entries = shell_history::read_entries()?;
 
     // Filter by project path and extract commands
     let mut commands: Vec<String> = entries
         .into_iter()
-        .filter(|e| e.project_path == project_path || e.project_path.starts_with(&format!("{}/", project_path)))
+        .filter(|e| shell_history::in_project(e, &project_path))
         .map(|e| e.command)
         .collect();
 
@@ -2440,6 +4989,152 @@ fn get_project_shell_history(project_path: String, limit: Option<usize>) -> Resu
     Ok(commands[start..].to_vec())
 }
 
+/// Ctrl-R style search over the project's recorded commands, newest first
+#[tauri::command]
+async fn search_command_history(
+    project_path: String,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<shell_history::HistoryMatch>, String> {
+    let _timer = profiling::Timer::start("search_command_history");
+    tokio::task::spawn_blocking(move || shell_history::search(&project_path, &query, limit.unwrap_or(50)))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Orca's project history and the user's own shell history as one list, newest first
+#[tauri::command]
+async fn get_combined_history(
+    project_path: String,
+    limit: Option<usize>,
+) -> Result<Vec<shell_history::CombinedHistoryEntry>, String> {
+    let _timer = profiling::Timer::start("get_combined_history");
+    tokio::task::spawn_blocking(move || shell_history::combined(&project_path, limit.unwrap_or(500)))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+// Locally captured error report for diagnostic bundles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorReport {
+    pub id: String,
+    pub timestamp: i64,
+    pub context: String,
+    pub error: String,
+    pub metadata: Option<serde_json::Value>,
+    #[serde(rename = "appVersion")]
+    pub app_version: String,
+    pub os: String,
+}
+
+const MAX_ERROR_REPORTS: usize = 500;
+
+fn get_error_reports_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("orca").join("error_reports.json"))
+}
+
+fn read_error_reports(path: &std::path::Path) -> Vec<ErrorReport> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Save a PNG of the focused Orca window (falling back to the main window) to `path`
+/// for attaching to bug reports. On macOS this needs Screen Recording permission.
+#[tauri::command]
+async fn capture_window_screenshot(path: String, app_handle: tauri::AppHandle) -> Result<String, String> {
+    let windows = app_handle.webview_windows();
+    let target = windows
+        .values()
+        .find(|w| w.is_focused().unwrap_or(false))
+        .or_else(|| windows.get("main"))
+        .ok_or("No window to capture")?;
+    let title = target.title().unwrap_or_default();
+    let position = target.outer_position().ok();
+
+    tokio::task::spawn_blocking(move || {
+        let pid = std::process::id();
+        let candidates: Vec<xcap::Window> = xcap::Window::all()
+            .map_err(|e| format!("Failed to list windows: {}", e))?
+            .into_iter()
+            .filter(|w| w.pid().ok() == Some(pid) && !w.is_minimized().unwrap_or(false))
+            .collect();
+
+        // Titles can collide between Orca windows, so use position to break ties
+        let matches_position = |w: &xcap::Window| match position {
+            Some(p) => w.x().ok() == Some(p.x) && w.y().ok() == Some(p.y),
+            None => false,
+        };
+        let window = candidates
+            .iter()
+            .find(|w| w.title().ok().as_deref() == Some(title.as_str()) && matches_position(w))
+            .or_else(|| candidates.iter().find(|w| w.title().ok().as_deref() == Some(title.as_str())))
+            .or_else(|| candidates.first())
+            .ok_or("Could not find the Orca window on screen")?;
+
+        let image = window
+            .capture_image()
+            .map_err(|e| format!("Failed to capture window: {}", e))?;
+
+        let mut path = PathBuf::from(path);
+        if path.extension().is_none() {
+            path.set_extension("png");
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        image
+            .save_with_format(&path, xcap::image::ImageFormat::Png)
+            .map_err(|e| format!("Failed to save screenshot: {}", e))?;
+        Ok(path.to_string_lossy().to_string())
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+// Record a failed command (context is usually the command name) for later diagnosis
+#[tauri::command]
+fn report_error(context: String, error: String, metadata: Option<serde_json::Value>) -> Result<(), String> {
+    let path = get_error_reports_path().ok_or("Could not determine data directory")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let mut reports = read_error_reports(&path);
+    reports.push(ErrorReport {
+        id: Uuid::new_v4().to_string(),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        context,
+        error: logging::redact_secrets(&error),
+        metadata,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+    });
+
+    if reports.len() > MAX_ERROR_REPORTS {
+        reports = reports.split_off(reports.len() - MAX_ERROR_REPORTS);
+    }
+
+    let content = serde_json::to_string(&reports).map_err(|e| e.to_string())?;
+    std::fs::write(&path, content).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_error_reports() -> Result<Vec<ErrorReport>, String> {
+    let path = get_error_reports_path().ok_or("Could not determine data directory")?;
+    Ok(read_error_reports(&path))
+}
+
+#[tauri::command]
+fn clear_error_reports() -> Result<(), String> {
+    let path = get_error_reports_path().ok_or("Could not determine data directory")?;
+    if path.exists() {
+        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
 // Helper function to find the full path of a command
 fn find_command_path(cmd: &str) -> Option<std::path::PathBuf> {
     // First try the standard which lookup
@@ -2809,6 +5504,9 @@ impl AiMessage {
     fn system(content: &str) -> Self {
         Self { role: "system".into(), content: Some(content.into()), tool_calls: None, tool_call_id: None, name: None }
     }
+    fn assistant(content: &str) -> Self {
+        Self { role: "assistant".into(), content: Some(content.into()), tool_calls: None, tool_call_id: None, name: None }
+    }
     fn tool_result(tool_call_id: &str, name: &str, content: &str) -> Self {
         Self { role: "tool".into(), content: Some(content.into()), tool_calls: None, tool_call_id: Some(tool_call_id.into()), name: Some(name.into()) }
     }
@@ -2867,6 +5565,52 @@ struct AiChoice {
 #[derive(Debug, Deserialize)]
 struct AiResponse {
     choices: Vec<AiChoice>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
+}
+
+/// Tokens consumed by a request. Reads both OpenAI-style `usage`
+/// (prompt_tokens/completion_tokens) and Claude's (input_tokens/output_tokens).
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct TokenUsage {
+    #[serde(rename = "promptTokens", alias = "prompt_tokens", alias = "input_tokens", default)]
+    prompt_tokens: u64,
+    #[serde(rename = "completionTokens", alias = "completion_tokens", alias = "output_tokens", default)]
+    completion_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Sum two optional usages; stays `None` only if neither provider reported any
+    fn combine(a: Option<TokenUsage>, b: Option<TokenUsage>) -> Option<TokenUsage> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(TokenUsage {
+                prompt_tokens: a.prompt_tokens + b.prompt_tokens,
+                completion_tokens: a.completion_tokens + b.completion_tokens,
+            }),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct NltUsageEvent {
+    request_id: String,
+    provider: String,
+    usage: TokenUsage,
+}
+
+/// Add a finished request's tokens to the provider's monthly total
+fn record_ai_usage(state: &AppState, provider: &str, usage: Option<TokenUsage>) {
+    let Some(usage) = usage else { return };
+    if let Err(e) = state.database.record_ai_usage(provider, usage.prompt_tokens, usage.completion_tokens) {
+        log::warn!("Failed to record AI usage: {}", e);
+    }
+}
+
+/// Monthly token totals per provider, for estimating spend
+#[tauri::command]
+fn get_ai_usage(month: Option<String>, state: tauri::State<Arc<AppState>>) -> Result<Vec<database::AiUsageTotal>, String> {
+    state.database.get_ai_usage(month.as_deref())
 }
 
 // --- Claude (Anthropic) API types ---
@@ -2926,6 +5670,8 @@ struct ClaudeTool {
 struct ClaudeResponse {
     content: Vec<ClaudeResponseBlock>,
     stop_reason: Option<String>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -2952,6 +5698,30 @@ struct ProviderConfig {
     supports_temperature: bool,
     commit_max_tokens: u32,
     nlt_max_tokens: u32,
+    /// Local providers (Ollama) run without an API key
+    requires_api_key: bool,
+    auth_style: AuthStyle,
+}
+
+/// How an OpenAI-compatible endpoint expects the key
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AuthStyle {
+    /// `Authorization: Bearer <key>` (OpenAI, Groq, OpenRouter, Together, ...)
+    Bearer,
+    /// `api-key: <key>` (Azure OpenAI)
+    ApiKey,
+    None,
+}
+
+impl AuthStyle {
+    fn parse(style: &str) -> Option<Self> {
+        match style {
+            "bearer" => Some(Self::Bearer),
+            "api-key" | "azure" => Some(Self::ApiKey),
+            "none" => Some(Self::None),
+            _ => None,
+        }
+    }
 }
 
 fn get_provider_config(provider: &str) -> ProviderConfig {
@@ -2965,6 +5735,8 @@ fn get_provider_config(provider: &str) -> ProviderConfig {
             supports_temperature: false,
             commit_max_tokens: 2048,  // reasoning models need headroom for thinking
             nlt_max_tokens: 4096,
+            requires_api_key: true,
+            auth_style: AuthStyle::Bearer,
         },
         "claude" => ProviderConfig {
             endpoint: "https://api.anthropic.com/v1/messages".into(),
@@ -2975,6 +5747,45 @@ fn get_provider_config(provider: &str) -> ProviderConfig {
             supports_temperature: true,
             commit_max_tokens: 200,
             nlt_max_tokens: 1024,
+            requires_api_key: true,
+            auth_style: AuthStyle::Bearer,
+        },
+        "ollama" => ProviderConfig {
+            endpoint: format!("{}/v1/chat/completions", OLLAMA_DEFAULT_HOST),
+            commit_model: "llama3.2".into(),
+            nlt_model: "llama3.1".into(),
+            is_claude: false,
+            use_max_completion_tokens: false,
+            supports_temperature: true,
+            commit_max_tokens: 200,
+            nlt_max_tokens: 1024,
+            requires_api_key: false,
+            auth_style: AuthStyle::Bearer,
+        },
+        // Azure and custom gateways have no default endpoint; one must be passed in
+        "azure" => ProviderConfig {
+            endpoint: String::new(),
+            commit_model: String::new(),
+            nlt_model: String::new(),
+            is_claude: false,
+            use_max_completion_tokens: true,
+            supports_temperature: false,
+            commit_max_tokens: 2048,
+            nlt_max_tokens: 4096,
+            requires_api_key: true,
+            auth_style: AuthStyle::ApiKey,
+        },
+        "custom" => ProviderConfig {
+            endpoint: String::new(),
+            commit_model: String::new(),
+            nlt_model: String::new(),
+            is_claude: false,
+            use_max_completion_tokens: false,
+            supports_temperature: true,
+            commit_max_tokens: 200,
+            nlt_max_tokens: 1024,
+            requires_api_key: false,
+            auth_style: AuthStyle::Bearer,
         },
         _ => ProviderConfig { // "groq" default
             endpoint: "https://api.groq.com/openai/v1/chat/completions".into(),
@@ -2985,42 +5796,98 @@ fn get_provider_config(provider: &str) -> ProviderConfig {
             supports_temperature: true,
             commit_max_tokens: 200,
             nlt_max_tokens: 1024,
+            requires_api_key: true,
+            auth_style: AuthStyle::Bearer,
         },
     }
 }
 
-/// Send a simple (non-tool) request to Claude and return the text response.
-async fn claude_simple_request(
-    client: &reqwest::Client,
-    api_key: &str,
-    model: &str,
-    endpoint: &str,
-    system: Option<&str>,
-    user_message: &str,
-    temperature: f32,
-    max_tokens: u32,
-) -> Result<String, String> {
-    let request = ClaudeRequest {
-        model: model.to_string(),
-        max_tokens,
-        system: system.map(|s| s.to_string()),
-        messages: vec![ClaudeMessage {
-            role: "user".into(),
-            content: ClaudeContent::Text(user_message.into()),
+const OLLAMA_DEFAULT_HOST: &str = "http://localhost:11434";
+const AZURE_API_VERSION: &str = "2024-10-21";
+
+/// Point a provider at a custom endpoint and/or auth header style. For Ollama a bare
+/// host like `http://gpu-box:11434` is enough; the OpenAI-compatible path is filled in.
+/// Azure endpoints are deployment URLs and get a default `api-version` if missing.
+fn apply_endpoint_override(
+    config: &mut ProviderConfig,
+    provider: &str,
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+) -> Result<(), String> {
+    if let Some(style) = auth_style.filter(|s| !s.is_empty()) {
+        config.auth_style = AuthStyle::parse(&style).ok_or_else(|| format!("Unknown auth style: {}", style))?;
+    }
+
+    let Some(endpoint) = endpoint.filter(|e| !e.trim().is_empty()) else {
+        if config.endpoint.is_empty() {
+            return Err(format!("The {} provider needs an endpoint URL", provider));
+        }
+        return Ok(());
+    };
+    let endpoint = endpoint.trim().trim_end_matches('/').to_string();
+    config.endpoint = if provider == "ollama" && !endpoint.ends_with("/chat/completions") {
+        format!("{}/v1/chat/completions", endpoint.trim_end_matches("/v1"))
+    } else if provider == "azure" && !endpoint.contains("api-version=") {
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        format!("{}{}api-version={}", endpoint, separator, AZURE_API_VERSION)
+    } else {
+        endpoint
+    };
+    Ok(())
+}
+
+/// Key passed from the frontend, falling back to the provider's entry in the OS keychain.
+/// Keyless providers (Ollama, custom gateways) never get a stored key, since their
+/// endpoint is whatever the user typed in.
+fn resolve_api_key(api_key: String, provider: &str) -> String {
+    if !api_key.is_empty() || !get_provider_config(provider).requires_api_key {
+        return api_key;
+    }
+    secrets::ai_api_key(provider).unwrap_or_default()
+}
+
+/// Auth header for OpenAI-compatible endpoints; skipped when there's no key (local Ollama)
+fn authorize(request: reqwest::RequestBuilder, config: &ProviderConfig, api_key: &str) -> reqwest::RequestBuilder {
+    if api_key.is_empty() {
+        return request;
+    }
+    match config.auth_style {
+        AuthStyle::Bearer => request.header("Authorization", format!("Bearer {}", api_key)),
+        AuthStyle::ApiKey => request.header("api-key", api_key),
+        AuthStyle::None => request,
+    }
+}
+
+/// Send a simple (non-tool) request to Claude and return the text response.
+async fn claude_simple_request(
+    client: &reqwest::Client,
+    api_key: &str,
+    model: &str,
+    endpoint: &str,
+    system: Option<&str>,
+    user_message: &str,
+    temperature: f32,
+    max_tokens: u32,
+) -> Result<(String, Option<TokenUsage>), String> {
+    let request = ClaudeRequest {
+        model: model.to_string(),
+        max_tokens,
+        system: system.map(|s| s.to_string()),
+        messages: vec![ClaudeMessage {
+            role: "user".into(),
+            content: ClaudeContent::Text(user_message.into()),
         }],
         temperature: Some(temperature),
         tools: None,
     };
 
-    let response = client
+    let request = client
         .post(endpoint)
         .header("x-api-key", api_key)
         .header("anthropic-version", "2023-06-01")
         .header("Content-Type", "application/json")
-        .json(&request)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+        .json(&request);
+    let response = send_with_retry(request, None, |_, _| {}).await?;
 
     if !response.status().is_success() {
         let error_text = response.text().await.unwrap_or_default();
@@ -3032,7 +5899,7 @@ async fn claude_simple_request(
     // Extract text from response blocks
     for block in &claude_response.content {
         if let ClaudeResponseBlock::Text { text } = block {
-            return Ok(text.clone());
+            return Ok((text.clone(), claude_response.usage));
         }
     }
     Err("No text response from Claude".to_string())
@@ -3042,12 +5909,16 @@ async fn claude_simple_request(
 struct CommitSuggestion {
     subject: String,
     description: String,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    usage: Option<TokenUsage>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct NltResponse {
     command: String,
     explanation: Option<String>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    usage: Option<TokenUsage>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -3058,6 +5929,9 @@ struct NltProgressEvent {
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_name: Option<String>,
     iteration: usize,
+    // Preview of a tool's output for "tool_result" events; the model still gets the full text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<String>,
 }
 
 // Portal commands
@@ -3068,20 +5942,20 @@ fn set_portal_enabled(enabled: bool, state: tauri::State<Arc<AppState>>) {
 
 #[tauri::command]
 fn get_portal_config(state: tauri::State<Arc<AppState>>) -> Result<database::PortalConfig, String> {
-    let db = state.database.lock();
+    let db = &state.database;
     db.get_portal_config()
 }
 
 #[tauri::command]
 fn set_portal_config(config: database::PortalConfig, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
-    let db = state.database.lock();
+    let db = &state.database;
     db.set_portal_config(&config)
 }
 
 #[tauri::command]
 fn portal_enable(app: tauri::AppHandle, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
     let mut config = {
-        let db = state.database.lock();
+        let db = &state.database;
         db.get_portal_config()?
     };
 
@@ -3089,7 +5963,7 @@ fn portal_enable(app: tauri::AppHandle, state: tauri::State<Arc<AppState>>) -> R
 
     // Save config
     {
-        let db = state.database.lock();
+        let db = &state.database;
         db.set_portal_config(&config)?;
     }
 
@@ -3109,14 +5983,14 @@ fn portal_enable(app: tauri::AppHandle, state: tauri::State<Arc<AppState>>) -> R
 fn portal_disable(state: tauri::State<Arc<AppState>>) -> Result<(), String> {
     // Update config
     let mut config = {
-        let db = state.database.lock();
+        let db = &state.database;
         db.get_portal_config()?
     };
 
     config.is_enabled = false;
 
     {
-        let db = state.database.lock();
+        let db = &state.database;
         db.set_portal_config(&config)?;
     }
 
@@ -3137,7 +6011,7 @@ fn portal_regenerate_pairing(app: tauri::AppHandle, state: tauri::State<Arc<AppS
     use rand::seq::SliceRandom;
 
     let mut config = {
-        let db = state.database.lock();
+        let db = &state.database;
         db.get_portal_config()?
     };
 
@@ -3162,7 +6036,7 @@ fn portal_regenerate_pairing(app: tauri::AppHandle, state: tauri::State<Arc<AppS
 
     // Save config
     {
-        let db = state.database.lock();
+        let db = &state.database;
         db.set_portal_config(&config)?;
     }
 
@@ -3187,7 +6061,7 @@ fn portal_regenerate_pairing(app: tauri::AppHandle, state: tauri::State<Arc<AppS
 fn portal_get_status(state: tauri::State<Arc<AppState>>) -> Result<serde_json::Value, String> {
     let is_connected = state.portal.lock().as_ref().map(|p| p.is_connected()).unwrap_or(false);
     let config = {
-        let db = state.database.lock();
+        let db = &state.database;
         db.get_portal_config()?
     };
 
@@ -3201,6 +6075,42 @@ fn portal_get_status(state: tauri::State<Arc<AppState>>) -> Result<serde_json::V
     }))
 }
 
+/// Record which project the desktop is showing, tell the frontend, and mirror it to a
+/// connected Portal. Used both by the UI and by `select_project` from mobile.
+pub(crate) fn activate_project(state: &AppState, app_handle: &tauri::AppHandle, id: Option<String>) -> Result<(), String> {
+    if let Some(ref id) = id {
+        if state.database.get_project(id)?.is_none() {
+            return Err(format!("Project not found: {}", id));
+        }
+    }
+    *state.active_project.lock() = id.clone();
+
+    let _ = app_handle.emit("portal-active-project-changed", serde_json::json!({ "projectId": id }));
+    if let (Some(portal), Some(id)) = (state.portal.lock().as_ref(), id) {
+        portal.send_message(&serde_json::json!({
+            "type": "project_changed",
+            "id": uuid::Uuid::new_v4().to_string(),
+            "timestamp": chrono::Utc::now().timestamp_millis(),
+            "projectId": id,
+        }));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn set_active_project(
+    id: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Arc<AppState>>,
+) -> Result<(), String> {
+    activate_project(&state, &app_handle, id)
+}
+
+#[tauri::command]
+fn get_active_project(state: tauri::State<Arc<AppState>>) -> Option<String> {
+    state.active_project.lock().clone()
+}
+
 #[tauri::command]
 fn portal_send_message(message: serde_json::Value, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
     if let Some(portal) = state.portal.lock().as_ref() {
@@ -3221,10 +6131,7 @@ fn portal_register_mobile_terminal(terminal_id: String, state: tauri::State<Arc<
             .terminals
             .lock()
             .get(&terminal_id)
-            .map(|t| {
-                let buffer = t.output_buffer.lock();
-                String::from_utf8_lossy(&buffer).to_string()
-            })
+            .map(|t| decode_output_buffer(&t.output_buffer.lock()))
             .unwrap_or_default();
 
         if !buffer_data.is_empty() {
@@ -3267,189 +6174,924 @@ pub struct ProjectContext {
 }
 
 #[tauri::command]
-async fn generate_commit_message(
-    diffs: Vec<FileDiff>,
+async fn generate_commit_message(
+    diffs: Vec<FileDiff>,
+    api_key: String,
+    provider: Option<String>,
+    model: Option<String>,
+    conventional: Option<bool>,
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<CommitSuggestion, String> {
+    let conventional = conventional.unwrap_or(false);
+    let config = commit_provider_config(provider.as_deref(), model, endpoint, auth_style)?;
+    let api_key = resolve_api_key(api_key, provider.as_deref().unwrap_or("groq"));
+    if api_key.is_empty() && config.requires_api_key {
+        return Err("No API key provided".to_string());
+    }
+    let prompt = build_commit_prompt(&diffs, conventional);
+
+    let client = http_client();
+    let mut suggestion = request_commit_suggestion(&client, &config, &api_key, &prompt).await?;
+    if conventional {
+        suggestion = ensure_conventional(&client, &config, &api_key, &prompt, suggestion).await?;
+    }
+    record_ai_usage(&state, provider.as_deref().unwrap_or("groq"), suggestion.usage);
+    Ok(suggestion)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CommitMessageTokenEvent {
+    request_id: String,
+    token: String,
+}
+
+/// Like `generate_commit_message`, but emits `commit-message-token` events as the
+/// response streams in. `request_id` lets the UI tell concurrent generations apart.
+#[tauri::command]
+async fn generate_commit_message_stream(
+    request_id: String,
+    diffs: Vec<FileDiff>,
+    api_key: String,
+    provider: Option<String>,
+    model: Option<String>,
+    conventional: Option<bool>,
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<CommitSuggestion, String> {
+    let conventional = conventional.unwrap_or(false);
+    let config = commit_provider_config(provider.as_deref(), model, endpoint, auth_style)?;
+    let api_key = resolve_api_key(api_key, provider.as_deref().unwrap_or("groq"));
+    if api_key.is_empty() && config.requires_api_key {
+        return Err("No API key provided".to_string());
+    }
+    let prompt = build_commit_prompt(&diffs, conventional);
+
+    let client = http_client();
+    let (content, usage) = stream_completion(&client, &config, &api_key, &prompt, |token| {
+        let _ = app_handle.emit("commit-message-token", CommitMessageTokenEvent {
+            request_id: request_id.clone(),
+            token: token.to_string(),
+        });
+    })
+    .await?;
+    let mut suggestion = parse_commit_suggestion(&content)?;
+    suggestion.usage = usage;
+    if conventional {
+        suggestion = ensure_conventional(&client, &config, &api_key, &prompt, suggestion).await?;
+    }
+    record_ai_usage(&state, provider.as_deref().unwrap_or("groq"), suggestion.usage);
+    Ok(suggestion)
+}
+
+fn commit_provider_config(
+    provider: Option<&str>,
+    model: Option<String>,
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+) -> Result<ProviderConfig, String> {
+    let provider = provider.unwrap_or("groq");
+    let mut config = get_provider_config(provider);
+    apply_endpoint_override(&mut config, provider, endpoint, auth_style)?;
+    if let Some(m) = model {
+        if !m.is_empty() {
+            config.commit_model = m.clone();
+            config.nlt_model = m;
+        }
+    }
+    Ok(config)
+}
+
+/// Condense diffs for a prompt: code changes first (capped per file), then a short
+/// list of metadata/lock file changes, truncated to ~5000 characters overall
+fn summarize_diffs(diffs: &[FileDiff], line_numbers: bool) -> String {
+    // Metadata/config files that should be summarized briefly
+    let metadata_patterns = [
+        "package.json", "package-lock.json", "Cargo.toml", "Cargo.lock",
+        "yarn.lock", "pnpm-lock.yaml", "composer.lock", "Gemfile.lock",
+        "poetry.lock", "go.sum", "pubspec.lock", ".version", "version.txt",
+        "VERSION", "tauri.conf.json", "app.json",
+    ];
+
+    let is_metadata_file = |path: &str| -> bool {
+        let filename = path.rsplit('/').next().unwrap_or(path);
+        metadata_patterns.iter().any(|p| filename == *p || filename.ends_with(".lock"))
+    };
+
+    // Separate diffs into code changes and metadata changes
+    let mut code_diffs: Vec<&FileDiff> = Vec::new();
+    let mut metadata_diffs: Vec<&FileDiff> = Vec::new();
+
+    for diff in diffs {
+        if is_metadata_file(&diff.path) {
+            metadata_diffs.push(diff);
+        } else {
+            code_diffs.push(diff);
+        }
+    }
+
+    // Build summary - prioritize code changes
+    let mut changes_summary = String::new();
+
+    // First, add code changes with full diffs (up to a limit per file)
+    for diff in &code_diffs {
+        changes_summary.push_str(&format!("## {} ({})\n", diff.path, diff.status));
+        let mut file_lines = 0;
+        for hunk in &diff.hunks {
+            for line in &hunk.lines {
+                if line.line_type == "addition" || line.line_type == "deletion" {
+                    let prefix = if line.line_type == "addition" { "+" } else { "-" };
+                    if line_numbers {
+                        // New-file line numbers, so the model can point at specific lines
+                        let number = line.new_line_no.map(|n| n.to_string()).unwrap_or_default();
+                        changes_summary.push_str(&format!("{:>5} {}{}\n", number, prefix, line.content));
+                    } else {
+                        changes_summary.push_str(&format!("{}{}\n", prefix, line.content));
+                    }
+                    file_lines += 1;
+                    // Limit lines per file to ensure we see all files
+                    if file_lines > 50 {
+                        changes_summary.push_str("  ... (more changes in this file)\n");
+                        break;
+                    }
+                }
+            }
+            if file_lines > 50 { break; }
+        }
+        changes_summary.push('\n');
+
+        // Stop if we're getting too long, but ensure we list remaining files
+        if changes_summary.len() > 3000 {
+            break;
+        }
+    }
+
+    // Then summarize metadata files briefly (just list them with key changes)
+    if !metadata_diffs.is_empty() {
+        changes_summary.push_str("## Metadata/Config changes:\n");
+        for diff in &metadata_diffs {
+            changes_summary.push_str(&format!("- {} ({})", diff.path, diff.status));
+            // For version bumps, try to extract the version change
+            if diff.path.contains("package.json") || diff.path.contains("Cargo.toml") || diff.path.contains("tauri.conf") {
+                for hunk in &diff.hunks {
+                    for line in &hunk.lines {
+                        if (line.content.contains("version") || line.content.contains("\"version\""))
+                            && (line.line_type == "addition" || line.line_type == "deletion") {
+                            let prefix = if line.line_type == "addition" { "+" } else { "-" };
+                            changes_summary.push_str(&format!("\n  {}{}", prefix, line.content.trim()));
+                        }
+                    }
+                }
+            }
+            changes_summary.push('\n');
+        }
+    }
+
+    // Truncate if still too long
+    if changes_summary.len() > 5000 {
+        let mut end = 5000;
+        while !changes_summary.is_char_boundary(end) {
+            end -= 1;
+        }
+        changes_summary.truncate(end);
+        changes_summary.push_str("\n... (truncated)");
+    }
+    changes_summary
+}
+
+/// Summarize the diffs into a prompt asking for a {"subject", "description"} JSON reply
+fn build_commit_prompt(diffs: &[FileDiff], conventional: bool) -> String {
+    let changes_summary = summarize_diffs(diffs, false);
+    let mut prompt = format!(
+        r#"Analyze these git changes and generate a commit message.
+
+IMPORTANT: Focus on the actual CODE changes, not just version bumps or lock file updates. If there are both code changes and version/metadata changes, the commit message should describe what the code does, not just "bump version".
+
+Changes:
+{}
+
+Respond with JSON only, no markdown:
+{{"subject": "short imperative subject line (max 50 chars)", "description": "optional longer description explaining why (can be empty string)"}}
+
+Examples of good subjects: "Add user authentication", "Fix null pointer in parser", "Refactor database queries"
+Bad subjects: "Update package.json", "Bump version", "Update dependencies"
+Keep the description brief or empty if the subject is self-explanatory."#,
+        changes_summary
+    );
+    if conventional {
+        prompt.push_str(&format!(
+            "\n\nThe subject MUST follow Conventional Commits: \"type(scope): subject\" where type is one of {} and the scope is optional. Examples: \"feat(auth): add login form\", \"fix: handle empty config\".",
+            commit_lint::CONVENTIONAL_TYPES.join(", ")
+        ));
+    }
+    prompt
+}
+
+/// Models don't always follow the Conventional Commits format on the first try;
+/// feed the problem back a couple of times before giving up
+async fn ensure_conventional(
+    client: &reqwest::Client,
+    config: &ProviderConfig,
+    api_key: &str,
+    prompt: &str,
+    mut suggestion: CommitSuggestion,
+) -> Result<CommitSuggestion, String> {
+    for _ in 0..2 {
+        if commit_lint::is_conventional_subject(&suggestion.subject) {
+            return Ok(suggestion);
+        }
+        let retry_prompt = format!(
+            "{}\n\nYour previous subject \"{}\" is not a valid Conventional Commits header. Respond again with JSON only.",
+            prompt, suggestion.subject
+        );
+        let usage_so_far = suggestion.usage;
+        suggestion = request_commit_suggestion(client, config, api_key, &retry_prompt).await?;
+        suggestion.usage = TokenUsage::combine(usage_so_far, suggestion.usage);
+    }
+    if commit_lint::is_conventional_subject(&suggestion.subject) {
+        Ok(suggestion)
+    } else {
+        Err(format!(
+            "AI did not return a Conventional Commits subject: {}",
+            suggestion.subject
+        ))
+    }
+}
+
+/// Send a commit message prompt and parse the {"subject", "description"} JSON reply
+async fn request_commit_suggestion(
+    client: &reqwest::Client,
+    config: &ProviderConfig,
+    api_key: &str,
+    prompt: &str,
+) -> Result<CommitSuggestion, String> {
+    let (content, usage) = ai_complete(client, config, api_key, prompt, config.commit_max_tokens).await?;
+    let mut suggestion = parse_commit_suggestion(&content)?;
+    suggestion.usage = usage;
+    Ok(suggestion)
+}
+
+/// Single-prompt, non-streaming completion with the commit model
+async fn ai_complete(
+    client: &reqwest::Client,
+    config: &ProviderConfig,
+    api_key: &str,
+    prompt: &str,
+    max_tokens: u32,
+) -> Result<(String, Option<TokenUsage>), String> {
+    if config.is_claude {
+        claude_simple_request(
+            client, api_key, &config.commit_model, &config.endpoint,
+            None, prompt, 0.3, max_tokens,
+        ).await
+    } else {
+        // OpenAI-compatible path (Groq, OpenAI, Ollama)
+        let request = AiRequest {
+            model: config.commit_model.clone(),
+            messages: vec![AiMessage::user(prompt)],
+            temperature: if config.supports_temperature { Some(0.3) } else { None },
+            max_tokens: if config.use_max_completion_tokens { None } else { Some(max_tokens) },
+            max_completion_tokens: if config.use_max_completion_tokens { Some(max_tokens) } else { None },
+            tools: None,
+            tool_choice: None,
+        };
+
+        let request = authorize(client.post(&config.endpoint), config, api_key)
+            .header("Content-Type", "application/json")
+            .json(&request);
+        let response = send_with_retry(request, None, |_, _| {}).await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("API error: {}", error_text));
+        }
+
+        let ai_response: AiResponse = response.json().await.map_err(|e| e.to_string())?;
+
+        let content = ai_response.choices.first()
+            .and_then(|c| c.message.content.clone())
+            .ok_or_else(|| "No response from AI".to_string())?;
+        Ok((content, ai_response.usage))
+    }
+}
+
+fn parse_commit_suggestion(content: &str) -> Result<CommitSuggestion, String> {
+    parse_ai_json(content)
+}
+
+/// Parse a JSON reply from a model, tolerating a surrounding markdown code fence
+fn parse_ai_json<T: serde::de::DeserializeOwned>(content: &str) -> Result<T, String> {
+    // Strip markdown code fences if present (e.g., ```json ... ```)
+    let json_content = content
+        .trim()
+        .strip_prefix("```json")
+        .or_else(|| content.trim().strip_prefix("```"))
+        .unwrap_or(content.trim())
+        .trim()
+        .strip_suffix("```")
+        .unwrap_or(content.trim())
+        .trim();
+
+    // Parse the JSON response
+    serde_json::from_str(json_content)
+        .map_err(|e| format!("Failed to parse AI response: {} - Content: {}", e, json_content))
+}
+
+/// Stream a single-prompt completion, calling `on_token` for each text chunk, and
+/// return the full text plus usage when the provider reports it. Handles both
+/// OpenAI-style SSE and Anthropic's event stream.
+async fn stream_completion(
+    client: &reqwest::Client,
+    config: &ProviderConfig,
+    api_key: &str,
+    prompt: &str,
+    mut on_token: impl FnMut(&str),
+) -> Result<(String, Option<TokenUsage>), String> {
+    use futures_util::StreamExt;
+
+    let request = if config.is_claude {
+        let body = ClaudeRequest {
+            model: config.commit_model.clone(),
+            max_tokens: config.commit_max_tokens,
+            system: None,
+            messages: vec![ClaudeMessage {
+                role: "user".into(),
+                content: ClaudeContent::Text(prompt.into()),
+            }],
+            temperature: Some(0.3),
+            tools: None,
+        };
+        let mut body = serde_json::to_value(&body).map_err(|e| e.to_string())?;
+        body["stream"] = true.into();
+        client
+            .post(&config.endpoint)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01")
+            .header("Content-Type", "application/json")
+            .json(&body)
+    } else {
+        let body = AiRequest {
+            model: config.commit_model.clone(),
+            messages: vec![AiMessage::user(prompt)],
+            temperature: if config.supports_temperature { Some(0.3) } else { None },
+            max_tokens: if config.use_max_completion_tokens { None } else { Some(config.commit_max_tokens) },
+            max_completion_tokens: if config.use_max_completion_tokens { Some(config.commit_max_tokens) } else { None },
+            tools: None,
+            tool_choice: None,
+        };
+        let mut body = serde_json::to_value(&body).map_err(|e| e.to_string())?;
+        body["stream"] = true.into();
+        // OpenAI only reports usage on a stream when asked, in a final chunk with no choices
+        body["stream_options"] = serde_json::json!({ "include_usage": true });
+        authorize(client.post(&config.endpoint), config, api_key)
+            .header("Content-Type", "application/json")
+            .json(&body)
+    };
+
+    let response = send_with_retry(request, None, |_, _| {}).await?;
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("API error: {}", error_text));
+    }
+
+    let mut stream = response.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut content = String::new();
+    let mut usage: Option<TokenUsage> = None;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        buffer.extend_from_slice(&chunk);
+
+        // SSE events are newline-delimited; keep any partial line for the next chunk. Lines
+        // are split as bytes so a character straddling two chunks is decoded whole.
+        while let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+            let line_bytes: Vec<u8> = buffer.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line_bytes);
+            let Some(data) = line.trim().strip_prefix("data:") else { continue };
+            let data = data.trim();
+            if data == "[DONE]" {
+                break;
+            }
+            let Ok(event) = serde_json::from_str::<serde_json::Value>(data) else { continue };
+            if let Some(error) = event.get("error") {
+                return Err(format!("API error: {}", error));
+            }
+            // Usage arrives piecemeal: Claude sends input tokens in message_start and a running
+            // output count in message_delta; OpenAI-style APIs (and Groq's x_groq) in the last chunk
+            for reported in [&event["usage"], &event["message"]["usage"], &event["x_groq"]["usage"]] {
+                if let Ok(reported) = serde_json::from_value::<TokenUsage>(reported.clone()) {
+                    let current = usage.get_or_insert_with(TokenUsage::default);
+                    current.prompt_tokens = current.prompt_tokens.max(reported.prompt_tokens);
+                    current.completion_tokens = current.completion_tokens.max(reported.completion_tokens);
+                }
+            }
+            // OpenAI: choices[0].delta.content; Claude: content_block_delta with delta.text
+            let token = event["choices"][0]["delta"]["content"]
+                .as_str()
+                .or_else(|| event["delta"]["text"].as_str());
+            if let Some(token) = token.filter(|t| !t.is_empty()) {
+                content.push_str(token);
+                on_token(token);
+            }
+        }
+    }
+
+    if content.is_empty() {
+        return Err("No response from AI".to_string());
+    }
+    Ok((content, usage))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ReviewFinding {
+    file: String,
+    #[serde(default)]
+    line: Option<u32>,
+    /// "error", "warning" or "info"
+    severity: String,
+    message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DiffReview {
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    findings: Vec<ReviewFinding>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    usage: Option<TokenUsage>,
+}
+
+/// Ask the model to review the given diffs before they're committed. Progress goes out
+/// as `ai-review-progress` events (same shape as `nlt-progress`) tagged with `request_id`.
+#[tauri::command]
+async fn ai_review_diff(
+    diffs: Vec<FileDiff>,
+    api_key: String,
+    provider: Option<String>,
+    model: Option<String>,
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+    request_id: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<DiffReview, String> {
+    let config = commit_provider_config(provider.as_deref(), model, endpoint, auth_style)?;
+    let api_key = resolve_api_key(api_key, provider.as_deref().unwrap_or("groq"));
+    if api_key.is_empty() && config.requires_api_key {
+        return Err("No API key provided".to_string());
+    }
+    if diffs.is_empty() {
+        return Ok(DiffReview { summary: "No changes to review.".to_string(), findings: Vec::new(), usage: None });
+    }
+
+    let request_id = request_id.unwrap_or_default();
+    let emit = |status: &str, message: &str| {
+        let _ = app_handle.emit("ai-review-progress", NltProgressEvent {
+            request_id: request_id.clone(),
+            status: status.into(),
+            message: message.into(),
+            tool_name: None,
+            iteration: 0,
+            result: None,
+        });
+    };
+    emit("thinking", &format!("Reviewing {} changed file(s)...", diffs.len()));
+
+    let prompt = format!(
+        r#"You are reviewing a set of uncommitted git changes. Lines are prefixed with their line number in the new file and +/-.
+
+Changes:
+{}
+
+Look for bugs, security problems, error handling gaps, leftover debug code and anything that will likely break. Skip style nitpicks and don't comment on lock files or version bumps.
+
+Respond with JSON only, no markdown:
+{{"summary": "one or two sentence overall assessment", "findings": [{{"file": "path/as/shown", "line": 42, "severity": "error|warning|info", "message": "what is wrong and how to fix it"}}]}}
+
+Use an empty findings array if the changes look good. "line" may be null when a finding isn't about a specific line."#,
+        summarize_diffs(&diffs, true)
+    );
+
+    let client = http_client();
+    let result = ai_complete(&client, &config, &api_key, &prompt, config.nlt_max_tokens.max(2048)).await;
+    let (content, usage) = match result {
+        Ok(r) => r,
+        Err(e) => {
+            emit("error", &e);
+            return Err(e);
+        }
+    };
+    record_ai_usage(&state, provider.as_deref().unwrap_or("groq"), usage);
+
+    let mut review: DiffReview = match parse_ai_json(&content) {
+        Ok(r) => r,
+        Err(e) => {
+            emit("error", &e);
+            return Err(e);
+        }
+    };
+    for finding in &mut review.findings {
+        finding.severity = match finding.severity.to_lowercase().as_str() {
+            "error" | "critical" | "high" => "error",
+            "warning" | "medium" => "warning",
+            _ => "info",
+        }
+        .to_string();
+    }
+    review.usage = usage;
+
+    emit("done", &format!("{} finding(s)", review.findings.len()));
+    Ok(review)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ResolvedConflictRegion {
+    #[serde(flatten)]
+    region: ConflictRegion,
+    resolved: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ConflictResolutionSuggestion {
+    /// The whole file with every conflict block replaced by its suggestion
+    content: String,
+    regions: Vec<ResolvedConflictRegion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<TokenUsage>,
+}
+
+/// Lines of unconflicted text shown around each block so the model sees what it's merging into
+const CONFLICT_CONTEXT_LINES: usize = 15;
+
+/// Ask the model for a merged version of each conflict block in `file_path`. Nothing is
+/// written; the UI shows the suggestion and passes the accepted content to `resolve_conflict`.
+#[tauri::command]
+async fn ai_resolve_conflict(
+    repo_path: String,
+    file_path: String,
     api_key: String,
     provider: Option<String>,
     model: Option<String>,
-) -> Result<CommitSuggestion, String> {
-    if api_key.is_empty() {
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<ConflictResolutionSuggestion, String> {
+    let config = commit_provider_config(provider.as_deref(), model, endpoint, auth_style)?;
+    let api_key = resolve_api_key(api_key, provider.as_deref().unwrap_or("groq"));
+    if api_key.is_empty() && config.requires_api_key {
         return Err("No API key provided".to_string());
     }
 
-    let provider_str = provider.as_deref().unwrap_or("groq");
-    let mut config = get_provider_config(provider_str);
-    if let Some(m) = model {
-        if !m.is_empty() {
-            config.commit_model = m.clone();
-            config.nlt_model = m;
-        }
+    let content = GitService::get_conflict_content(&repo_path, &file_path)?;
+    let chunks = GitService::parse_conflict_chunks(&content);
+    if !chunks.iter().any(|c| matches!(c, git::ConflictChunk::Conflict(_))) {
+        return Err(format!("No conflict markers found in {}", file_path));
     }
 
-    // Metadata/config files that should be summarized briefly
-    let metadata_patterns = [
-        "package.json", "package-lock.json", "Cargo.toml", "Cargo.lock",
-        "yarn.lock", "pnpm-lock.yaml", "composer.lock", "Gemfile.lock",
-        "poetry.lock", "go.sum", "pubspec.lock", ".version", "version.txt",
-        "VERSION", "tauri.conf.json", "app.json",
-    ];
+    let client = http_client();
+    let mut merged = String::new();
+    let mut regions = Vec::new();
+    let mut usage = None;
+
+    // Each block is resolved on its own, with a little surrounding text for context
+    for (i, chunk) in chunks.iter().enumerate() {
+        let region = match chunk {
+            git::ConflictChunk::Text(text) => {
+                merged.push_str(text);
+                continue;
+            }
+            git::ConflictChunk::Conflict(region) => region,
+        };
 
-    let is_metadata_file = |path: &str| -> bool {
-        let filename = path.rsplit('/').next().unwrap_or(path);
-        metadata_patterns.iter().any(|p| filename == *p || filename.ends_with(".lock"))
-    };
+        let before = match i.checked_sub(1).map(|j| &chunks[j]) {
+            Some(git::ConflictChunk::Text(text)) => {
+                let lines: Vec<&str> = text.lines().collect();
+                lines[lines.len().saturating_sub(CONFLICT_CONTEXT_LINES)..].join("\n")
+            }
+            _ => String::new(),
+        };
+        let after = match chunks.get(i + 1) {
+            Some(git::ConflictChunk::Text(text)) => {
+                text.lines().take(CONFLICT_CONTEXT_LINES).collect::<Vec<_>>().join("\n")
+            }
+            _ => String::new(),
+        };
+        let base = region
+            .base
+            .as_ref()
+            .map(|b| format!("\n=== BASE (common ancestor) ===\n{}", b))
+            .unwrap_or_default();
 
-    // Separate diffs into code changes and metadata changes
-    let mut code_diffs: Vec<&FileDiff> = Vec::new();
-    let mut metadata_diffs: Vec<&FileDiff> = Vec::new();
+        let prompt = format!(
+            r#"Resolve this git merge conflict in {file}.
+
+=== Code before the conflict ===
+{before}
+
+=== OURS ({ours_label}) ===
+{ours}{base}
+=== THEIRS ({theirs_label}) ===
+{theirs}
+=== Code after the conflict ===
+{after}
+
+Combine both sides so that the intent of each change is kept. If they are truly incompatible, prefer OURS.
+Reply with ONLY the merged code that replaces the conflict block (without the surrounding code and without conflict markers), inside a single ``` code block."#,
+            file = file_path,
+            ours_label = region.ours_label,
+            theirs_label = region.theirs_label,
+            ours = region.ours,
+            theirs = region.theirs,
+        );
 
-    for diff in &diffs {
-        if is_metadata_file(&diff.path) {
-            metadata_diffs.push(diff);
-        } else {
-            code_diffs.push(diff);
+        let (reply, reply_usage) =
+            ai_complete(&client, &config, &api_key, &prompt, config.nlt_max_tokens.max(2048)).await?;
+        usage = TokenUsage::combine(usage, reply_usage);
+
+        let mut resolved = extract_code_block(&reply);
+        // Keep the block's trailing newline so the following text stays on its own line
+        if !resolved.is_empty() && region.ours.ends_with('\n') && !resolved.ends_with('\n') {
+            resolved.push('\n');
         }
+        merged.push_str(&resolved);
+        regions.push(ResolvedConflictRegion { region: region.clone(), resolved });
     }
 
-    // Build summary - prioritize code changes
-    let mut changes_summary = String::new();
+    record_ai_usage(&state, provider.as_deref().unwrap_or("groq"), usage);
+    Ok(ConflictResolutionSuggestion { content: merged, regions, usage })
+}
 
-    // First, add code changes with full diffs (up to a limit per file)
-    for diff in &code_diffs {
-        changes_summary.push_str(&format!("## {} ({})\n", diff.path, diff.status));
-        let mut file_lines = 0;
-        for hunk in &diff.hunks {
-            for line in &hunk.lines {
-                if line.line_type == "addition" || line.line_type == "deletion" {
-                    let prefix = if line.line_type == "addition" { "+" } else { "-" };
-                    changes_summary.push_str(&format!("{}{}\n", prefix, line.content));
-                    file_lines += 1;
-                    // Limit lines per file to ensure we see all files
-                    if file_lines > 50 {
-                        changes_summary.push_str("  ... (more changes in this file)\n");
-                        break;
-                    }
-                }
-            }
-            if file_lines > 50 { break; }
-        }
-        changes_summary.push('\n');
+/// Text between the first and last ``` fence of a model reply, or the whole reply if unfenced
+fn extract_code_block(reply: &str) -> String {
+    let Some(start) = reply.find("```") else {
+        return reply.trim_matches('\n').to_string();
+    };
+    // Skip the language tag line
+    let after_fence = &reply[start + 3..];
+    let body_start = after_fence.find('\n').map(|i| i + 1).unwrap_or(after_fence.len());
+    let body = &after_fence[body_start..];
+    let body = body.rfind("```").map(|end| &body[..end]).unwrap_or(body);
+    body.trim_end_matches([' ', '\t']).trim_end_matches('\n').to_string()
+}
 
-        // Stop if we're getting too long, but ensure we list remaining files
-        if changes_summary.len() > 3000 {
-            break;
-        }
+#[derive(Debug, Serialize, Deserialize)]
+struct PrDescription {
+    title: String,
+    body: String,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    usage: Option<TokenUsage>,
+}
+
+/// Commits listed in a PR description prompt; long branches are cut from the oldest end
+const PR_PROMPT_MAX_COMMITS: usize = 50;
+
+/// Draft a pull request title and markdown body from the commits and diff between
+/// `base` and `head`, ready to hand to `create_pull_request`
+#[tauri::command]
+async fn ai_generate_pr_description(
+    repo_path: String,
+    base: String,
+    head: String,
+    api_key: String,
+    provider: Option<String>,
+    model: Option<String>,
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<PrDescription, String> {
+    let config = commit_provider_config(provider.as_deref(), model, endpoint, auth_style)?;
+    let api_key = resolve_api_key(api_key, provider.as_deref().unwrap_or("groq"));
+    if api_key.is_empty() && config.requires_api_key {
+        return Err("No API key provided".to_string());
     }
 
-    // Then summarize metadata files briefly (just list them with key changes)
-    if !metadata_diffs.is_empty() {
-        changes_summary.push_str("## Metadata/Config changes:\n");
-        for diff in &metadata_diffs {
-            changes_summary.push_str(&format!("- {} ({})", diff.path, diff.status));
-            // For version bumps, try to extract the version change
-            if diff.path.contains("package.json") || diff.path.contains("Cargo.toml") || diff.path.contains("tauri.conf") {
-                for hunk in &diff.hunks {
-                    for line in &hunk.lines {
-                        if (line.content.contains("version") || line.content.contains("\"version\""))
-                            && (line.line_type == "addition" || line.line_type == "deletion") {
-                            let prefix = if line.line_type == "addition" { "+" } else { "-" };
-                            changes_summary.push_str(&format!("\n  {}{}", prefix, line.content.trim()));
-                        }
-                    }
-                }
-            }
-            changes_summary.push('\n');
+    let (diffs, messages) = tokio::task::spawn_blocking(move || {
+        let diffs = GitService::diff_refs(&repo_path, &base, &head, true, false, None)?;
+        let mut messages = GitService::get_commit_messages_between(&repo_path, &base, &head, usize::MAX)?;
+        if messages.len() > PR_PROMPT_MAX_COMMITS {
+            messages.drain(..messages.len() - PR_PROMPT_MAX_COMMITS);
         }
-    }
+        Ok::<_, String>((diffs, messages))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))??;
 
-    // Truncate if still too long
-    if changes_summary.len() > 5000 {
-        changes_summary = changes_summary[..5000].to_string();
-        changes_summary.push_str("\n... (truncated)");
+    if diffs.is_empty() && messages.is_empty() {
+        return Err("No changes between the branches".to_string());
     }
 
+    let commits = messages
+        .iter()
+        .map(|m| format!("- {}", m.replace('\n', "\n  ")))
+        .collect::<Vec<_>>()
+        .join("\n");
     let prompt = format!(
-        r#"Analyze these git changes and generate a commit message.
+        r###"Write a pull request description for these changes.
 
-IMPORTANT: Focus on the actual CODE changes, not just version bumps or lock file updates. If there are both code changes and version/metadata changes, the commit message should describe what the code does, not just "bump version".
+Commits on the branch (oldest first):
+{}
 
 Changes:
 {}
 
-Respond with JSON only, no markdown:
-{{"subject": "short imperative subject line (max 50 chars)", "description": "optional longer description explaining why (can be empty string)"}}
+Respond with JSON only, no markdown around it:
+{{"title": "short imperative PR title (max 72 chars)", "body": "markdown body"}}
 
-Examples of good subjects: "Add user authentication", "Fix null pointer in parser", "Refactor database queries"
-Bad subjects: "Update package.json", "Bump version", "Update dependencies"
-Keep the description brief or empty if the subject is self-explanatory."#,
-        changes_summary
+The body should have a "## Summary" section with a short paragraph on what the PR does and why, then a "## Changes" section with a checklist ("- [x] ...") of the notable changes. Don't list lock file or version bumps unless that's all there is."###,
+        commits,
+        summarize_diffs(&diffs, false)
     );
 
     let client = http_client();
+    let (content, usage) = ai_complete(&client, &config, &api_key, &prompt, config.nlt_max_tokens.max(1024)).await?;
+    record_ai_usage(&state, provider.as_deref().unwrap_or("groq"), usage);
 
-    let content = if config.is_claude {
-        claude_simple_request(
-            &client, &api_key, &config.commit_model, &config.endpoint,
-            None, &prompt, 0.3, 200,
-        ).await?
+    let mut description: PrDescription = parse_ai_json(&content)?;
+    description.usage = usage;
+    Ok(description)
+}
+
+/// Lint a commit message: subject length, body wrapping and, optionally, Conventional Commits format
+#[tauri::command]
+fn validate_commit_message(message: String, conventional: Option<bool>) -> Vec<commit_lint::LintIssue> {
+    commit_lint::lint(&message, conventional.unwrap_or(false))
+}
+
+/// Send a tiny completion with these settings and return the model that answered,
+/// so a bad key, endpoint or model name shows up in Settings rather than later
+#[tauri::command]
+async fn test_ai_connection(
+    provider: String,
+    api_key: String,
+    model: String,
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+) -> Result<String, String> {
+    let mut config = get_provider_config(&provider);
+    apply_endpoint_override(&mut config, &provider, endpoint, auth_style)?;
+    if !model.is_empty() {
+        config.commit_model = model;
+    }
+    let api_key = resolve_api_key(api_key, &provider);
+    if api_key.is_empty() && config.requires_api_key {
+        return Err("No API key provided".to_string());
+    }
+
+    let client = http_client();
+    let request = if config.is_claude {
+        let body = ClaudeRequest {
+            model: config.commit_model.clone(),
+            max_tokens: 1,
+            system: None,
+            messages: vec![ClaudeMessage {
+                role: "user".into(),
+                content: ClaudeContent::Text("ping".into()),
+            }],
+            temperature: None,
+            tools: None,
+        };
+        client
+            .post(&config.endpoint)
+            .header("x-api-key", &api_key)
+            .header("anthropic-version", "2023-06-01")
+            .json(&body)
     } else {
-        // OpenAI-compatible path (Groq, OpenAI)
-        let request = AiRequest {
+        // Reasoning models can spend a few tokens before answering; still cheap
+        let body = AiRequest {
             model: config.commit_model.clone(),
-            messages: vec![AiMessage::user(&prompt)],
-            temperature: if config.supports_temperature { Some(0.3) } else { None },
-            max_tokens: if config.use_max_completion_tokens { None } else { Some(config.commit_max_tokens) },
-            max_completion_tokens: if config.use_max_completion_tokens { Some(config.commit_max_tokens) } else { None },
+            messages: vec![AiMessage::user("ping")],
+            temperature: None,
+            max_tokens: if config.use_max_completion_tokens { None } else { Some(16) },
+            max_completion_tokens: if config.use_max_completion_tokens { Some(16) } else { None },
             tools: None,
             tool_choice: None,
         };
+        authorize(client.post(&config.endpoint), &config, &api_key).json(&body)
+    };
 
-        let response = client
-            .post(&config.endpoint)
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
+    let response = request
+        .timeout(Duration::from_secs(15))
+        .send()
+        .await
+        .map_err(|e| {
+            if e.is_timeout() {
+                "Connection timed out".to_string()
+            } else if e.is_connect() {
+                format!("Could not connect to {}", config.endpoint)
+            } else {
+                e.to_string()
+            }
+        })?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(format!("API error: {}", error_text));
-        }
+    let status = response.status();
+    if !status.is_success() {
+        let detail = response.text().await.unwrap_or_default();
+        return Err(match status.as_u16() {
+            401 => "Invalid API key".to_string(),
+            403 => format!("Access denied: {}", detail),
+            404 => format!("Endpoint or model not found ({})", config.endpoint),
+            _ => format!("API error ({}): {}", status, detail),
+        });
+    }
 
-        let ai_response: AiResponse = response.json().await.map_err(|e| e.to_string())?;
+    let body: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+    Ok(body["model"]
+        .as_str()
+        .map(String::from)
+        .unwrap_or(config.commit_model))
+}
 
-        ai_response.choices.first()
-            .and_then(|c| c.message.content.clone())
-            .ok_or_else(|| "No response from AI".to_string())?
-    };
+#[derive(Debug, Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelEntry>,
+}
 
-    // Strip markdown code fences if present (e.g., ```json ... ```)
-    let json_content = content
-        .trim()
-        .strip_prefix("```json")
-        .or_else(|| content.trim().strip_prefix("```"))
-        .unwrap_or(content.trim())
-        .trim()
-        .strip_suffix("```")
-        .unwrap_or(content.trim())
-        .trim();
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
+}
 
-    // Parse the JSON response
-    let suggestion: CommitSuggestion = serde_json::from_str(json_content)
-        .map_err(|e| format!("Failed to parse AI response: {} - Content: {}", e, json_content))?;
-    Ok(suggestion)
+const MODELS_CACHE_TTL: Duration = Duration::from_secs(300);
+
+// (provider, endpoint, api_key) -> (fetched at, model ids)
+type ModelsCache = HashMap<(String, String, String), (std::time::Instant, Vec<String>)>;
+
+fn models_cache() -> &'static Mutex<ModelsCache> {
+    static CACHE: std::sync::OnceLock<Mutex<ModelsCache>> = std::sync::OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
+/// Fallback for endpoints that don't implement a models listing
+fn curated_models(provider: &str) -> Vec<String> {
+    let models: &[&str] = match provider {
+        "openai" => &["gpt-5.2-2025-12-11", "gpt-5-mini-2025-08-07", "gpt-4.1", "gpt-4.1-mini"],
+        "claude" => &["claude-sonnet-4-5-20250929", "claude-opus-4-1-20250805", "claude-3-5-haiku-20241022"],
+        "ollama" => &["llama3.1", "llama3.2", "qwen2.5-coder", "mistral"],
+        // Azure models are the user's own deployment names; custom gateways vary
+        "azure" | "custom" => &[],
+        _ => &["llama-3.3-70b-versatile", "llama-3.1-8b-instant", "openai/gpt-oss-120b"],
+    };
+    models.iter().map(|m| m.to_string()).collect()
+}
+
+/// List the model ids available to this API key, for the settings model picker
 #[tauri::command]
-fn test_ai_connection(
-    _provider: String,
-    _api_key: String,
-    _model: String,
-    _endpoint: Option<String>,
-) -> Result<(), String> {
-    Ok(())
+async fn list_models(
+    provider: String,
+    api_key: String,
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+) -> Result<Vec<String>, String> {
+    let mut prov_config = get_provider_config(&provider);
+    apply_endpoint_override(&mut prov_config, &provider, endpoint, auth_style)?;
+    let api_key = resolve_api_key(api_key, &provider);
+    let chat_endpoint = prov_config.endpoint.clone();
+
+    // Both the OpenAI-style and Anthropic APIs serve models next to the chat endpoint
+    let models_endpoint = if let Some(base) = chat_endpoint.strip_suffix("/chat/completions") {
+        format!("{}/models", base)
+    } else if let Some(base) = chat_endpoint.strip_suffix("/messages") {
+        format!("{}/models", base)
+    } else {
+        return Ok(curated_models(&provider));
+    };
+
+    let cache_key = (provider.clone(), models_endpoint.clone(), api_key.clone());
+    if let Some((fetched, models)) = models_cache().lock().get(&cache_key) {
+        if fetched.elapsed() < MODELS_CACHE_TTL {
+            return Ok(models.clone());
+        }
+    }
+
+    let client = http_client();
+    let request = if prov_config.is_claude {
+        client
+            .get(format!("{}?limit=1000", models_endpoint))
+            .header("x-api-key", &api_key)
+            .header("anthropic-version", "2023-06-01")
+    } else {
+        authorize(client.get(&models_endpoint), &prov_config, &api_key)
+    };
+
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(curated_models(&provider));
+    }
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("API error: {}", error_text));
+    }
+
+    let parsed: ModelsResponse = response.json().await.map_err(|e| e.to_string())?;
+    let mut models: Vec<String> = parsed.data.into_iter().map(|m| m.id).collect();
+    models.sort();
+
+    models_cache().lock().insert(cache_key, (std::time::Instant::now(), models.clone()));
+    Ok(models)
 }
 
 // Helper function to detect project context from filesystem
@@ -3528,25 +7170,150 @@ fn detect_project_context(path: &std::path::Path) -> ProjectContext {
 
 #[tauri::command]
 fn read_text_file(path: String) -> Result<String, String> {
-    std::fs::read_to_string(&path)
-        .map_err(|e| format!("Failed to read file: {}", e))
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    if !git::is_lfs_pointer(content.as_bytes()) {
+        return Ok(content);
+    }
+
+    // Checked out without git-lfs (or with smudging skipped). Fetching the object could mean
+    // a download, which doesn't belong in a file read; show a placeholder instead.
+    Err(match git::parse_lfs_pointer(content.as_bytes()) {
+        Some(p) => format!("LFS_POINTER: Stored in Git LFS ({}, {} bytes) and not available locally", p.oid, p.size),
+        None => "LFS_POINTER: Stored in Git LFS and not available locally".to_string(),
+    })
 }
 
 #[tauri::command]
 fn write_text_file(path: String, content: String) -> Result<(), String> {
-    std::fs::write(&path, &content)
+    // Keep CRLF files CRLF even though the editor hands us LF text
+    let content = match std::fs::read(&path) {
+        Ok(existing) if existing.contains(&b'\n') => {
+            let line_ending = detect_line_ending(&String::from_utf8_lossy(&existing));
+            normalize_line_endings(&content, line_ending)
+        }
+        _ => content,
+    };
+    atomic_write(std::path::Path::new(&path), content.as_bytes())
         .map_err(|e| format!("Failed to write file: {}", e))
 }
 
+/// `"\r\n"` if the text's first line break is CRLF, otherwise `"\n"`
+fn detect_line_ending(text: &str) -> &'static str {
+    match text.find('\n') {
+        Some(i) if i > 0 && text.as_bytes()[i - 1] == b'\r' => "\r\n",
+        _ => "\n",
+    }
+}
+
+fn normalize_line_endings(text: &str, line_ending: &str) -> String {
+    let lf = text.replace("\r\n", "\n");
+    if line_ending == "\n" {
+        lf
+    } else {
+        lf.replace('\n', line_ending)
+    }
+}
+
+/// Write via a temp file in the same directory and rename over the target, so a crash
+/// mid-write never leaves a truncated file. Keeps the original file's permissions.
+fn atomic_write(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    // Write through a symlink to its target; renaming over the link itself would replace
+    // it with a plain file
+    let resolved = std::fs::canonicalize(path).ok();
+    let path = resolved.as_deref().unwrap_or(path);
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(std::path::Path::new("."));
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp_path = dir.join(format!(".{}.{}.tmp", file_name, Uuid::new_v4()));
+
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            std::fs::set_permissions(&tmp_path, metadata.permissions())?;
+        }
+        std::fs::rename(&tmp_path, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+#[tauri::command]
+fn create_directory(path: String) -> Result<(), String> {
+    std::fs::create_dir_all(&path)
+        .map_err(|e| format!("Failed to create directory: {}", e))
+}
+
+// Project file commands for .orca files (Issue #6)
+/// Current `.orca` schema version. Bump alongside a step in `migrate_orca_file`.
+const ORCA_FILE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrcaFileVersion {
+    pub version: u32,
+    #[serde(rename = "currentVersion")]
+    pub current_version: u32,
+    pub supported: bool,
+    #[serde(rename = "needsMigration")]
+    pub needs_migration: bool,
+}
+
+/// Schema version of a parsed `.orca` file. Files written before versioning count as 1.
+fn orca_file_version(value: &serde_json::Value) -> Result<u32, String> {
+    match value.get("version") {
+        None | Some(serde_json::Value::Null) => Ok(1),
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("UNSUPPORTED_VERSION: Invalid version field: {}", v)),
+    }
+}
+
+/// Upgrade an older `.orca` document to the current schema, one version at a time
+fn migrate_orca_file(mut value: serde_json::Value) -> Result<serde_json::Value, String> {
+    let version = orca_file_version(&value)?;
+    if version == 0 || version > ORCA_FILE_VERSION {
+        return Err(format!(
+            "UNSUPPORTED_VERSION: This project file uses format version {}, but this version of Orca supports up to {}. Update Orca to open it.",
+            version, ORCA_FILE_VERSION
+        ));
+    }
+    // No migrations yet; future steps go here as `if version < N { ... }`
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("version".to_string(), ORCA_FILE_VERSION.into());
+    }
+    Ok(value)
+}
+
 #[tauri::command]
-fn create_directory(path: String) -> Result<(), String> {
-    std::fs::create_dir_all(&path)
-        .map_err(|e| format!("Failed to create directory: {}", e))
+fn get_orca_file_version(path: String) -> Result<OrcaFileVersion, String> {
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read project file: {}", e))?;
+    let value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse project file: {}", e))?;
+    let version = orca_file_version(&value)?;
+    Ok(OrcaFileVersion {
+        version,
+        current_version: ORCA_FILE_VERSION,
+        supported: (1..=ORCA_FILE_VERSION).contains(&version),
+        needs_migration: version < ORCA_FILE_VERSION,
+    })
 }
 
-// Project file commands for .orca files (Issue #6)
 #[tauri::command]
-fn save_project_file(path: String, data: ProjectFileData) -> Result<(), String> {
+fn save_project_file(path: String, mut data: ProjectFileData) -> Result<(), String> {
+    data.version = ORCA_FILE_VERSION;
     let json = serde_json::to_string_pretty(&data)
         .map_err(|e| format!("Failed to serialize project: {}", e))?;
     std::fs::write(&path, &json)
@@ -3557,10 +7324,167 @@ fn save_project_file(path: String, data: ProjectFileData) -> Result<(), String>
 fn load_project_file(path: String) -> Result<ProjectFileData, String> {
     let content = std::fs::read_to_string(&path)
         .map_err(|e| format!("Failed to read project file: {}", e))?;
-    serde_json::from_str(&content)
+    let value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse project file: {}", e))?;
+    serde_json::from_value(migrate_orca_file(value)?)
         .map_err(|e| format!("Failed to parse project file: {}", e))
 }
 
+/// Find the project whose primary path is `path`, or create one named after the folder,
+/// and mark it as just opened
+fn find_or_create_project(db: &Database, path: &str) -> Result<Project, String> {
+    let path = path.trim_end_matches(['/', '\\']);
+    let path = if path.is_empty() { "/" } else { path };
+    if !std::path::Path::new(path).is_dir() {
+        return Err(format!("Not a directory: {}", path));
+    }
+
+    let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    let project = match db.get_all_projects()?.into_iter().find(|p| p.path == path) {
+        Some(existing) => Project { last_opened: now, ..existing },
+        None => Project {
+            id: Uuid::new_v4().to_string(),
+            name: std::path::Path::new(path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string()),
+            path: path.to_string(),
+            last_opened: now,
+            folders: None,
+            default_shell: None,
+            startup_commands: Vec::new(),
+            env: HashMap::new(),
+            tags: Vec::new(),
+            pinned: false,
+        },
+    };
+    db.add_project(&project)?;
+    Ok(project)
+}
+
+#[tauri::command]
+fn open_project_by_path(path: String, state: tauri::State<Arc<AppState>>) -> Result<Project, String> {
+    find_or_create_project(&state.database, &path)
+}
+
+/// Folders and `.orca` files among command-line arguments, relative ones resolved against `cwd`
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+fn paths_from_args(args: impl IntoIterator<Item = String>, cwd: &std::path::Path) -> Vec<std::path::PathBuf> {
+    args.into_iter()
+        .filter(|arg| !arg.starts_with('-'))
+        .map(|arg| cwd.join(arg))
+        .filter(|path| path.is_dir() || path.extension().is_some_and(|ext| ext == "orca"))
+        .collect()
+}
+
+/// Open a folder (as a project) or a `.orca` file that the OS handed to Orca. Until the
+/// frontend calls `open_pending_paths` its listeners may not exist yet, so paths are queued.
+fn open_path_from_os(app_handle: &tauri::AppHandle, path: std::path::PathBuf) {
+    let state = app_handle.state::<Arc<AppState>>();
+    if let Some(pending) = state.pending_opens.lock().as_mut() {
+        pending.push(path);
+        return;
+    }
+    let Some(window) = app_handle.get_webview_window("main") else { return };
+
+    if path.is_dir() {
+        match find_or_create_project(&state.database, &path.to_string_lossy()) {
+            Ok(project) => {
+                let _ = window.show();
+                let _ = window.set_focus();
+                let _ = window.emit("open-project", &project);
+            }
+            Err(e) => log::warn!("Failed to open folder {:?}: {}", path, e),
+        }
+    } else if path.extension().is_some_and(|ext| ext == "orca") {
+        let _ = window.show();
+        let _ = window.set_focus();
+        let _ = window.emit("open-workspace-file", path.to_string_lossy());
+    }
+}
+
+/// Called by the frontend once its open-project listeners are registered. Opens anything
+/// the OS passed in before that; later paths are opened as they arrive.
+#[tauri::command]
+fn open_pending_paths(app_handle: tauri::AppHandle, state: tauri::State<Arc<AppState>>) {
+    let pending = state.pending_opens.lock().take().unwrap_or_default();
+    for path in pending {
+        open_path_from_os(&app_handle, path);
+    }
+}
+
+/// Write a stored project, folders and settings included, to a `.orca` file
+#[tauri::command]
+fn export_workspace(
+    project_id: String,
+    path: String,
+    state: tauri::State<Arc<AppState>>,
+) -> Result<(), String> {
+    let project = state
+        .database
+        .get_project(&project_id)?
+        .ok_or_else(|| format!("Project not found: {}", project_id))?;
+
+    let folders = project.folders.unwrap_or_else(|| {
+        vec![ProjectFolder {
+            id: Uuid::new_v4().to_string(),
+            name: project.name.clone(),
+            path: project.path.clone(),
+        }]
+    });
+    let data = ProjectFileData {
+        version: ORCA_FILE_VERSION,
+        name: project.name,
+        folders,
+        default_shell: project.default_shell,
+        startup_commands: project.startup_commands,
+        env: project.env,
+    };
+    save_project_file(path, data)
+}
+
+/// Load a `.orca` file into the projects table, updating the project that already
+/// owns its primary folder if there is one
+#[tauri::command]
+fn import_workspace(path: String, state: tauri::State<Arc<AppState>>) -> Result<Project, String> {
+    let data = load_project_file(path)?;
+    let primary_path = data
+        .folders
+        .first()
+        .map(|f| f.path.clone())
+        .filter(|p| !p.is_empty())
+        .ok_or("Workspace file has no folders")?;
+
+    let db = &state.database;
+    let existing_id = db
+        .get_all_projects()?
+        .into_iter()
+        .find(|p| p.path == primary_path)
+        .map(|p| p.id);
+
+    let project = Project {
+        id: existing_id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+        name: data.name,
+        path: primary_path,
+        last_opened: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        folders: Some(data.folders),
+        default_shell: data.default_shell,
+        startup_commands: data.startup_commands,
+        env: data.env,
+        tags: Vec::new(),
+        pinned: false,
+    };
+    db.add_project(&project)?;
+    // add_project leaves an existing project's shell settings alone; the file's should win
+    db.set_project_shell_settings(
+        &project.id,
+        project.default_shell.as_deref(),
+        &project.startup_commands,
+        &project.env,
+    )?;
+    Ok(project)
+}
+
 #[tauri::command]
 fn scan_project_context(cwd: String, _force_refresh: Option<bool>) -> Result<ProjectContext, String> {
     use std::path::Path;
@@ -3584,6 +7508,22 @@ fn resolve_and_validate_path(cwd: &str, rel_path: &str) -> Result<PathBuf, Strin
     Ok(resolved)
 }
 
+/// Like `resolve_and_validate_path`, but the file itself may not exist yet.
+/// Its parent directory must, and must be inside the project.
+fn resolve_write_path(cwd: &str, rel_path: &str) -> Result<PathBuf, String> {
+    let rel = std::path::Path::new(rel_path);
+    if rel_path.is_empty() || rel.is_absolute() || rel.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+        return Err(format!("Access denied: '{}' must be a relative path inside the project", rel_path));
+    }
+    if PathBuf::from(cwd).join(rel).exists() {
+        return resolve_and_validate_path(cwd, rel_path);
+    }
+    let file_name = rel.file_name().ok_or_else(|| format!("Invalid file path: {}", rel_path))?;
+    let parent = rel.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+    let parent = resolve_and_validate_path(cwd, if parent.is_empty() { "." } else { &parent })?;
+    Ok(parent.join(file_name))
+}
+
 /// Flat directory listing suitable for LLM consumption.
 fn list_directory_flat(path: &std::path::Path, max_depth: usize) -> Result<String, String> {
     use std::fs;
@@ -3623,8 +7563,9 @@ fn list_directory_flat(path: &std::path::Path, max_depth: usize) -> Result<Strin
     Ok(lines.join("\n"))
 }
 
-/// Execute a tool call and return the result as a string.
-fn execute_tool_call(tool_name: &str, arguments_json: &str, cwd: &str) -> String {
+/// Execute a tool call and return the result as a string. `write_file` only does
+/// anything when the user opted in with `allow_writes`.
+fn execute_tool_call(tool_name: &str, arguments_json: &str, cwd: &str, allow_writes: bool) -> String {
     let args: serde_json::Value = match serde_json::from_str(arguments_json) {
         Ok(v) => v,
         Err(e) => return format!("Error parsing arguments: {}", e),
@@ -3656,7 +7597,7 @@ fn execute_tool_call(tool_name: &str, arguments_json: &str, cwd: &str) -> String
                 Ok(p) => p.to_string_lossy().to_string(),
                 Err(e) => return e,
             };
-            match search_file_contents_sync(search_root, query.to_string(), false, Some(50)) {
+            match search_file_contents_sync(search_root, query.to_string(), false, Some(50), false, false, false) {
                 Ok(result) => {
                     if result.matches.is_empty() {
                         "No matches found.".to_string()
@@ -3708,6 +7649,22 @@ fn execute_tool_call(tool_name: &str, arguments_json: &str, cwd: &str) -> String
                 Err(e) => format!("Not a git repository or error: {}", e),
             }
         }
+        "write_file" => {
+            if !allow_writes {
+                return "Error: writes not permitted. File writes are disabled for this request; suggest a command instead.".to_string();
+            }
+            let rel_path = args["path"].as_str().unwrap_or("");
+            let Some(content) = args["content"].as_str() else {
+                return "Error: missing 'content'".to_string();
+            };
+            match resolve_write_path(cwd, rel_path) {
+                Ok(abs) => match atomic_write(&abs, content.as_bytes()) {
+                    Ok(()) => format!("Wrote {} bytes to {}", content.len(), rel_path),
+                    Err(e) => format!("Error writing file: {}", e),
+                },
+                Err(e) => e,
+            }
+        }
         _ => format!("Unknown tool: {}", tool_name),
     }
 }
@@ -3774,6 +7731,27 @@ fn build_nlt_tools() -> Vec<Tool> {
                 }),
             },
         },
+        Tool {
+            tool_type: "function".into(),
+            function: ToolFunction {
+                name: "write_file".into(),
+                description: "Create or overwrite a file with the given content. Only works when the user has allowed file writes; otherwise it returns an error and you should suggest a command instead.".into(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "Relative path to the file from the project root"
+                        },
+                        "content": {
+                            "type": "string",
+                            "description": "The full new contents of the file"
+                        }
+                    },
+                    "required": ["path", "content"]
+                }),
+            },
+        },
         Tool {
             tool_type: "function".into(),
             function: ToolFunction {
@@ -3842,6 +7820,7 @@ fn extract_json_object(text: &str) -> Option<NltResponse> {
                     return Some(NltResponse {
                         command: cmd.to_string(),
                         explanation: val.get("explanation").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                        usage: None,
                     });
                 }
             }
@@ -3873,6 +7852,7 @@ fn parse_final_response(content: &str) -> NltResponse {
             return NltResponse {
                 command: cmd.to_string(),
                 explanation: val.get("explanation").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                usage: None,
             };
         }
     }
@@ -3889,9 +7869,152 @@ fn parse_final_response(content: &str) -> NltResponse {
         .trim_matches('\'')
         .trim()
         .to_string();
-    NltResponse { command, explanation: None }
+    NltResponse { command, explanation: None, usage: None }
+}
+
+/// Unregisters an in-flight AI request when the command returns, however it returns
+struct AiRequestGuard {
+    state: Arc<AppState>,
+    request_id: String,
+}
+
+impl Drop for AiRequestGuard {
+    fn drop(&mut self) {
+        self.state.ai_requests.lock().remove(&self.request_id);
+    }
+}
+
+/// Send an HTTP request, dropping it (and the connection) as soon as `cancel` fires
+async fn send_cancellable(
+    request: reqwest::RequestBuilder,
+    cancel: &CancellationToken,
+) -> Result<reqwest::Response, String> {
+    tokio::select! {
+        result = request.send() => result.map_err(|e| e.to_string()),
+        _ = cancel.cancelled() => Err("Request cancelled".to_string()),
+    }
+}
+
+const AI_MAX_RETRIES: u32 = 3;
+
+/// Send an AI API request, retrying rate limits (429) and server errors (5xx) up to
+/// three times with exponential backoff, or however long `Retry-After` asks (capped).
+/// Any other status comes straight back so the caller fails fast on 400/401 and friends.
+async fn send_with_retry(
+    request: reqwest::RequestBuilder,
+    cancel: Option<&CancellationToken>,
+    mut on_retry: impl FnMut(u32, Duration),
+) -> Result<reqwest::Response, String> {
+    let mut attempt = 0;
+    loop {
+        let this_try = request.try_clone().ok_or("Request can't be retried")?;
+        let response = match cancel {
+            Some(token) => send_cancellable(this_try, token).await?,
+            None => this_try.send().await.map_err(|e| e.to_string())?,
+        };
+
+        let status = response.status();
+        let retryable = status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+        if !retryable || attempt >= AI_MAX_RETRIES {
+            return Ok(response);
+        }
+        attempt += 1;
+        // 1s, 2s, 4s unless the server says otherwise
+        let delay = retry_after(&response).unwrap_or_else(|| Duration::from_millis(500 << attempt));
+        log::warn!("AI request got {}, retrying in {:?} (attempt {}/{})", status, delay, attempt, AI_MAX_RETRIES);
+        on_retry(attempt, delay);
+
+        match cancel {
+            Some(token) => tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = token.cancelled() => return Err("Request cancelled".to_string()),
+            },
+            None => tokio::time::sleep(delay).await,
+        }
+    }
+}
+
+/// `Retry-After` in its delay-seconds form, capped at 30s. AI APIs don't send HTTP dates.
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    let secs: f64 = value.trim().parse().ok()?;
+    Some(Duration::from_secs_f64(secs.clamp(0.0, 30.0)))
+}
+
+fn emit_nlt_retrying(app_handle: &tauri::AppHandle, request_id: &str, iteration: usize, attempt: u32, delay: Duration) {
+    let _ = app_handle.emit("nlt-progress", NltProgressEvent {
+        request_id: request_id.to_string(),
+        status: "retrying".into(),
+        message: format!(
+            "Provider busy, retrying in {}s (attempt {}/{})...",
+            delay.as_secs_f32().ceil(),
+            attempt,
+            AI_MAX_RETRIES
+        ),
+        tool_name: None,
+        iteration,
+        result: None,
+    });
+}
+
+const TOOL_PREVIEW_LINES: usize = 20;
+const TOOL_PREVIEW_CHARS: usize = 2000;
+
+/// Short preview of a tool result for the progress stream
+fn tool_result_preview(result: &str) -> String {
+    let mut preview: String = result.lines().take(TOOL_PREVIEW_LINES).collect::<Vec<_>>().join("\n");
+    if preview.chars().count() > TOOL_PREVIEW_CHARS {
+        preview = preview.chars().take(TOOL_PREVIEW_CHARS).collect();
+    }
+    if preview.len() < result.trim_end().len() {
+        preview.push_str("\n...");
+    }
+    preview
+}
+
+fn emit_nlt_cancelled(app_handle: &tauri::AppHandle, request_id: &str, iteration: usize) {
+    let _ = app_handle.emit("nlt-progress", NltProgressEvent {
+        request_id: request_id.to_string(),
+        status: "cancelled".into(),
+        message: "Request cancelled".into(),
+        tool_name: None,
+        iteration,
+        result: None,
+    });
+}
+
+/// Stop an in-flight `ai_shell_command`. The tool loop stops at its next check and
+/// emits a "cancelled" nlt-progress event. Unknown ids have already finished.
+#[tauri::command]
+fn cancel_ai_shell_command(request_id: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    if let Some(token) = state.ai_requests.lock().get(&request_id) {
+        token.cancel();
+    }
+    Ok(())
+}
+
+/// Older name for `cancel_ai_shell_command`
+#[tauri::command]
+fn cancel_ai_request(request_id: String, state: tauri::State<Arc<AppState>>) -> Result<(), String> {
+    cancel_ai_shell_command(request_id, state)
+}
+
+// A previous request/answer pair in a multi-turn AI shell session
+#[derive(Debug, Clone)]
+struct AiShellTurn {
+    request: String,
+    response: NltResponse,
 }
 
+struct AiShellSession {
+    turns: Vec<AiShellTurn>,
+    last_used: std::time::Instant,
+}
+
+const MAX_AI_SHELL_SESSIONS: usize = 20;
+const MAX_AI_SHELL_TURNS: usize = 10;
+const AI_SHELL_SESSION_TTL: Duration = Duration::from_secs(30 * 60);
+
 #[tauri::command]
 async fn ai_shell_command(
     request: String,
@@ -3900,15 +8023,153 @@ async fn ai_shell_command(
     api_key: String,
     provider: Option<String>,
     model: Option<String>,
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+    allow_writes: Option<bool>,
     request_id: String,
+    template: Option<String>,
+    selection: Option<String>,
     app_handle: tauri::AppHandle,
+    state: tauri::State<'_, Arc<AppState>>,
 ) -> Result<NltResponse, String> {
-    if api_key.is_empty() {
-        return Err("No API key provided. Set your API key in Settings.".to_string());
+    let request = expand_prompt_template(&state, template.as_deref(), &request, &cwd, selection.as_deref())?;
+    run_ai_shell(
+        request, context, cwd, api_key, provider, model, endpoint, auth_style, allow_writes.unwrap_or(false), request_id, Vec::new(),
+        app_handle, state.inner().clone(),
+    ).await
+}
+
+/// Start a multi-turn AI shell session. Follow-ups sent with `ai_shell_continue` see earlier turns.
+#[tauri::command]
+fn ai_shell_start(state: tauri::State<Arc<AppState>>) -> String {
+    let mut sessions = state.ai_shell_sessions.lock();
+    sessions.retain(|_, s| s.last_used.elapsed() < AI_SHELL_SESSION_TTL);
+
+    // Evict the least recently used session when at capacity
+    if sessions.len() >= MAX_AI_SHELL_SESSIONS {
+        if let Some(oldest) = sessions.iter().min_by_key(|(_, s)| s.last_used).map(|(id, _)| id.clone()) {
+            sessions.remove(&oldest);
+        }
+    }
+
+    let id = Uuid::new_v4().to_string();
+    sessions.insert(id.clone(), AiShellSession {
+        turns: Vec::new(),
+        last_used: std::time::Instant::now(),
+    });
+    id
+}
+
+#[tauri::command]
+async fn ai_shell_continue(
+    session_id: String,
+    request: String,
+    context: ProjectContext,
+    cwd: String,
+    api_key: String,
+    provider: Option<String>,
+    model: Option<String>,
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+    allow_writes: Option<bool>,
+    request_id: String,
+    template: Option<String>,
+    selection: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<NltResponse, String> {
+    let request = expand_prompt_template(&state, template.as_deref(), &request, &cwd, selection.as_deref())?;
+    let history = {
+        let mut sessions = state.ai_shell_sessions.lock();
+        match sessions.get_mut(&session_id) {
+            Some(session) if session.last_used.elapsed() < AI_SHELL_SESSION_TTL => {
+                session.last_used = std::time::Instant::now();
+                session.turns.clone()
+            }
+            Some(_) => {
+                sessions.remove(&session_id);
+                return Err("AI shell session expired".to_string());
+            }
+            None => return Err("AI shell session not found".to_string()),
+        }
+    };
+
+    let response = run_ai_shell(
+        request.clone(), context, cwd, api_key, provider, model, endpoint, auth_style, allow_writes.unwrap_or(false), request_id,
+        history,
+        app_handle, state.inner().clone(),
+    ).await?;
+
+    if let Some(session) = state.ai_shell_sessions.lock().get_mut(&session_id) {
+        // Usage is bookkeeping for the UI, not something the model should see in history
+        session.turns.push(AiShellTurn { request, response: NltResponse { usage: None, ..response.clone() } });
+        if session.turns.len() > MAX_AI_SHELL_TURNS {
+            session.turns.remove(0);
+        }
+    }
+    Ok(response)
+}
+
+#[tauri::command]
+fn ai_shell_end(session_id: String, state: tauri::State<Arc<AppState>>) {
+    state.ai_shell_sessions.lock().remove(&session_id);
+}
+
+/// Run the AI shell tool loop, then report the tokens it used (even when it failed or was
+/// cancelled, since they were still spent) via an `nlt-usage` event and the usage table
+async fn run_ai_shell(
+    request: String,
+    context: ProjectContext,
+    cwd: String,
+    api_key: String,
+    provider: Option<String>,
+    model: Option<String>,
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+    allow_writes: bool,
+    request_id: String,
+    history: Vec<AiShellTurn>,
+    app_handle: tauri::AppHandle,
+    state: Arc<AppState>,
+) -> Result<NltResponse, String> {
+    let provider_name = provider.clone().unwrap_or_else(|| "groq".to_string());
+    let mut usage = None;
+    let result = run_ai_shell_loop(
+        request, context, cwd, api_key, provider, model, endpoint, auth_style, allow_writes, request_id.clone(), history,
+        app_handle.clone(), state.clone(), &mut usage,
+    ).await;
+
+    if let Some(usage) = usage {
+        let _ = app_handle.emit("nlt-usage", NltUsageEvent {
+            request_id,
+            provider: provider_name.clone(),
+            usage,
+        });
     }
+    record_ai_usage(&state, &provider_name, usage);
+
+    result.map(|response| NltResponse { usage, ..response })
+}
 
+async fn run_ai_shell_loop(
+    request: String,
+    context: ProjectContext,
+    cwd: String,
+    api_key: String,
+    provider: Option<String>,
+    model: Option<String>,
+    endpoint: Option<String>,
+    auth_style: Option<String>,
+    allow_writes: bool,
+    request_id: String,
+    history: Vec<AiShellTurn>,
+    app_handle: tauri::AppHandle,
+    state: Arc<AppState>,
+    usage: &mut Option<TokenUsage>,
+) -> Result<NltResponse, String> {
     let provider_str = provider.as_deref().unwrap_or("groq");
     let mut prov_config = get_provider_config(provider_str);
+    apply_endpoint_override(&mut prov_config, provider_str, endpoint, auth_style)?;
     if let Some(m) = model {
         if !m.is_empty() {
             prov_config.commit_model = m.clone();
@@ -3916,6 +8177,18 @@ async fn ai_shell_command(
         }
     }
 
+    let api_key = resolve_api_key(api_key, provider_str);
+    if api_key.is_empty() && prov_config.requires_api_key {
+        return Err("No API key provided. Set your API key in Settings.".to_string());
+    }
+
+    let cancel_token = CancellationToken::new();
+    state.ai_requests.lock().insert(request_id.clone(), cancel_token.clone());
+    let _request_guard = AiRequestGuard {
+        state: state.clone(),
+        request_id: request_id.clone(),
+    };
+
     // Detect the user's default shell
     let default_shell = std::env::var("SHELL").unwrap_or_else(|_| {
         #[cfg(target_os = "macos")]
@@ -3949,6 +8222,7 @@ async fn ai_shell_command(
         message: "Analyzing your request...".into(),
         tool_name: None,
         iteration: 0,
+        result: None,
     });
 
     if prov_config.is_claude {
@@ -3959,11 +8233,26 @@ async fn ai_shell_command(
             input_schema: t.function.parameters,
         }).collect();
 
-        let mut claude_messages: Vec<ClaudeMessage> = vec![
-            ClaudeMessage { role: "user".into(), content: ClaudeContent::Text(user_msg.clone()) },
-        ];
+        // Earlier session turns come first so follow-ups keep their context
+        let mut claude_messages: Vec<ClaudeMessage> = Vec::new();
+        for turn in &history {
+            claude_messages.push(ClaudeMessage {
+                role: "user".into(),
+                content: ClaudeContent::Text(format!("User request: {}", turn.request)),
+            });
+            claude_messages.push(ClaudeMessage {
+                role: "assistant".into(),
+                content: ClaudeContent::Text(serde_json::to_string(&turn.response).unwrap_or_default()),
+            });
+        }
+        claude_messages.push(ClaudeMessage { role: "user".into(), content: ClaudeContent::Text(user_msg.clone()) });
 
         for iteration in 0..max_iterations {
+            if cancel_token.is_cancelled() {
+                emit_nlt_cancelled(&app_handle, &request_id, iteration);
+                return Err("Request cancelled".to_string());
+            }
+
             if started.elapsed() > timeout {
                 let _ = app_handle.emit("nlt-progress", NltProgressEvent {
                     request_id: request_id.clone(),
@@ -3971,6 +8260,7 @@ async fn ai_shell_command(
                     message: "Request timed out after 30 seconds".into(),
                     tool_name: None,
                     iteration,
+                    result: None,
                 });
                 return Err("Request timed out after 30 seconds".to_string());
             }
@@ -3984,15 +8274,22 @@ async fn ai_shell_command(
                 tools: Some(claude_tools.clone()),
             };
 
-            let response = client
+            let request = client
                 .post(&prov_config.endpoint)
                 .header("x-api-key", &api_key)
                 .header("anthropic-version", "2023-06-01")
                 .header("Content-Type", "application/json")
-                .json(&claude_request)
-                .send()
-                .await
-                .map_err(|e| e.to_string())?;
+                .json(&claude_request);
+            let on_retry = |attempt, delay| emit_nlt_retrying(&app_handle, &request_id, iteration, attempt, delay);
+            let response = match send_with_retry(request, Some(&cancel_token), on_retry).await {
+                Ok(r) => r,
+                Err(e) => {
+                    if cancel_token.is_cancelled() {
+                        emit_nlt_cancelled(&app_handle, &request_id, iteration);
+                    }
+                    return Err(e);
+                }
+            };
 
             if !response.status().is_success() {
                 let error_text = response.text().await.unwrap_or_default();
@@ -4000,6 +8297,7 @@ async fn ai_shell_command(
             }
 
             let claude_response: ClaudeResponse = response.json().await.map_err(|e| e.to_string())?;
+            *usage = TokenUsage::combine(*usage, claude_response.usage);
             let stop_reason = claude_response.stop_reason.as_deref().unwrap_or("end_turn");
 
             // Check if we have tool_use blocks
@@ -4035,10 +8333,21 @@ async fn ai_shell_command(
                             message: format!("Calling {}...", name),
                             tool_name: Some(name.clone()),
                             iteration: iteration + 1,
+                            result: None,
                         });
 
                         let args_str = serde_json::to_string(input).unwrap_or_default();
-                        let result = execute_tool_call(name, &args_str, &cwd);
+                        let result = execute_tool_call(name, &args_str, &cwd, allow_writes);
+
+                        let _ = app_handle.emit("nlt-progress", NltProgressEvent {
+                            request_id: request_id.clone(),
+                            status: "tool_result".into(),
+                            message: format!("{} finished", name),
+                            tool_name: Some(name.clone()),
+                            iteration: iteration + 1,
+                            result: Some(tool_result_preview(&result)),
+                        });
+
                         let result = if result.len() > 30_000 {
                             format!("{}\n... (output truncated)", &result[..30_000])
                         } else {
@@ -4073,6 +8382,7 @@ async fn ai_shell_command(
                 message: "Command ready".into(),
                 tool_name: None,
                 iteration: iteration + 1,
+                result: None,
             });
 
             println!("[NLT] Final response: {:?}", nlt_response);
@@ -4085,19 +8395,28 @@ async fn ai_shell_command(
             message: "Too many tool-calling iterations".into(),
             tool_name: None,
             iteration: max_iterations,
+            result: None,
         });
         Err("AI used too many tool calls without producing a final answer".to_string())
     } else {
         // --- OpenAI-compatible path (Groq, OpenAI) ---
-        let mut messages = vec![
-            AiMessage::system(&system_prompt),
-            AiMessage::user(&user_msg),
-        ];
+        let mut messages = vec![AiMessage::system(&system_prompt)];
+        for turn in &history {
+            messages.push(AiMessage::user(&format!("User request: {}", turn.request)));
+            messages.push(AiMessage::assistant(&serde_json::to_string(&turn.response).unwrap_or_default()));
+        }
+        messages.push(AiMessage::user(&user_msg));
+        let base_message_count = messages.len();
 
         let tools = build_nlt_tools();
         let mut use_tools = true;
 
         for iteration in 0..max_iterations {
+            if cancel_token.is_cancelled() {
+                emit_nlt_cancelled(&app_handle, &request_id, iteration);
+                return Err("Request cancelled".to_string());
+            }
+
             if started.elapsed() > timeout {
                 let _ = app_handle.emit("nlt-progress", NltProgressEvent {
                     request_id: request_id.clone(),
@@ -4105,6 +8424,7 @@ async fn ai_shell_command(
                     message: "Request timed out after 30 seconds".into(),
                     tool_name: None,
                     iteration,
+                    result: None,
                 });
                 return Err("Request timed out after 30 seconds".to_string());
             }
@@ -4119,20 +8439,30 @@ async fn ai_shell_command(
                 tool_choice: None,
             };
 
-            let response = client
-                .post(&prov_config.endpoint)
-                .header("Authorization", format!("Bearer {}", api_key))
+            let request = authorize(client.post(&prov_config.endpoint), &prov_config, &api_key)
                 .header("Content-Type", "application/json")
-                .json(&ai_request)
-                .send()
-                .await
-                .map_err(|e| e.to_string())?;
+                .json(&ai_request);
+            let on_retry = |attempt, delay| emit_nlt_retrying(&app_handle, &request_id, iteration, attempt, delay);
+            let response = match send_with_retry(request, Some(&cancel_token), on_retry).await {
+                Ok(r) => r,
+                Err(e) => {
+                    if cancel_token.is_cancelled() {
+                        emit_nlt_cancelled(&app_handle, &request_id, iteration);
+                    }
+                    return Err(e);
+                }
+            };
 
             if !response.status().is_success() {
                 let error_text = response.text().await.unwrap_or_default();
 
                 // If tool calling failed, retry without tools
-                if use_tools && (error_text.contains("tool_use_failed") || error_text.contains("tool call validation")) {
+                // (many local Ollama models reject tools outright with "does not support tools")
+                if use_tools
+                    && (error_text.contains("tool_use_failed")
+                        || error_text.contains("tool call validation")
+                        || error_text.contains("does not support tools"))
+                {
                     println!("[NLT] Tool call validation failed, retrying without tools");
                     let _ = app_handle.emit("nlt-progress", NltProgressEvent {
                         request_id: request_id.clone(),
@@ -4140,9 +8470,10 @@ async fn ai_shell_command(
                         message: "Retrying without tools...".into(),
                         tool_name: None,
                         iteration: iteration + 1,
+                        result: None,
                     });
                     use_tools = false;
-                    messages.truncate(2);
+                    messages.truncate(base_message_count);
                     continue;
                 }
 
@@ -4150,6 +8481,7 @@ async fn ai_shell_command(
             }
 
             let ai_response: AiResponse = response.json().await.map_err(|e| e.to_string())?;
+            *usage = TokenUsage::combine(*usage, ai_response.usage);
             let choice = ai_response.choices.into_iter().next()
                 .ok_or("No response from AI")?;
 
@@ -4169,9 +8501,20 @@ async fn ai_shell_command(
                             message: format!("Calling {}...", tool_name),
                             tool_name: Some(tool_name.clone()),
                             iteration: iteration + 1,
+                            result: None,
+                        });
+
+                        let result = execute_tool_call(tool_name, &tc.function.arguments, &cwd, allow_writes);
+
+                        let _ = app_handle.emit("nlt-progress", NltProgressEvent {
+                            request_id: request_id.clone(),
+                            status: "tool_result".into(),
+                            message: format!("{} finished", tool_name),
+                            tool_name: Some(tool_name.clone()),
+                            iteration: iteration + 1,
+                            result: Some(tool_result_preview(&result)),
                         });
 
-                        let result = execute_tool_call(tool_name, &tc.function.arguments, &cwd);
                         let result = if result.len() > 30_000 {
                             format!("{}\n... (output truncated)", &result[..30_000])
                         } else {
@@ -4194,6 +8537,7 @@ async fn ai_shell_command(
                 message: "Command ready".into(),
                 tool_name: None,
                 iteration: iteration + 1,
+                result: None,
             });
 
             println!("[NLT] Final response: {:?}", nlt_response);
@@ -4206,6 +8550,7 @@ async fn ai_shell_command(
             message: "Too many tool-calling iterations".into(),
             tool_name: None,
             iteration: max_iterations,
+            result: None,
         });
         Err("AI used too many tool calls without producing a final answer".to_string())
     }
@@ -4213,55 +8558,132 @@ async fn ai_shell_command(
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    env_logger::init();
+    logging::init();
 
     let data_dir = dirs::data_dir()
         .unwrap_or_else(|| PathBuf::from("."))
         .join("orca");
     std::fs::create_dir_all(&data_dir).ok();
 
-    let db = match Database::new(data_dir.join("orca.db")) {
+    let db_path = data_dir.join("orca.db");
+    match Database::apply_pending_restore(&db_path) {
+        Ok(true) => log::info!("Restored database from backup"),
+        Ok(false) => {}
+        Err(e) => log::error!("Failed to apply pending database restore: {}", e),
+    }
+
+    let db = match Database::new(db_path) {
         Ok(db) => db,
         Err(e) => {
-            eprintln!("Failed to initialize database: {}", e);
+            log::error!("Failed to initialize database: {}", e);
+            log::logger().flush();
             std::process::exit(1);
         }
     };
 
+    // "Open with Orca" on Windows and Linux starts the app with the folder or .orca file as
+    // an argument; macOS delivers those through RunEvent::Opened instead
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    let launch_paths = std::env::current_dir()
+        .map(|cwd| paths_from_args(std::env::args().skip(1), &cwd))
+        .unwrap_or_default();
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    let launch_paths = Vec::new();
+
     // Load portal config from database
     let portal_config = db.get_portal_config().unwrap_or_default();
     let portal_was_enabled = portal_config.is_enabled;
 
     let state = Arc::new(AppState {
         terminals: Mutex::new(HashMap::new()),
-        database: Mutex::new(db),
+        database: db,
         portal_enabled: Mutex::new(portal_was_enabled),
-        git_watchers: Mutex::new(HashMap::new()),
-        file_watchers: Mutex::new(HashMap::new()),
+        project_watchers: Mutex::new(HashMap::new()),
+        single_file_watchers: Mutex::new(HashMap::new()),
         portal: Mutex::new(None),
+        db_watcher_running: std::sync::atomic::AtomicBool::new(false),
+        ai_requests: Mutex::new(HashMap::new()),
+        ai_shell_sessions: Mutex::new(HashMap::new()),
+        fs_batch_depth: std::sync::atomic::AtomicUsize::new(0),
+        global_shortcuts: Mutex::new(HashMap::new()),
+        recent_exits: Mutex::new(HashMap::new()),
+        active_project: Mutex::new(None),
+        pending_opens: Mutex::new(Some(launch_paths)),
     });
     let state_for_window_event = state.clone();
+    let state_for_shortcuts = state.clone();
     let state_for_portal = state.clone();
 
-    tauri::Builder::default()
+    let builder = tauri::Builder::default();
+    // A second launch (e.g. another "Open with Orca") hands its arguments to this instance
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+        let paths = paths_from_args(argv.into_iter().skip(1), std::path::Path::new(&cwd));
+        if paths.is_empty() {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+        }
+        for path in paths {
+            open_path_from_os(app, path);
+        }
+    }));
+
+    builder
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_process::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(move |app, shortcut, event| {
+                    if event.state() != tauri_plugin_global_shortcut::ShortcutState::Pressed {
+                        return;
+                    }
+                    let action = state_for_shortcuts.global_shortcuts.lock().get(&shortcut.id()).cloned();
+                    if let Some(action) = action {
+                        run_global_shortcut_action(app, &action);
+                    }
+                })
+                .build(),
+        )
         .manage(state)
         .invoke_handler(tauri::generate_handler![
             // Debug
             debug_log,
+            get_app_logs,
+            set_profiling,
+            get_command_timings,
+            register_global_shortcut,
+            unregister_global_shortcut,
+            get_global_shortcuts,
             get_home_dir,
             request_microphone_permission,
             // Terminal
             spawn_terminal,
+            resolve_project_environment,
+            get_setting,
+            set_setting,
+            get_all_settings,
+            get_terminal_profiles,
+            save_terminal_profile,
+            delete_terminal_profile,
+            spawn_terminal_from_profile,
             write_terminal,
             write_terminal_bytes,
+            write_terminals,
+            set_terminal_title,
+            start_terminal_recording,
+            stop_terminal_recording,
             resize_terminal,
             kill_terminal,
             kill_terminals,
+            signal_terminal,
             list_terminals,
+            list_recent_exits,
+            set_terminal_notify_on_exit,
             clear_terminals,
             get_terminal_buffer,
             // Git
@@ -4271,14 +8693,38 @@ pub fn run() {
             commit,
             flatten_nested_repo,
             add_as_submodule,
+            find_nested_repos,
+            list_submodules,
+            update_submodules,
             get_branches,
             checkout_branch,
             create_branch,
+            rename_branch,
+            delete_branch,
+            delete_remote_branch,
             get_history,
+            get_commit_graph,
+            get_head_info,
+            get_reflog,
+            get_contributor_activity,
             get_commit_diff,
+            diff_refs,
+            get_branch_changes,
             discard_file,
+            restore_file_from_ref,
+            check_large_files,
             add_to_gitignore,
             get_remote_url,
+            get_compare_url,
+            list_remotes,
+            add_remote,
+            remove_remote,
+            rename_remote,
+            set_remote_url,
+            get_git_config,
+            set_git_config,
+            get_git_aliases,
+            set_git_alias,
             discard_hunk,
             edit_file_line,
             checkout_commit,
@@ -4287,6 +8733,7 @@ pub fn run() {
             init_repo,
             clone_repo,
             fetch_remote,
+            prune_remote_branches,
             pull_remote,
             get_branch_tracking_remote,
             push_remote,
@@ -4303,10 +8750,12 @@ pub fn run() {
             stash_save,
             stash_list,
             stash_apply,
+            diff_stash_against_worktree,
             stash_pop,
             stash_drop,
             // Merge
             merge_branch,
+            preview_merge,
             abort_merge,
             continue_merge,
             // Conflicts
@@ -4314,12 +8763,16 @@ pub fn run() {
             get_conflict_content,
             resolve_conflict,
             resolve_conflict_with_side,
+            ai_resolve_conflict,
             // Undo
             undo_last_commit,
+            undo_last_commit_keep_message,
             // Rebase
             rebase_onto,
             rebase_continue,
             rebase_abort,
+            get_repo_state,
+            abort_current_operation,
             cherry_pick_commit,
             // Tags
             list_tags,
@@ -4327,10 +8780,17 @@ pub fn run() {
             delete_tag,
             push_tag,
             // Line staging
+            stage_file,
+            unstage_file,
+            stage_all,
+            unstage_all,
             stage_lines,
             // Image diff
             get_old_file_content,
             // GitHub
+            store_secret,
+            has_secret,
+            delete_secret,
             github_get_user,
             github_get_cli_token,
             github_list_pull_requests,
@@ -4342,38 +8802,83 @@ pub fn run() {
             add_project,
             remove_project,
             get_project,
+            open_project_by_path,
+            open_pending_paths,
             get_all_projects,
+            set_project_tags,
+            set_project_shell_settings,
+            set_project_pinned,
+            get_projects_by_tag,
             // File system
             open_folder_dialog,
             open_in_finder,
             reveal_in_file_manager,
             open_file_in_editor,
+            get_editors,
+            set_editors,
             open_in_terminal_editor,
             list_directories,
             get_shell_history,
             record_project_command,
             get_project_shell_history,
+            search_command_history,
+            get_combined_history,
+            report_error,
+            capture_window_screenshot,
+            get_error_reports,
+            clear_error_reports,
             get_file_tree,
+            get_directory_children,
             search_file_contents,
+            find_files,
             delete_file,
             rename_file,
+            delete_files,
+            move_files,
+            copy_path,
             save_clipboard_image,
             read_text_file,
             write_text_file,
             create_directory,
             watch_project_files,
             unwatch_project_files,
+            watch_file,
+            unwatch_file,
+            watch_database,
+            backup_database,
+            restore_database,
+            export_database,
+            import_database,
+            get_database_stats,
+            save_prompt_template,
+            get_prompt_templates,
+            delete_prompt_template,
+            vacuum_database,
             save_project_file,
             load_project_file,
+            get_orca_file_version,
+            export_workspace,
+            import_workspace,
             // Assistants
             check_installed_assistants,
             check_commands_installed,
             install_assistant,
             // AI
             generate_commit_message,
+            generate_commit_message_stream,
+            ai_review_diff,
+            ai_generate_pr_description,
+            validate_commit_message,
             test_ai_connection,
+            list_models,
             scan_project_context,
             ai_shell_command,
+            cancel_ai_request,
+            cancel_ai_shell_command,
+            get_ai_usage,
+            ai_shell_start,
+            ai_shell_continue,
+            ai_shell_end,
             // Portal
             set_portal_enabled,
             get_portal_config,
@@ -4383,9 +8888,25 @@ pub fn run() {
             portal_regenerate_pairing,
             portal_get_status,
             portal_send_message,
+            set_active_project,
+            get_active_project,
             portal_register_mobile_terminal,
         ])
         .setup(move |app| {
+            // Stream captured log entries to the debug panel
+            let log_handle = app.handle().clone();
+            logging::set_sink(move |entry| {
+                let _ = log_handle.emit("app-log", entry);
+            });
+
+            // Re-register global shortcuts saved in previous sessions
+            let shortcut_state = app.state::<Arc<AppState>>();
+            for saved in shortcut_state.database.get_global_shortcuts().unwrap_or_default() {
+                if let Err(e) = register_shortcut(app.handle(), &shortcut_state, &saved.accelerator, &saved.action) {
+                    log::warn!("Failed to restore global shortcut {}: {}", saved.accelerator, e);
+                }
+            }
+
             // Warm up the PTY system early to avoid first-spawn delays
             // This initializes the native PTY interface before any terminal is created
             std::thread::spawn(|| {
@@ -4535,27 +9056,93 @@ pub fn run() {
                 }
             }
 
-            // Handle file associations - when a .orca file is double-clicked (macOS only)
+            // Handle file associations - when a .orca file or a folder is opened with Orca (macOS only)
             #[cfg(target_os = "macos")]
             if let tauri::RunEvent::Opened { urls } = _event {
-                for url in urls {
-                    // Convert URL to file path
-                    if let Ok(path) = url.to_file_path() {
-                        if let Some(ext) = path.extension() {
-                            if ext == "orca" {
-                                if let Some(path_str) = path.to_str() {
-                                    // Show and focus the main window
-                                    if let Some(window) = _app_handle.get_webview_window("main") {
-                                        let _ = window.show();
-                                        let _ = window.set_focus();
-                                        // Emit event to frontend to open the workspace file
-                                        let _ = window.emit("open-workspace-file", path_str);
-                                    }
-                                }
-                            }
-                        }
-                    }
+                for path in urls.into_iter().filter_map(|url| url.to_file_path().ok()) {
+                    open_path_from_os(_app_handle, path);
                 }
             }
         });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atomic_write_round_trips_crlf_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("windows.txt");
+        let contents = b"first\r\nsecond\r\n\r\nlast\r\n";
+        atomic_write(&path, contents).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), contents);
+
+        // The editor hands back LF text; the file keeps its CRLF endings and trailing
+        // newline byte for byte
+        let as_edited = String::from_utf8_lossy(contents).replace("\r\n", "\n");
+        write_text_file(path.to_string_lossy().to_string(), as_edited).unwrap();
+        let written = std::fs::read(&path).unwrap();
+        assert!(written.ends_with(b"last\r\n"));
+        assert_eq!(written, contents);
+
+        // Rewriting a line with its own text leaves the file untouched
+        edit_file_line(path.to_string_lossy().to_string(), 2, "second".to_string(), None).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), contents);
+    }
+
+    #[test]
+    fn decode_utf8_chunk_joins_characters_split_across_reads() {
+        let text = "héllo → 世界 🦀";
+        let bytes = text.as_bytes();
+        // Every split point, including ones inside 2, 3 and 4 byte characters
+        for split in 0..=bytes.len() {
+            let mut pending = Vec::new();
+            let mut decoded = decode_utf8_chunk(&mut pending, &bytes[..split]);
+            assert!(!decoded.contains('\u{FFFD}'), "split at {}", split);
+            decoded.push_str(&decode_utf8_chunk(&mut pending, &bytes[split..]));
+            assert_eq!(decoded, text, "split at {}", split);
+            assert!(pending.is_empty());
+        }
+
+        // A 4-byte character arriving one byte per read
+        let mut pending = Vec::new();
+        let crab = "🦀".as_bytes();
+        for byte in &crab[..3] {
+            assert_eq!(decode_utf8_chunk(&mut pending, std::slice::from_ref(byte)), "");
+        }
+        assert_eq!(decode_utf8_chunk(&mut pending, &crab[3..]), "🦀");
+    }
+
+    #[test]
+    fn decode_output_buffer_drops_partial_characters_at_both_ends() {
+        let bytes = "世界🦀".as_bytes();
+        // Trimmed one byte into 世 and cut one byte short of the end of 🦀
+        assert_eq!(decode_output_buffer(&bytes[1..bytes.len() - 1]), "界");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_write_keeps_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target.txt");
+        let link = dir.path().join("link.txt");
+        std::fs::write(&target, "old").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        atomic_write(&link, b"new").unwrap();
+
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read(&target).unwrap(), b"new");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn signal_number_maps_strsignal_descriptions() {
+        assert_eq!(signal_number("Killed"), Some(libc::SIGKILL));
+        assert_eq!(signal_number("Killed: 9"), Some(9));
+        assert_eq!(signal_number("Abort trap"), Some(libc::SIGABRT));
+        assert_eq!(signal_number("Signal 42"), Some(42));
+        assert_eq!(signal_number("Not a signal"), None);
+    }
+}
@@ -0,0 +1,144 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use parking_lot::Mutex;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+const MAX_LOG_ENTRIES: usize = 2000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub timestamp: i64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+type LogSink = Box<dyn Fn(&LogEntry) + Send + Sync>;
+
+/// Logger that keeps recent entries in memory for the debug panel while still
+/// writing to stderr through env_logger (respecting RUST_LOG)
+struct AppLogger {
+    inner: env_logger::Logger,
+    entries: Mutex<VecDeque<LogEntry>>,
+    sink: OnceLock<LogSink>,
+}
+
+static LOGGER: OnceLock<AppLogger> = OnceLock::new();
+
+thread_local! {
+    // Guards against re-entrant logging from inside the sink (e.g. while emitting an event)
+    static IN_SINK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Only Orca's own logs are captured; dependency chatter stays on stderr
+fn is_captured(metadata: &Metadata) -> bool {
+    metadata.level() <= Level::Info && metadata.target().starts_with("orca")
+}
+
+impl Log for AppLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        is_captured(metadata) || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+        if !is_captured(record.metadata()) {
+            return;
+        }
+
+        let entry = LogEntry {
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            level: record.level().to_string(),
+            target: record.target().to_string(),
+            message: redact_secrets(&record.args().to_string()),
+        };
+
+        {
+            let mut entries = self.entries.lock();
+            if entries.len() >= MAX_LOG_ENTRIES {
+                entries.pop_front();
+            }
+            entries.push_back(entry.clone());
+        }
+
+        if let Some(sink) = self.sink.get() {
+            if !IN_SINK.with(|f| f.replace(true)) {
+                sink(&entry);
+                IN_SINK.with(|f| f.set(false));
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Install the logger. Replaces `env_logger::init()`.
+pub fn init() {
+    let inner = env_logger::Builder::from_default_env().build();
+    let max_level = inner.filter().max(LevelFilter::Info);
+    let logger = LOGGER.get_or_init(|| AppLogger {
+        inner,
+        entries: Mutex::new(VecDeque::with_capacity(MAX_LOG_ENTRIES)),
+        sink: OnceLock::new(),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Register a callback that receives each captured entry as it's logged
+pub fn set_sink(sink: impl Fn(&LogEntry) + Send + Sync + 'static) {
+    if let Some(logger) = LOGGER.get() {
+        let _ = logger.sink.set(Box::new(sink));
+    }
+}
+
+/// Most recent entries at or above `min_level`, oldest first
+pub fn recent(min_level: Option<&str>, limit: usize) -> Vec<LogEntry> {
+    let Some(logger) = LOGGER.get() else { return Vec::new() };
+    let min_level = min_level
+        .and_then(|l| l.parse::<Level>().ok())
+        .unwrap_or(Level::Trace);
+
+    let entries = logger.entries.lock();
+    let mut matched: Vec<LogEntry> = entries
+        .iter()
+        .rev()
+        .filter(|e| e.level.parse::<Level>().map(|l| l <= min_level).unwrap_or(true))
+        .take(limit)
+        .cloned()
+        .collect();
+    matched.reverse();
+    matched
+}
+
+/// Mask API keys, tokens and passwords before they end up in a log a user might share
+pub fn redact_secrets(message: &str) -> String {
+    static PATTERNS: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        [
+            // Provider key formats (OpenAI, Anthropic, Groq, GitHub)
+            (r"\b(sk-[A-Za-z0-9_\-]{8,}|gsk_[A-Za-z0-9]{8,}|gh[pousr]_[A-Za-z0-9]{16,}|github_pat_[A-Za-z0-9_]{16,})", "[REDACTED]"),
+            // Authorization headers
+            (r"(?i)\b(bearer|basic)\s+[A-Za-z0-9._~+/=\-]{8,}", "$1 [REDACTED]"),
+            // key=value / "key": "value" pairs
+            (r#"(?i)((?:api[_-]?key|token|secret|password|passphrase)["']?\s*[:=]\s*["']?)[^\s"',&]+"#, "${1}[REDACTED]"),
+        ]
+        .into_iter()
+        .filter_map(|(p, r)| Regex::new(p).ok().map(|re| (re, r)))
+        .collect()
+    });
+
+    let mut result = message.to_string();
+    for (re, replacement) in patterns {
+        result = re.replace_all(&result, *replacement).into_owned();
+    }
+    result
+}
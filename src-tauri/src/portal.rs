@@ -324,7 +324,7 @@ async fn handle_message(
                 config.linked_devices = devices.clone();
 
                 // Save to database
-                let db = state.database.lock();
+                let db = &state.database;
                 let _ = db.set_portal_config(&config);
 
                 // Emit to frontend
@@ -338,7 +338,6 @@ async fn handle_message(
             // Get projects from database
             let projects: Vec<ProjectInfo> = state
                 .database
-                .lock()
                 .get_all_projects()
                 .unwrap_or_default()
                 .into_iter()
@@ -372,15 +371,22 @@ async fn handle_message(
 
             log::info!("[Portal] Sending status with {} projects and {} terminals", projects.len(), terminals.len());
 
+            let theme = state
+                .database
+                .get_setting("theme")
+                .ok()
+                .flatten()
+                .unwrap_or_else(|| "dark".to_string());
+
             let status_update = json!({
                 "type": "status_update",
                 "id": uuid::Uuid::new_v4().to_string(),
                 "timestamp": chrono::Utc::now().timestamp_millis(),
                 "connectionStatus": "connected",
                 "projects": projects,
-                "activeProjectId": null,
+                "activeProjectId": state.active_project.lock().clone(),
                 "terminals": terminals,
-                "theme": "tokyo",
+                "theme": theme,
             });
 
             if let Ok(json) = serde_json::to_string(&status_update) {
@@ -426,11 +432,7 @@ async fn handle_message(
                 .terminals
                 .lock()
                 .get(terminal_id)
-                .map(|t| {
-                    let buffer = t.output_buffer.lock();
-                    // Buffer is raw bytes, decode and convert to string
-                    String::from_utf8_lossy(&buffer).to_string()
-                })
+                .map(|t| crate::decode_output_buffer(&t.output_buffer.lock()))
                 .unwrap_or_default();
 
             if !buffer_data.is_empty() {
@@ -484,8 +486,14 @@ async fn handle_message(
         }
 
         "select_project" => {
-            // Forward to frontend - it needs to open the project tab
-            let _ = app_handle.emit("portal-select-project", message);
+            let project_id = message
+                .get("projectId")
+                .and_then(|p| p.as_str())
+                .map(|p| p.to_string());
+            // Emits portal-active-project-changed, which the frontend answers by opening the tab
+            if let Err(e) = crate::activate_project(state, app_handle, project_id) {
+                log::warn!("[Portal] select_project failed: {}", e);
+            }
         }
 
         "error" => {
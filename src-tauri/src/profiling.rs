@@ -0,0 +1,87 @@
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+const MAX_SAMPLES: usize = 5000;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SAMPLES: Mutex<VecDeque<(&'static str, Duration)>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandTiming {
+    pub command: String,
+    pub count: usize,
+    #[serde(rename = "avgMs")]
+    pub avg_ms: f64,
+    #[serde(rename = "p95Ms")]
+    pub p95_ms: f64,
+    #[serde(rename = "maxMs")]
+    pub max_ms: f64,
+}
+
+/// Records how long a command took when dropped. Cheap no-op while profiling is off.
+pub struct Timer {
+    command: &'static str,
+    started: Option<Instant>,
+}
+
+impl Timer {
+    pub fn start(command: &'static str) -> Self {
+        let started = ENABLED.load(Ordering::Relaxed).then(Instant::now);
+        Self { command, started }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if let Some(started) = self.started {
+            record(self.command, started.elapsed());
+        }
+    }
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        SAMPLES.lock().clear();
+    }
+}
+
+fn record(command: &'static str, duration: Duration) {
+    let mut samples = SAMPLES.lock();
+    if samples.len() >= MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back((command, duration));
+}
+
+/// Per-command stats over the samples currently in the ring buffer, slowest p95 first
+pub fn timings() -> Vec<CommandTiming> {
+    let mut by_command: HashMap<&'static str, Vec<f64>> = HashMap::new();
+    for (command, duration) in SAMPLES.lock().iter() {
+        by_command
+            .entry(command)
+            .or_default()
+            .push(duration.as_secs_f64() * 1000.0);
+    }
+
+    let mut result: Vec<CommandTiming> = by_command
+        .into_iter()
+        .map(|(command, mut ms)| {
+            ms.sort_by(|a, b| a.total_cmp(b));
+            let count = ms.len();
+            let p95_index = ((count as f64 * 0.95).ceil() as usize).clamp(1, count) - 1;
+            CommandTiming {
+                command: command.to_string(),
+                count,
+                avg_ms: ms.iter().sum::<f64>() / count as f64,
+                p95_ms: ms[p95_index],
+                max_ms: ms[count - 1],
+            }
+        })
+        .collect();
+    result.sort_by(|a, b| b.p95_ms.total_cmp(&a.p95_ms));
+    result
+}
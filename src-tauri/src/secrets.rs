@@ -0,0 +1,42 @@
+use keyring::Entry;
+
+/// Keychain service every Orca secret is stored under; the secret name is the account
+const SERVICE: &str = "orca";
+
+fn entry(name: &str) -> Result<Entry, String> {
+    if name.is_empty() {
+        return Err("Secret name is empty".to_string());
+    }
+    Entry::new(SERVICE, name).map_err(|e| e.to_string())
+}
+
+/// Save a secret in the OS credential store (Keychain, Credential Manager or Secret Service)
+pub fn store(name: &str, value: &str) -> Result<(), String> {
+    entry(name)?.set_password(value).map_err(|e| e.to_string())
+}
+
+/// `None` when nothing is stored under `name`
+pub fn get(name: &str) -> Result<Option<String>, String> {
+    match entry(name)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Deleting a secret that doesn't exist is not an error
+pub fn delete(name: &str) -> Result<(), String> {
+    match entry(name)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Key for `provider` from its `ai/<provider>` keychain entry
+pub fn ai_api_key(provider: &str) -> Option<String> {
+    get(&format!("ai/{}", provider)).ok().flatten().filter(|key| !key.is_empty())
+}
+
+pub fn github_token() -> Option<String> {
+    get("github").ok().flatten().filter(|token| !token.is_empty())
+}
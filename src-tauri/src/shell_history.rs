@@ -0,0 +1,438 @@
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Entries kept when the file is compacted
+const MAX_ENTRIES: usize = 5000;
+/// How far past `MAX_ENTRIES` the file may grow before compacting, so most writes are plain appends
+const COMPACT_SLACK: usize = 1000;
+
+// Per-project shell history entry, one JSON object per line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShellHistoryEntry {
+    pub command: String,
+    pub project_path: String,
+    pub timestamp: i64,
+}
+
+/// A search hit from `search`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryMatch {
+    pub command: String,
+    pub timestamp: i64,
+}
+
+struct WriterState {
+    lines: usize,
+    /// Most recent command per project, for dropping consecutive repeats
+    last_command: HashMap<String, String>,
+}
+
+/// Serializes appends and compaction across terminals. Loaded from disk on first write.
+static WRITER: Mutex<Option<WriterState>> = Mutex::new(None);
+static MIGRATE_LEGACY: std::sync::Once = std::sync::Once::new();
+
+fn history_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".claude"))
+}
+
+pub fn history_path() -> Option<PathBuf> {
+    history_dir().map(|dir| dir.join("shell_history.jsonl"))
+}
+
+/// Lines that don't parse (e.g. a write cut short by a crash) are skipped
+fn parse(content: &str) -> Vec<ShellHistoryEntry> {
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+fn serialize(entries: &[ShellHistoryEntry]) -> Result<String, String> {
+    let mut out = String::new();
+    for entry in entries {
+        out.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Convert the old single-array `shell_history.json` to JSONL, once per launch at most
+fn migrate_legacy(path: &Path) {
+    MIGRATE_LEGACY.call_once(|| {
+        let Some(legacy) = history_dir().map(|dir| dir.join("shell_history.json")) else { return };
+        if path.exists() || !legacy.exists() {
+            return;
+        }
+        let entries: Vec<ShellHistoryEntry> = std::fs::read_to_string(&legacy)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        let result = serialize(&entries)
+            .and_then(|content| crate::atomic_write(path, content.as_bytes()).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => {
+                let _ = std::fs::remove_file(&legacy);
+            }
+            Err(e) => log::warn!("Failed to migrate shell history: {}", e),
+        }
+    });
+}
+
+fn read_file(path: &Path) -> Result<Vec<ShellHistoryEntry>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(parse(&content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// All recorded entries, oldest first
+pub fn read_entries() -> Result<Vec<ShellHistoryEntry>, String> {
+    let path = history_path().ok_or("Could not determine history path")?;
+    migrate_legacy(&path);
+    read_file(&path)
+}
+
+/// Append a command unless it repeats the project's previous one
+pub fn record(command: &str, project_path: &str) -> Result<(), String> {
+    let path = history_path().ok_or("Could not determine history path")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    migrate_legacy(&path);
+
+    let mut writer = WRITER.lock();
+    let state = match writer.as_mut() {
+        Some(state) => state,
+        None => {
+            let entries = read_file(&path)?;
+            let mut last_command = HashMap::new();
+            for entry in &entries {
+                last_command.insert(entry.project_path.clone(), entry.command.clone());
+            }
+            writer.insert(WriterState { lines: entries.len(), last_command })
+        }
+    };
+
+    if state.last_command.get(project_path).map(String::as_str) == Some(command) {
+        return Ok(());
+    }
+
+    let entry = ShellHistoryEntry {
+        command: command.to_string(),
+        project_path: project_path.to_string(),
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0),
+    };
+    let line = serialize(std::slice::from_ref(&entry))?;
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| e.to_string())?;
+    state.lines += 1;
+    state.last_command.insert(entry.project_path, entry.command);
+
+    if state.lines > MAX_ENTRIES + COMPACT_SLACK {
+        let mut entries = read_file(&path)?;
+        if entries.len() > MAX_ENTRIES {
+            entries = entries.split_off(entries.len() - MAX_ENTRIES);
+        }
+        crate::atomic_write(&path, serialize(&entries)?.as_bytes()).map_err(|e| e.to_string())?;
+        state.lines = entries.len();
+    }
+    Ok(())
+}
+
+/// Whether `entry` was run in `project_path` or one of its subdirectories
+pub fn in_project(entry: &ShellHistoryEntry, project_path: &str) -> bool {
+    entry
+        .project_path
+        .strip_prefix(project_path)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\'))
+}
+
+/// Case-insensitive (ASCII) substring test that doesn't allocate per candidate
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    let (haystack, needle) = (haystack.as_bytes(), needle.as_bytes());
+    needle.is_empty() || haystack.windows(needle.len()).any(|window| window.eq_ignore_ascii_case(needle))
+}
+
+/// Commands in `project_path` containing `query`, newest first, each command once (at its
+/// most recent use). Smart case: the match is case-sensitive only if `query` has capitals.
+pub fn search(project_path: &str, query: &str, limit: usize) -> Result<Vec<HistoryMatch>, String> {
+    let entries = read_entries()?;
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let mut seen = std::collections::HashSet::new();
+    let mut matches = Vec::new();
+
+    for entry in entries.iter().rev() {
+        if matches.len() >= limit {
+            break;
+        }
+        if !in_project(entry, project_path) {
+            continue;
+        }
+        let hit = if case_sensitive {
+            entry.command.contains(query)
+        } else {
+            contains_ignore_case(&entry.command, query)
+        };
+        if hit && seen.insert(entry.command.as_str()) {
+            matches.push(HistoryMatch {
+                command: entry.command.clone(),
+                timestamp: entry.timestamp,
+            });
+        }
+    }
+    Ok(matches)
+}
+
+/// One command in `combined`, tagged with where it came from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CombinedHistoryEntry {
+    pub command: String,
+    /// Unix seconds; `None` for shells that don't record when a command ran
+    pub timestamp: Option<i64>,
+    /// "orca", "zsh", "bash", "fish" or "powershell"
+    pub source: String,
+}
+
+/// The user's shell history files that exist, with the shell each belongs to
+fn os_history_files() -> Vec<(&'static str, PathBuf)> {
+    let mut files = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        let zsh = std::env::var("HISTFILE")
+            .ok()
+            .filter(|f| f.ends_with("zsh_history"))
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".zsh_history"));
+        files.push(("zsh", zsh));
+        files.push(("bash", home.join(".bash_history")));
+        files.push(("fish", home.join(".local/share/fish/fish_history")));
+    }
+    if let Some(appdata) = dirs::config_dir().filter(|_| cfg!(windows)) {
+        files.push((
+            "powershell",
+            appdata.join("Microsoft\\Windows\\PowerShell\\PSReadLine\\ConsoleHost_history.txt"),
+        ));
+    }
+    files.retain(|(_, path)| path.is_file());
+    files
+}
+
+/// zsh "metafies" bytes it uses internally (NUL and 0x83 to 0x9f) in its history file as
+/// 0x83 followed by the byte XOR 0x20; undo that so multibyte UTF-8 commands decode
+fn unmetafy_zsh(bytes: &[u8]) -> Vec<u8> {
+    const META: u8 = 0x83;
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&b) = iter.next() {
+        if b == META {
+            if let Some(&next) = iter.next() {
+                out.push(next ^ 0x20);
+            }
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
+
+/// zsh history, with timestamps from EXTENDED_HISTORY lines (`: 1700000000:0;command`).
+/// A trailing backslash continues a command onto the next line, and is kept so the
+/// command still runs as written.
+fn parse_zsh(content: &str) -> Vec<(String, Option<i64>)> {
+    let mut commands: Vec<(String, Option<i64>)> = Vec::new();
+    let mut continuing = false;
+    for line in content.lines() {
+        if continuing {
+            if let Some((command, _)) = commands.last_mut() {
+                command.push('\n');
+                command.push_str(line);
+            }
+        } else if let Some(rest) = line.strip_prefix(": ") {
+            let Some((meta, command)) = rest.split_once(';') else { continue };
+            let timestamp = meta.split(':').next().and_then(|t| t.trim().parse().ok());
+            commands.push((command.to_string(), timestamp));
+        } else {
+            commands.push((line.to_string(), None));
+        }
+        continuing = line.ends_with('\\');
+    }
+    commands
+}
+
+/// bash history; with HISTTIMEFORMAT set, `#1700000000` lines timestamp the next command
+fn parse_bash(content: &str) -> Vec<(String, Option<i64>)> {
+    let mut commands = Vec::new();
+    let mut pending_time = None;
+    for line in content.lines() {
+        if let Some(time) = line.strip_prefix('#').and_then(|t| t.parse::<i64>().ok()) {
+            pending_time = Some(time);
+            continue;
+        }
+        commands.push((line.to_string(), pending_time.take()));
+    }
+    commands
+}
+
+/// fish's YAML-like history: `- cmd: ...` followed by an indented `when: <unix time>`
+fn parse_fish(content: &str) -> Vec<(String, Option<i64>)> {
+    let mut commands: Vec<(String, Option<i64>)> = Vec::new();
+    for line in content.lines() {
+        if let Some(command) = line.strip_prefix("- cmd: ") {
+            // fish escapes backslashes and newlines in the command
+            let mut unescaped = String::with_capacity(command.len());
+            let mut chars = command.chars();
+            while let Some(c) = chars.next() {
+                match (c, chars.clone().next()) {
+                    ('\\', Some('n')) => {
+                        unescaped.push('\n');
+                        chars.next();
+                    }
+                    ('\\', Some('\\')) => {
+                        unescaped.push('\\');
+                        chars.next();
+                    }
+                    _ => unescaped.push(c),
+                }
+            }
+            commands.push((unescaped, None));
+        } else if let Some(when) = line.trim_start().strip_prefix("when: ") {
+            if let Some((_, timestamp)) = commands.last_mut() {
+                *timestamp = when.trim().parse().ok();
+            }
+        }
+    }
+    commands
+}
+
+/// Commands in a shell's history file, oldest first, with the time each ran when the shell
+/// recorded it. `shell` is "zsh", "bash" or "fish"; anything else is read as one command per line.
+pub fn parse_history(shell: &str, bytes: &[u8]) -> Vec<(String, Option<i64>)> {
+    match shell {
+        "zsh" => parse_zsh(&String::from_utf8_lossy(&unmetafy_zsh(bytes))),
+        "bash" => parse_bash(&String::from_utf8_lossy(bytes)),
+        "fish" => parse_fish(&String::from_utf8_lossy(bytes)),
+        _ => String::from_utf8_lossy(bytes)
+            .lines()
+            .map(|line| (line.to_string(), None))
+            .collect(),
+    }
+}
+
+/// A command in `combined` before sorting and deduplication
+struct Candidate {
+    /// Time to sort by: the command's own, or the last one recorded before it in its file
+    sort_time: Option<i64>,
+    /// Which source it came from, in the order they're read
+    source_index: usize,
+    /// Position within its source
+    position: usize,
+    timestamp: Option<i64>,
+    source: &'static str,
+    command: String,
+}
+
+/// Orca's history for `project_path` merged with the user's zsh, bash, fish and PowerShell
+/// history. Newest first, each command once (tagged with its most recent source). An untimed
+/// command takes the time of the timed one before it in its file so it keeps its place there;
+/// commands from files with no timestamps at all sort last, newest of each file first.
+pub fn combined(project_path: &str, limit: usize) -> Result<Vec<CombinedHistoryEntry>, String> {
+    let mut all: Vec<Candidate> = Vec::new();
+
+    for (position, entry) in read_entries()?.into_iter().enumerate() {
+        if in_project(&entry, project_path) {
+            all.push(Candidate {
+                sort_time: Some(entry.timestamp),
+                source_index: 0,
+                position,
+                timestamp: Some(entry.timestamp),
+                source: "orca",
+                command: entry.command,
+            });
+        }
+    }
+
+    for (index, (shell, path)) in os_history_files().into_iter().enumerate() {
+        let Ok(bytes) = std::fs::read(&path) else { continue };
+        let mut last_time = None;
+        for (position, (command, timestamp)) in parse_history(shell, &bytes).into_iter().enumerate() {
+            last_time = timestamp.or(last_time);
+            all.push(Candidate {
+                sort_time: last_time,
+                source_index: index + 1,
+                position,
+                timestamp,
+                source: shell,
+                command,
+            });
+        }
+    }
+
+    // Newest first; ties (and untimed files) keep file order, reversed
+    all.sort_by(|a, b| {
+        let by_file = a.source_index.cmp(&b.source_index).then(b.position.cmp(&a.position));
+        match (a.sort_time, b.sort_time) {
+            (Some(x), Some(y)) => y.cmp(&x).then(by_file),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => by_file,
+        }
+    });
+
+    let mut seen = std::collections::HashSet::new();
+    let mut merged = Vec::new();
+    for Candidate { timestamp, source, command, .. } in all {
+        if merged.len() >= limit {
+            break;
+        }
+        let trimmed = command.trim();
+        if trimmed.is_empty() || !seen.insert(trimmed.to_string()) {
+            continue;
+        }
+        merged.push(CombinedHistoryEntry {
+            command: trimmed.to_string(),
+            timestamp,
+            source: source.to_string(),
+        });
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_history_decodes_metafied_zsh() {
+        // "echo é" (é = C3 A9) with a timestamp, then "ls ✓" (✓ = E2 9C 93), where zsh
+        // metafied the 0x9c and 0x93 bytes
+        let mut bytes = b": 1700000000:0;echo \xc3\xa9\n: 1700000010:0;ls \xe2".to_vec();
+        bytes.extend_from_slice(&[0x83, 0x9c ^ 0x20, 0x83, 0x93 ^ 0x20, b'\n']);
+        assert_eq!(
+            parse_history("zsh", &bytes),
+            vec![("echo é".to_string(), Some(1_700_000_000)), ("ls ✓".to_string(), Some(1_700_000_010))]
+        );
+    }
+
+    #[test]
+    fn parse_history_reads_each_shell_format() {
+        assert_eq!(
+            parse_history("bash", b"#1700000000\ngit status\nls\n"),
+            vec![("git status".to_string(), Some(1_700_000_000)), ("ls".to_string(), None)]
+        );
+        assert_eq!(
+            parse_history("fish", b"- cmd: echo a\\nb\n  when: 1700000000\n"),
+            vec![("echo a\nb".to_string(), Some(1_700_000_000))]
+        );
+        assert_eq!(parse_history("powershell", b"dir\r\n"), vec![("dir".to_string(), None)]);
+    }
+}
@@ -97,6 +97,19 @@ function App() {
     return () => { unlisten.then((fn) => fn()); };
   }, [navigate, projects, addProject, updateProject]);
 
+  // Handle folders opened with Orca from the OS (Open With, a second launch, CLI argument).
+  // The backend has already found or created the project.
+  useEffect(() => {
+    const unlisten = listen<Project>("open-project", (event) => {
+      const project = event.payload;
+      useProjectStore.getState().addProject(project);
+      navigate(`/project/${project.id}`);
+    });
+    // Anything the OS passed in before this listener existed is held until we ask for it
+    unlisten.then(() => invoke("open_pending_paths"));
+    return () => { unlisten.then((fn) => fn()); };
+  }, [navigate]);
+
   return (
     <TooltipProvider>
       <div className="h-screen w-screen overflow-hidden text-foreground">
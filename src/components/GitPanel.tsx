@@ -90,7 +90,7 @@ import {
 import { useGitStore } from "@/stores/gitStore";
 import { useSettingsStore } from "@/stores/settingsStore";
 import { cn, formatTimestamp } from "@/lib/utils";
-import type { FileDiff, DiffHunk, ProjectFolder, WorktreeInfo, DiffPanelSelection, Stash, Tag, PullRequest } from "@/types";
+import type { FileDiff, DiffHunk, ProjectFolder, WorktreeInfo, DiffPanelSelection, Stash, Tag, PullRequest, FilesChangedEvent } from "@/types";
 
 
 interface GitPanelProps {
@@ -689,10 +689,10 @@ export default function GitPanel({ projectPath, isGitRepo, onRefresh, onInitRepo
         });
       });
 
-      const unlisten = listen<string>("fs-files-changed", (event) => {
+      const unlisten = listen<FilesChangedEvent>("fs-files-changed", (event) => {
         if (viewMode === "files") {
           // Find which folder changed and reload its tree
-          const changedFolder = folders.find(f => f.path === event.payload);
+          const changedFolder = folders.find(f => f.path === event.payload.projectPath);
           if (changedFolder) {
             loadFolderTree(changedFolder.id, changedFolder.path);
           }
@@ -714,8 +714,8 @@ export default function GitPanel({ projectPath, isGitRepo, onRefresh, onInitRepo
       console.error("Failed to start file watcher:", err);
     });
 
-    const unlisten = listen<string>("fs-files-changed", (event) => {
-      if (event.payload === fileTreeRoot && viewMode === "files") {
+    const unlisten = listen<FilesChangedEvent>("fs-files-changed", (event) => {
+      if (event.payload.projectPath === fileTreeRoot && viewMode === "files") {
         loadFileTree();
       }
     });
@@ -46,7 +46,7 @@ import {
 } from "@/components/ui/select";
 // import { RemotePortalSettings } from "@/components/RemotePortalSettings";
 import { getAllAssistants, getAllAssistantCommands } from "@/lib/assistants";
-import type { ThemeOption, AssistantDefinition, AiProviderType } from "@/types";
+import type { ThemeOption, AssistantDefinition, AiProviderType, MicrophonePermission } from "@/types";
 
 interface SettingsSheetProps {
   open: boolean;
@@ -139,7 +139,7 @@ export default function SettingsSheet({ open, onOpenChange }: SettingsSheetProps
   const [showAddForm, setShowAddForm] = useState(false);
   const [newAssistant, setNewAssistant] = useState({ name: "", command: "", description: "", installCommand: "", docsUrl: "" });
   const pollIntervalRef = useRef<ReturnType<typeof setInterval> | null>(null);
-  const [micPermission, setMicPermission] = useState<string | null>(null);
+  const [micPermission, setMicPermission] = useState<MicrophonePermission | null>(null);
 
   const allAssistants = getAllAssistants(customAssistants);
 
@@ -173,7 +173,7 @@ export default function SettingsSheet({ open, onOpenChange }: SettingsSheetProps
   // Check microphone permission status when dialog opens
   useEffect(() => {
     if (open && activeTab === "general") {
-      invoke<string>("request_microphone_permission")
+      invoke<MicrophonePermission>("request_microphone_permission")
         .then(setMicPermission)
         .catch(() => setMicPermission(null));
     }
@@ -535,24 +535,26 @@ export default function SettingsSheet({ open, onOpenChange }: SettingsSheetProps
                               <Check className="h-4 w-4" />
                               Granted
                             </div>
+                          ) : micPermission === "restricted" ? (
+                            <p className="text-sm text-muted-foreground">Restricted by system policy</p>
+                          ) : micPermission === "unavailable" ? (
+                            <p className="text-sm text-muted-foreground">No microphone found</p>
                           ) : (
                             <Button
                               variant="outline"
                               size="sm"
                               onClick={async () => {
                                 try {
-                                  const result = await invoke<string>("request_microphone_permission");
+                                  const result = await invoke<MicrophonePermission>("request_microphone_permission");
                                   setMicPermission(result);
                                   if (result === "authorized") {
                                     toast.success("Microphone access granted");
                                   } else if (result === "denied") {
-                                    toast.error("Microphone access denied. Enable in System Settings > Privacy & Security > Microphone");
-                                  } else if (result === "requested") {
-                                    toast.success("Permission requested. Check the system dialog.");
+                                    toast.error("Microphone access denied. Enable it in your system's privacy settings for the microphone");
                                   } else if (result === "restricted") {
                                     toast.error("Microphone access is restricted by system policy");
-                                  } else if (result === "not_applicable") {
-                                    toast.info("Microphone permissions are managed by the system on this platform");
+                                  } else if (result === "unavailable") {
+                                    toast.info("No microphone or audio input was found");
                                   }
                                 } catch (error) {
                                   console.error("Failed to request microphone permission:", error);
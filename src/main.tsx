@@ -5,6 +5,7 @@ import { getCurrentWindow } from "@tauri-apps/api/window";
 import App from "./App";
 import "@/index.css";
 import { setupPortalEventListeners, setupGitChangeForwarding } from "@/stores/portalStore";
+import { setupActiveProjectListener } from "@/stores/projectStore";
 
 // Initialize portal event listeners (connection state, commands from mobile)
 setupPortalEventListeners();
@@ -12,6 +13,9 @@ setupPortalEventListeners();
 // Initialize git file change forwarding to mobile devices
 setupGitChangeForwarding();
 
+// Keep the backend's active project (used by Portal) in sync with the active tab
+setupActiveProjectListener();
+
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
     <BrowserRouter>
@@ -88,7 +88,7 @@ import { useSettingsStore } from "@/stores/settingsStore";
 import { cn } from "@/lib/utils";
 import { hslToHex, THEME_DEFAULTS } from "@/lib/colorUtils";
 import { getAllAssistants, getAllAssistantCommands } from "@/lib/assistants";
-import type { Project, GitStatus, FileDiff, Branch, Commit, WorktreeInfo, CustomThemeColors, ProjectFolder, ProjectFileData, DiffPanelSelection } from "@/types";
+import type { Project, GitStatus, FileDiff, Branch, Commit, WorktreeInfo, CustomThemeColors, ProjectFolder, ProjectFileData, DiffPanelSelection, FilesChangedEvent } from "@/types";
 
 // Types for global file search
 interface FileTreeNode {
@@ -1948,17 +1948,17 @@ export default function ProjectPage() {
     });
 
     // Listen for file change events
-    const unlistenGit = listen<string>("git-files-changed", (event) => {
+    const unlistenGit = listen<FilesChangedEvent>("git-files-changed", (event) => {
       // Only refresh if this event is for our repo
-      if (event.payload === repoPath) {
+      if (event.payload.projectPath === repoPath) {
         loadGitData(repoPath);
       }
     });
 
     // Also refresh git status when working tree files change (detected by the
     // project file watcher), so unstaged changes update live.
-    const unlistenFs = listen<string>("fs-files-changed", (event) => {
-      if (event.payload === repoPath) {
+    const unlistenFs = listen<FilesChangedEvent>("fs-files-changed", (event) => {
+      if (event.payload.projectPath === repoPath) {
         loadGitData(repoPath);
       }
     });
@@ -1984,8 +1984,12 @@ export default function ProjectPage() {
         // File may have been deleted; ignore
       }
     };
-    const unlistenGit = listen<string>("git-files-changed", refreshMarkdown);
-    const unlistenFs = listen<string>("fs-files-changed", refreshMarkdown);
+    const unlistenGit = listen<FilesChangedEvent>("git-files-changed", refreshMarkdown);
+    const unlistenFs = listen<FilesChangedEvent>("fs-files-changed", (event) => {
+      if (event.payload.paths.includes(filePath)) {
+        refreshMarkdown();
+      }
+    });
 
     return () => {
       unlistenGit.then((fn) => fn());
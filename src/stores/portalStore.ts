@@ -1,6 +1,6 @@
 import { create } from "zustand";
 import { persist } from "zustand/middleware";
-import type { LinkedDevice } from "@/types";
+import type { FilesChangedEvent, LinkedDevice } from "@/types";
 import { useSettingsStore } from "./settingsStore";
 
 interface PortalState {
@@ -289,26 +289,6 @@ async function handleCommand(message: Record<string, unknown>) {
   }
 }
 
-// Handle select_project messages from backend (needs frontend to open tab)
-async function handleSelectProject(message: Record<string, unknown>) {
-  const { projectId } = message as { projectId: string };
-  const { useProjectStore } = await import("@/stores/projectStore");
-  const { projects, openTab } = useProjectStore.getState();
-  const project = projects.find((p) => p.id === projectId);
-
-  if (project) {
-    openTab(project);
-
-    const { sendMessage } = usePortalStore.getState();
-    await sendMessage({
-      type: "project_changed",
-      id: crypto.randomUUID(),
-      timestamp: Date.now(),
-      projectId,
-    });
-  }
-}
-
 // Setup event listeners for backend portal events
 export function setupPortalEventListeners() {
   import("@tauri-apps/api/event").then(({ listen }) => {
@@ -334,12 +314,6 @@ export function setupPortalEventListeners() {
       handleCommand(event.payload);
     });
 
-    // Select project from mobile - needs frontend handling
-    listen<Record<string, unknown>>("portal-select-project", (event) => {
-      console.log("[Portal] Received portal-select-project event");
-      handleSelectProject(event.payload);
-    });
-
     // Error from relay
     listen<{ code: string; message: string }>("portal-error", (event) => {
       console.error("[Portal] Error:", event.payload);
@@ -356,14 +330,14 @@ export function setupPortalEventListeners() {
 // Git file change forwarding - notify mobile when git files change
 export function setupGitChangeForwarding() {
   import("@tauri-apps/api/event").then(({ listen }) => {
-    listen<string>("git-files-changed", (event) => {
+    listen<FilesChangedEvent>("git-files-changed", (event) => {
       const { isConnected, sendMessage } = usePortalStore.getState();
       if (!isConnected) return;
 
       sendMessage({
         type: "git_files_changed",
         id: crypto.randomUUID(),
-        repoPath: event.payload,
+        repoPath: event.payload.projectPath,
       });
     });
   });
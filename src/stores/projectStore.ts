@@ -127,4 +127,46 @@ export const useProjectStore = create<ProjectState>()(
       partialize: (state) => ({ projects: state.projects }), // Only persist projects, not tabs
     }
   )
-);
\ No newline at end of file
+);
+const activeProjectId = (state: ProjectState) =>
+  state.tabs.find((t) => t.id === state.activeTabId)?.projectId ?? null;
+
+// Mirror the active tab's project to the backend so Portal follows what the desktop shows.
+// Each change we send comes back as a portal-active-project-changed event; those echoes
+// are skipped so a stale one can't switch tabs after the user has moved on.
+let syncedProjectId: string | null = null;
+const pendingEchoes: (string | null)[] = [];
+
+useProjectStore.subscribe((state) => {
+  const projectId = activeProjectId(state);
+  if (projectId === syncedProjectId) return;
+  syncedProjectId = projectId;
+  pendingEchoes.push(projectId);
+  import('@tauri-apps/api/core').then(({ invoke }) =>
+    invoke('set_active_project', { id: projectId }).catch((error) => {
+      const echo = pendingEchoes.indexOf(projectId);
+      if (echo !== -1) pendingEchoes.splice(echo, 1);
+      console.error('Failed to set active project:', error);
+    })
+  );
+});
+
+// The backend switched projects on its own (e.g. select_project from Portal): open that tab
+export function setupActiveProjectListener() {
+  import('@tauri-apps/api/event').then(({ listen }) => {
+    listen<{ projectId: string | null }>('portal-active-project-changed', (event) => {
+      const { projectId } = event.payload;
+      const echo = pendingEchoes.indexOf(projectId);
+      if (echo !== -1) {
+        pendingEchoes.splice(echo, 1);
+        return;
+      }
+      const { projects, openTab } = useProjectStore.getState();
+      const project = projects.find((p) => p.id === projectId);
+      if (project) {
+        syncedProjectId = project.id;
+        openTab(project);
+      }
+    });
+  });
+}
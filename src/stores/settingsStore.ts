@@ -211,3 +211,22 @@ export const useSettingsStore = create<SettingsState>()(
     }
   )
 );
+
+// Save the theme to the backend settings table too, where Portal reads it for mobile.
+// A custom theme is sent as the built-in theme it's based on.
+let syncedTheme: string | null = null;
+
+const syncThemeSetting = (state: SettingsState) => {
+  const theme = state.theme === 'custom' ? state.customTheme?.baseTheme ?? 'dark' : state.theme;
+  if (theme === syncedTheme) return;
+  syncedTheme = theme;
+  import('@tauri-apps/api/core').then(({ invoke }) =>
+    invoke('set_setting', { key: 'theme', value: theme }).catch((error) => {
+      syncedTheme = null;
+      console.error('Failed to save theme setting:', error);
+    })
+  );
+};
+
+useSettingsStore.subscribe(syncThemeSetting);
+syncThemeSetting(useSettingsStore.getState());
@@ -36,11 +36,13 @@ export interface GitStatus {
   staged: string[];
   unstaged: string[];
   untracked: string[];
+  modifiedSubmodules: string[];
 }
 
 export interface FileDiff {
   path: string;
-  status: 'added' | 'modified' | 'deleted' | 'renamed';
+  status: 'added' | 'modified' | 'deleted' | 'renamed' | 'copied';
+  oldPath?: string;
   hunks: DiffHunk[];
 }
 
@@ -64,6 +66,8 @@ export interface Branch {
   isHead: boolean;
   isRemote: boolean;
   upstream?: string;
+  ahead?: number;
+  behind?: number;
 }
 
 export interface Commit {
@@ -95,6 +99,15 @@ export interface DiffPanelSelection {
   projectPath: string;
 }
 
+// Payload of the project-files-changed, git-files-changed and fs-files-changed events
+export interface FilesChangedEvent {
+  projectPath: string;
+  // Something inside .git changed (index, HEAD, refs)
+  gitChanged: boolean;
+  // Working tree paths that changed, ignored paths excluded
+  paths: string[];
+}
+
 // Terminal types
 export interface Terminal {
   id: string;
@@ -103,6 +116,9 @@ export interface Terminal {
   type: "shell" | "assistant";
 }
 
+// Result of request_microphone_permission
+export type MicrophonePermission = "authorized" | "denied" | "restricted" | "unavailable";
+
 // AI types
 export type AiProviderType = "groq" | "openai" | "claude";
 